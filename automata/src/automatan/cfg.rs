@@ -0,0 +1,597 @@
+use std::borrow::Cow;
+
+use super::*;
+
+use crate::{
+    dual_enum_serde, dual_struct_serde, epsilon,
+    loader::{
+        Context, START_VARIABLE, Spanned,
+        ast::{self, ProductionUnit, TopLevel},
+        codes::Code,
+        log::{Applicability, LogSink},
+    },
+    sigma_upper,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Variable<'a>(pub &'a str);
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VariableInfo {
+    pub definition: Span,
+}
+
+dual_enum_serde! {
+    {#[serde(tag = "type", content = "value")] #[serde(rename_all = "snake_case")]}
+    #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+    pub enum Unit<'a> {
+        Variable(#[serde(borrow)] Variable<'a>),
+        Terminal(#[serde(borrow)] Letter<'a>),
+    }
+}
+
+dual_struct_serde! {
+    #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+    pub struct Production<'a> {
+        #[serde(borrow)]
+        pub body: Vec<Unit<'a>>,
+
+        pub production: Span,
+        pub rule: Span,
+    }
+}
+
+dual_struct_serde! {
+    #[derive(Clone, Debug)]
+    pub struct Cfg<'a> {
+        #[serde(borrow)]
+        pub start: Variable<'a>,
+
+        #[serde(borrow)]
+        pub variables: HashMap<Variable<'a>, VariableInfo>,
+
+        #[serde(borrow)]
+        pub alphabet: HashMap<Letter<'a>, LetterInfo>,
+
+        #[serde(borrow)]
+        pub productions: HashMap<Variable<'a>, Vec<Production<'a>>>,
+    }
+}
+
+impl<'a> Cfg<'a> {
+    pub fn compile(
+        items: impl Iterator<Item = Spanned<ast::TopLevel<'a>>>,
+        ctx: &mut Context<'a>,
+    ) -> Option<Cfg<'a>> {
+        CfgCompiler::new(ctx).compile(items)
+    }
+}
+
+struct Rule<'a> {
+    head: Spanned<ast::ProductionGroup<'a>>,
+    groups: Vec<Spanned<ast::ProductionGroup<'a>>>,
+    rule: Span,
+}
+
+pub struct CfgCompiler<'a, 'b> {
+    ctx: &'b mut Context<'a>,
+
+    start: Option<(Spanned<&'a str>, Span)>,
+
+    variables: HashMap<Variable<'a>, VariableInfo>,
+    variables_def: Option<Span>,
+
+    alphabet: HashMap<Letter<'a>, LetterInfo>,
+    alphabet_def: Option<Span>,
+
+    rules: Vec<Rule<'a>>,
+    productions: HashMap<Variable<'a>, Vec<Production<'a>>>,
+}
+
+impl<'a, 'b> CfgCompiler<'a, 'b> {
+    pub fn new(ctx: &'b mut Context<'a>) -> Self {
+        Self {
+            ctx,
+
+            start: Default::default(),
+            variables: Default::default(),
+            variables_def: Default::default(),
+            alphabet: Default::default(),
+            alphabet_def: Default::default(),
+            rules: Default::default(),
+            productions: Default::default(),
+        }
+    }
+
+    pub fn compile(
+        mut self,
+        items: impl Iterator<Item = Spanned<ast::TopLevel<'a>>>,
+    ) -> Option<Cfg<'a>> {
+        for Spanned(element, span) in items {
+            self.compile_top_level(element, span);
+        }
+
+        let rules = std::mem::take(&mut self.rules);
+
+        let heads: Vec<_> = rules.iter().map(|rule| self.expect_head(rule)).collect();
+
+        // without an explicit set of variables every head is one
+        if self.variables_def.is_none() {
+            for Spanned(head, span) in heads.iter().flatten() {
+                self.variables
+                    .entry(Variable(head))
+                    .or_insert(VariableInfo { definition: *span });
+            }
+        }
+
+        for (rule, head) in rules.iter().zip(&heads) {
+            if let Some(head) = head {
+                self.compile_rule(*head, rule);
+            }
+        }
+
+        if rules.is_empty() {
+            let (at, line) = self.ctx.append_line(&format!("{START_VARIABLE} -> ..."));
+            self.ctx
+                .emit_error_locless("no productions defined")
+                .code(Code::NO_PRODUCTIONS)
+                .help(format!("add: {START_VARIABLE} -> ..."))
+                .suggest(at, line, Applicability::HasPlaceholders);
+        }
+
+        let start = match self.start {
+            Some((start, _)) => Variable(start.0),
+            None => match heads.iter().flatten().next() {
+                Some(head) => Variable(head.0),
+                None => Variable(START_VARIABLE),
+            },
+        };
+        let start_defined = self.variables.contains_key(&start);
+        if !start_defined {
+            let span = self.start.map(|(start, _)| start.1);
+            match span {
+                Some(span) => self
+                    .ctx
+                    .emit_error("start variable not defined as a variable", span)
+                    .code(Code::UNDEFINED_NAME)
+                    .did_you_mean(
+                        span,
+                        start.0,
+                        self.variables.keys().map(|variable| variable.0),
+                    ),
+                None => {
+                    let (at, line) = self.ctx.append_line("start = ...");
+                    self.ctx
+                        .emit_error_locless("start variable not defined")
+                        .code(Code::NEVER_DEFINED)
+                        .help("add: start = ...")
+                        .suggest(at, line, Applicability::HasPlaceholders)
+                }
+            };
+        }
+
+        for (variable, info) in &self.variables {
+            if !self.productions.contains_key(variable) {
+                let (at, line) = self.ctx.append_line(&format!("{} -> ...", variable.0));
+                self.ctx
+                    .emit_warning("variable has no productions", info.definition)
+                    .code(Code::VARIABLE_WITHOUT_PRODUCTIONS)
+                    .help(format!("add: {} -> ...", variable.0))
+                    .suggest(at, line, Applicability::HasPlaceholders);
+            }
+        }
+
+        // even resilient compilation can't build a grammar without one
+        if self.ctx.should_abort() || !start_defined {
+            return None;
+        }
+
+        Some(Cfg {
+            start,
+            variables: self.variables,
+            alphabet: self.alphabet,
+            productions: self.productions,
+        })
+    }
+
+    fn compile_top_level(&mut self, element: TopLevel<'a>, span: Span) {
+        use Spanned as S;
+        use ast::TopLevel as TL;
+        match element {
+            TL::Item(S("V", _), list) => self.compile_variables(list, span),
+            TL::Item(S(sigma_upper!(pat), _), list) => self.compile_alphabet(list, span),
+            TL::Item(S("start", _), item) => self.compile_start(item, span),
+            TL::Item(S(name, dest_s), _) => {
+                self.ctx
+                    .emit_error(
+                        format!("unknown item {name:?}, expected variables | alphabet | start"),
+                        dest_s,
+                    )
+                    .code(Code::UNKNOWN_ITEM)
+                    .did_you_mean(
+                        dest_s,
+                        name,
+                        ["V", "start"].into_iter().chain(sigma_upper!(arr)),
+                    );
+            }
+
+            TL::TransitionFunc(_, _) => {
+                self.ctx
+                    .emit_error("unexpected transition function", span)
+                    .code(Code::UNEXPECTED_FUNCTION);
+            }
+            TL::ProductionRule(head, groups) => self.rules.push(Rule {
+                head,
+                groups: groups.0,
+                rule: span,
+            }),
+            TL::Table(_) => {
+                _ = self
+                    .ctx
+                    .emit_error("unexpected table", span)
+                    .code(Code::UNEXPECTED_FUNCTION)
+            }
+        }
+    }
+
+    fn compile_variables(&mut self, list: Spanned<ast::Item<'a>>, top_level: Span) {
+        if let Some(previous) = self.variables_def {
+            self.ctx
+                .emit_error("variables already set", top_level)
+                .code(Code::ALREADY_SET)
+                .secondary(previous, "previously defined here");
+        }
+        let Some(list) = list.expect_set(self.ctx) else {
+            return;
+        };
+        for item in list {
+            let Some(ident) = item.expect_ident(self.ctx) else {
+                continue;
+            };
+            if let Some(previous) = self
+                .variables
+                .insert(Variable(ident), VariableInfo { definition: item.1 })
+            {
+                self.ctx
+                    .emit_error("variable redefined", item.1)
+                    .code(Code::REDEFINED)
+                    .secondary(previous.definition, "previously defined here");
+            }
+            if let Some(letter) = self.alphabet.get(&Letter(ident)) {
+                self.ctx
+                    .emit_error("variable also defined as a letter", item.1)
+                    .code(Code::NAME_CONFLICT)
+                    .secondary(letter.definition, "letter defined here");
+            }
+        }
+
+        if list.is_empty() {
+            self.ctx
+                .emit_error("variables cannot be empty", top_level)
+                .code(Code::EMPTY_SET);
+        }
+        self.variables_def = Some(top_level);
+    }
+
+    fn compile_alphabet(&mut self, list: Spanned<ast::Item<'a>>, top_level: Span) {
+        if let Some(previous) = self.alphabet_def {
+            self.ctx
+                .emit_error("alphabet already set", top_level)
+                .code(Code::ALREADY_SET)
+                .secondary(previous, "previously defined here");
+        }
+        let Some(list) = list.expect_set(self.ctx) else {
+            return;
+        };
+        for item in list {
+            let Some(ident) = item.expect_ident_or_string(self.ctx) else {
+                continue;
+            };
+
+            if ident.is_empty() {
+                self.ctx
+                    .emit_error("letter cannot be empty", item.1)
+                    .code(Code::INVALID_LETTER);
+                continue;
+            } else if ident.chars().count() != 1 {
+                self.ctx
+                    .emit_error("letter cannot be longer than one char", item.1)
+                    .code(Code::INVALID_LETTER);
+                continue;
+            }
+
+            if let Some(previous) = self
+                .alphabet
+                .insert(Letter(ident), LetterInfo { definition: item.1 })
+            {
+                self.ctx
+                    .emit_error("letter redefined", item.1)
+                    .code(Code::REDEFINED)
+                    .secondary(previous.definition, "previously defined here");
+            }
+            if let Some(variable) = self.variables.get(&Variable(ident)) {
+                self.ctx
+                    .emit_error("letter also defined as a variable", item.1)
+                    .code(Code::NAME_CONFLICT)
+                    .secondary(variable.definition, "variable defined here");
+            }
+        }
+        if list.is_empty() {
+            self.ctx
+                .emit_error("alphabet cannot be empty", top_level)
+                .code(Code::EMPTY_SET);
+        }
+        self.alphabet_def = Some(top_level);
+    }
+
+    fn compile_start(&mut self, item: Spanned<ast::Item<'a>>, top_level: Span) {
+        if let Some((_, previous)) = self.start {
+            self.ctx
+                .emit_error("start variable already set", top_level)
+                .code(Code::ALREADY_SET)
+                .secondary(previous, "previously defined here");
+        }
+        let Some(ident) = item.expect_ident(self.ctx) else {
+            return;
+        };
+        self.start = Some((Spanned(ident, item.1), top_level));
+    }
+
+    fn expect_head(&mut self, rule: &Rule<'a>) -> Option<Spanned<&'a str>> {
+        match &rule.head.0.0[..] {
+            [Spanned(ProductionUnit::Ident(ident), span)] => Some(Spanned(ident, *span)),
+            [Spanned(ProductionUnit::Epsilon(_), span)] => {
+                self.ctx
+                    .emit_error("production head cannot be epsilon", *span)
+                    .code(Code::INVALID_PRODUCTION_HEAD);
+                None
+            }
+            [Spanned(ProductionUnit::String(_), span)] => {
+                self.ctx
+                    .emit_error("production head cannot be a terminal", *span)
+                    .code(Code::INVALID_PRODUCTION_HEAD)
+                    .help("the head of a context free production is a single variable");
+                None
+            }
+            _ => {
+                self.ctx
+                    .emit_error("production head must be a single variable", rule.head.1)
+                    .code(Code::INVALID_PRODUCTION_HEAD)
+                    .note("only context free grammars are supported");
+                None
+            }
+        }
+    }
+
+    fn compile_rule(&mut self, head: Spanned<&'a str>, rule: &Rule<'a>) {
+        if !self.variables.contains_key(&Variable(head.0)) {
+            self.ctx
+                .emit_error("production variable not defined as variable", head.1)
+                .code(Code::UNDEFINED_NAME)
+                .did_you_mean(
+                    head.1,
+                    head.0,
+                    self.variables.keys().map(|variable| variable.0),
+                );
+            return;
+        }
+
+        for group in &rule.groups {
+            if group.0.0.is_empty() {
+                self.ctx
+                    .emit_error("production cannot be empty", group.1)
+                    .code(Code::EMPTY_PRODUCTION)
+                    .help(concat!("use epsilon instead: ", epsilon!(str)));
+                continue;
+            }
+
+            let mut body = Vec::new();
+            let mut resolved = true;
+            for unit in &group.0.0 {
+                match &unit.0 {
+                    ProductionUnit::Epsilon(_) => {
+                        if group.0.0.len() != 1 {
+                            self.ctx
+                                .emit_warning("epsilon in a non empty production", unit.1)
+                                .code(Code::MISPLACED_EPSILON)
+                                .help("it can be removed");
+                        }
+                    }
+                    ProductionUnit::Ident(ident) => {
+                        match self.resolve_ident(Spanned(ident, unit.1)) {
+                            Some(unit) => body.push(unit),
+                            None => resolved = false,
+                        }
+                    }
+                    ProductionUnit::String(string) => {
+                        resolved &= self.resolve_string(Spanned(string, unit.1), &mut body)
+                    }
+                }
+            }
+            if !resolved {
+                continue;
+            }
+
+            let entry = self.productions.entry(Variable(head.0)).or_default();
+            if let Some(previous) = entry.iter().find(|production| production.body == body) {
+                self.ctx
+                    .emit_warning("duplicate production", group.1)
+                    .code(Code::DUPLICATE_PRODUCTION)
+                    .secondary(previous.production, "previously defined here");
+                continue;
+            }
+            entry.push(Production {
+                body,
+                production: group.1,
+                rule: rule.rule,
+            });
+        }
+    }
+
+    fn resolve_ident(&mut self, Spanned(ident, span): Spanned<&'a str>) -> Option<Unit<'a>> {
+        if self.variables.contains_key(&Variable(ident)) {
+            return Some(Unit::Variable(Variable(ident)));
+        }
+        if self.alphabet.contains_key(&Letter(ident)) {
+            return Some(Unit::Terminal(Letter(ident)));
+        }
+        // without an explicit alphabet lowercase letters, digits and the like
+        // are terminals, anything that looks like a variable has to be one
+        let mut chars = ident.chars();
+        if self.alphabet_def.is_none()
+            && let (Some(c), None) = (chars.next(), chars.next())
+            && !c.is_uppercase()
+        {
+            self.alphabet
+                .insert(Letter(ident), LetterInfo { definition: span });
+            return Some(Unit::Terminal(Letter(ident)));
+        }
+
+        let (at, line) = self.ctx.append_line(&format!("{ident} -> ..."));
+        let error = self
+            .ctx
+            .emit_error("undefined variable", span)
+            .code(Code::UNDEFINED_NAME)
+            .did_you_mean(
+                span,
+                ident,
+                self.variables.keys().map(|variable| variable.0),
+            );
+        if self.variables_def.is_some() {
+            error.help(format!("add {ident:?} to: V = {{...}}"));
+        } else {
+            error.help(format!("add: {ident} -> ...")).suggest(
+                at,
+                line,
+                Applicability::HasPlaceholders,
+            );
+        }
+        None
+    }
+
+    fn resolve_string(
+        &mut self,
+        Spanned(string, span): Spanned<&Cow<'a, str>>,
+        body: &mut Vec<Unit<'a>>,
+    ) -> bool {
+        if string.is_empty() {
+            self.ctx
+                .emit_warning("empty terminal string", span)
+                .code(Code::EMPTY_TERMINAL)
+                .help(concat!("use epsilon instead: ", epsilon!(str)));
+        }
+        let mut resolved = true;
+        for (index, c) in string.char_indices() {
+            let letter = match string {
+                Cow::Borrowed(string) => &string[index..index + c.len_utf8()],
+                Cow::Owned(_) => self.ctx.intern(c.encode_utf8(&mut [0; 4])),
+            };
+            if !self.alphabet.contains_key(&Letter(letter)) {
+                if self.alphabet_def.is_some() {
+                    self.ctx
+                        .emit_error(format!("terminal {letter:?} not defined in alphabet"), span)
+                        .code(Code::UNDEFINED_TERMINAL);
+                    resolved = false;
+                    continue;
+                }
+                self.alphabet
+                    .insert(Letter(letter), LetterInfo { definition: span });
+            }
+            body.push(Unit::Terminal(Letter(letter)));
+        }
+        resolved
+    }
+}
+
+#[test]
+fn undefined_variables() {
+    use crate::loader::{Interner, parse_universal};
+
+    fn errors(src: &str) -> (bool, Vec<&str>) {
+        let interner = Interner::default();
+        let mut ctx = Context::new(src, &interner);
+        let compiled = parse_universal(&mut ctx).is_some();
+        let errors: Vec<_> = ctx
+            .logs()
+            .entries()
+            .iter()
+            .filter(|entry| entry.code == Some(Code::UNDEFINED_NAME))
+            .map(|entry| {
+                let span = entry.span.unwrap();
+                &src[span.0..span.1]
+            })
+            .collect();
+        (compiled, errors)
+    }
+
+    // uppercase and longer names are variables even without an alphabet
+    assert_eq!(errors("type = CFG\nS -> a S b | A"), (false, vec!["A"]));
+    assert_eq!(errors("type = CFG\nS -> a S b | ab"), (false, vec!["ab"]));
+    assert_eq!(errors("type = CFG\nS -> a S b | A\nA -> c"), (true, vec![]));
+    // an explicit alphabet doesn't grow
+    assert_eq!(
+        errors("type = CFG\nE = {a, b}\nS -> a S b | c"),
+        (false, vec!["c"])
+    );
+}
+
+#[test]
+fn productions() {
+    use crate::loader::{Interner, Machine, parse_universal};
+
+    let src = "type = CFG\nS -> a S b | ~ | T\nT -> \"cd\" | c ~";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    let Some(Machine::Cfg(cfg)) = parse_universal(&mut ctx) else {
+        panic!("failed to compile");
+    };
+    let bodies = |variable: &str| {
+        cfg.productions[&Variable(variable)]
+            .iter()
+            .map(|production| {
+                production
+                    .body
+                    .iter()
+                    .map(|unit| match unit {
+                        Unit::Variable(variable) => variable.0,
+                        Unit::Terminal(letter) => letter.0,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // each alternative is a production, epsilon alone is the empty one
+    assert_eq!(cfg.start, Variable("S"));
+    assert_eq!(bodies("S"), [vec!["a", "S", "b"], vec![], vec!["T"]]);
+    assert_eq!(bodies("T"), [vec!["c", "d"], vec!["c"]]);
+    assert_eq!(cfg.alphabet.len(), 4);
+
+    // epsilon next to other units is only a warning
+    let codes: Vec<_> = ctx
+        .logs()
+        .entries()
+        .iter()
+        .map(|entry| entry.code)
+        .collect();
+    assert_eq!(codes, [Some(Code::MISPLACED_EPSILON)]);
+
+    // a grammar without productions is an error, not the no_transitions lint
+    let interner = Interner::default();
+    let mut ctx = Context::new("type = CFG", &interner);
+    assert!(parse_universal(&mut ctx).is_none());
+    let codes: Vec<_> = ctx
+        .logs()
+        .entries()
+        .iter()
+        .map(|entry| entry.code)
+        .collect();
+    assert!(codes.contains(&Some(Code::NO_PRODUCTIONS)));
+    assert!(!codes.contains(&Some(Code::NO_TRANSITIONS)));
+}
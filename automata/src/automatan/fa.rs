@@ -5,7 +5,8 @@ use crate::{
     loader::{
         Context, INITIAL_STATE, Spanned,
         ast::{self, Symbol as Sym, TopLevel},
-        log::LogSink,
+        codes::Code,
+        log::{Applicability, LogSink},
     },
     sigma_upper,
 };
@@ -35,11 +36,6 @@ struct Transition<'a> {
     pub state: State<'a>,
 }
 
-struct TransitionInfo {
-    pub transition: Span,
-    pub function: Span,
-}
-
 dual_struct_serde! { {#[serde_with::serde_as]}
     #[derive(Clone, Debug)]
     pub struct Fa<'a> {
@@ -87,6 +83,14 @@ pub struct FaCompiler<'a, 'b> {
     final_states_def: Option<Span>,
 
     transitions: HashMap<TransitionFrom<'a>, HashMap<Transition<'a>, TransitionInfo>>,
+    /// `d(state, *)` transitions, keyed without a letter.
+    otherwise: HashMap<TransitionFrom<'a>, HashMap<Transition<'a>, TransitionInfo>>,
+}
+
+impl<'a, 'b> AsMut<Context<'a>> for FaCompiler<'a, 'b> {
+    fn as_mut(&mut self) -> &mut Context<'a> {
+        self.ctx
+    }
 }
 
 impl<'a, 'b> FaCompiler<'a, 'b> {
@@ -103,6 +107,7 @@ impl<'a, 'b> FaCompiler<'a, 'b> {
             final_states: Default::default(),
             final_states_def: Default::default(),
             transitions: Default::default(),
+            otherwise: Default::default(),
         }
     }
 
@@ -115,53 +120,81 @@ impl<'a, 'b> FaCompiler<'a, 'b> {
         }
 
         if self.states_def.is_none() {
+            let (at, line) = self.ctx.append_line("Q = {...}");
             self.ctx
                 .emit_error_locless("states never defined")
-                .emit_help_logless("add: Q = {...}");
+                .code(Code::NEVER_DEFINED)
+                .help("add: Q = {...}")
+                .suggest(at, line, Applicability::HasPlaceholders);
         }
 
         if self.alphabet_def.is_none() {
+            let (at, line) = self.ctx.append_line("E = {...}");
             self.ctx
                 .emit_error_locless("alphabet never defined")
-                .emit_help_logless("add: E = {...}")
-                .emit_info_logless(concat!("E can be ", sigma_upper!(str)));
+                .code(Code::NEVER_DEFINED)
+                .help("add: E = {...}")
+                .suggest(at, line, Applicability::HasPlaceholders)
+                .note(concat!("E can be ", sigma_upper!(str)));
         }
 
         if self.final_states_def.is_none() {
+            let (at, line) = self.ctx.append_line("F = {...}");
             self.ctx
                 .emit_error_locless("final states never defined")
-                .emit_help_logless("add: F = {...}");
+                .code(Code::NEVER_DEFINED)
+                .help("add: F = {...}")
+                .suggest(at, line, Applicability::HasPlaceholders);
         }
 
         let initial_state = match self.initial_state {
-            Some(some) => some.0,
+            Some(some) => Some(some.0),
             None => {
                 if self.states.contains_key(&State("q0")) {
+                    let (at, line) = self.ctx.append_line(&format!("{INITIAL_STATE} = q0"));
                     self.ctx
                         .emit_warning_locless("initial state not defined, defaulting to 'q0'")
-                        .emit_help_logless(format!("add: {INITIAL_STATE} = q0"));
+                        .code(Code::DEFAULTED)
+                        .help(format!("add: {INITIAL_STATE} = q0"))
+                        .suggest(at, line, Applicability::MachineApplicable);
+                    Some(State("q0"))
                 } else {
+                    let (at, line) = self.ctx.append_line(&format!("{INITIAL_STATE} = ..."));
                     self.ctx
                         .emit_error_locless("initial state not defined")
-                        .emit_help_logless(format!("add: {INITIAL_STATE} = ..."));
+                        .code(Code::NEVER_DEFINED)
+                        .help(format!("add: {INITIAL_STATE} = ..."))
+                        .suggest(at, line, Applicability::HasPlaceholders);
+                    None
                 }
-                State("q0")
             }
         };
 
+        expand_otherwise(
+            self.ctx,
+            std::mem::take(&mut self.otherwise),
+            &mut self.transitions,
+            &self.alphabet,
+            |from, letter| TransitionFrom {
+                letter: Some(letter),
+                ..*from
+            },
+        );
+
         if self.transitions.is_empty() {
             self.ctx
                 .emit_warning_locless("no transitions defined")
-                .emit_help_logless(
-                    "consider defining one: d(state, letter|epsilon) = state | {state, ...}",
-                )
-                .emit_info_logless(concat!("d can be ", delta_lower!(str)))
-                .emit_info_logless(concat!("epsilon can be ", epsilon!(str)));
+                .code(Code::NO_TRANSITIONS)
+                .help("consider defining one: d(state, letter|epsilon) = state | {state, ...}")
+                .note(concat!("d can be ", delta_lower!(str)))
+                .note(concat!("epsilon can be ", epsilon!(str)));
         }
 
-        if self.ctx.contains_errors() {
+        if self.ctx.should_abort() {
             return None;
         }
+        // even resilient compilation can't build a machine without one
+        let initial_state = initial_state?;
 
         Some(Fa {
             initial_state,
@@ -190,32 +223,46 @@ impl<'a, 'b> FaCompiler<'a, 'b> {
     fn compile_top_level(&mut self, element: TopLevel<'a>, span: Span) {
         use Spanned as S;
         use ast::TopLevel as TL;
+        let element = match element {
+            TL::Item(name, item) => match template::expand_item(item, self.ctx) {
+                Some(item) => TL::Item(name, item),
+                None => return,
+            },
+            element => element,
+        };
         match element {
             TL::Item(S("Q", _), list) => self.compile_states(list, span),
             TL::Item(S(sigma_upper!(pat), _), list) => self.compile_alphabet(list, span),
             TL::Item(S("F", _), list) => self.compile_final_states(list, span),
             TL::Item(S(INITIAL_STATE, _), item) => self.compile_initial_state(item, span),
             TL::Item(S(name, dest_s), _) => {
-                self.ctx.emit_error(format!("unknown item {name:?}, expected states | alphabet | final states | initial state"), dest_s);
+                self.ctx.emit_error(format!("unknown item {name:?}, expected states | alphabet | final states | initial state"), dest_s)
+                    .code(Code::UNKNOWN_ITEM)
+                    .did_you_mean(dest_s, name, ["Q", "F", INITIAL_STATE].into_iter().chain(sigma_upper!(arr)));
             }
 
             TL::TransitionFunc(S((S(delta_lower!(pat), _), args), func), list) => {
                 self.compile_transition_function(args, func, list)
             }
-            TL::TransitionFunc(S((S(name, _), _), dest_s), _) => {
-                self.ctx.emit_error(
-                    format!(
-                        "unknown function {name:?}, expected transition function ( {} )",
-                        delta_lower!(str)
-                    ),
-                    dest_s,
-                );
+            TL::TransitionFunc(S((S(name, name_s), _), dest_s), _) => {
+                self.ctx
+                    .emit_error(
+                        format!(
+                            "unknown function {name:?}, expected transition function ( {} )",
+                            delta_lower!(str)
+                        ),
+                        dest_s,
+                    )
+                    .code(Code::UNEXPECTED_FUNCTION)
+                    .did_you_mean(name_s, name, delta_lower!(arr));
             }
 
             TL::ProductionRule(_, _) => {
-                self.ctx.emit_error("unexpected production rule", span);
+                self.ctx
+                    .emit_error("unexpected production rule", span)
+                    .code(Code::UNEXPECTED_FUNCTION);
             }
-            TL::Table() => _ = self.ctx.emit_error("unexpected table", span),
+            TL::Table(table) => compile_table(self, table, Self::compile_transition_function),
         }
     }
 
@@ -223,7 +270,8 @@ impl<'a, 'b> FaCompiler<'a, 'b> {
         if let Some(previous) = self.states_def {
             self.ctx
                 .emit_error("states already set", top_level)
-                .emit_info("previously defined here", previous);
+                .code(Code::ALREADY_SET)
+                .secondary(previous, "previously defined here");
         }
         let Some(list) = list.expect_set(self.ctx) else {
             return;
@@ -238,12 +286,15 @@ impl<'a, 'b> FaCompiler<'a, 'b> {
             {
                 self.ctx
                     .emit_error("state redefined", item.1)
-                    .emit_info("previously defined here", previous.definition);
+                    .code(Code::REDEFINED)
+                    .secondary(previous.definition, "previously defined here");
             }
         }
 
         if list.is_empty() {
-            self.ctx.emit_error("states cannot be empty", top_level);
+            self.ctx
+                .emit_error("states cannot be empty", top_level)
+                .code(Code::EMPTY_SET);
         }
         self.states_def = Some(top_level);
     }
@@ -252,19 +303,27 @@ impl<'a, 'b> FaCompiler<'a, 'b> {
         if let Some(previous) = self.alphabet_def {
             self.ctx
                 .emit_error("alphabet already set", top_level)
-                .emit_info("previously defined here", previous);
+                .code(Code::ALREADY_SET)
+                .secondary(previous, "previously defined here");
         }
         let Some(list) = list.expect_set(self.ctx) else {
             return;
         };
         for item in list {
-            let Some(ident) = item.expect_ident(self.ctx) else {
+            let Some(ident) = item.expect_ident_or_string(self.ctx) else {
                 continue;
             };
 
-            if ident.chars().count() != 1 {
+            if ident.is_empty() {
                 self.ctx
-                    .emit_error("letter cannot be longer than one char", item.1);
+                    .emit_error("letter cannot be empty", item.1)
+                    .code(Code::INVALID_LETTER);
+                continue;
+            } else if ident.chars().count() != 1 {
+                self.ctx
+                    .emit_error("letter cannot be longer than one char", item.1)
+                    .code(Code::INVALID_LETTER);
+                continue;
             }
 
             if let Some(previous) = self
@@ -273,11 +332,14 @@ impl<'a, 'b> FaCompiler<'a, 'b> {
             {
                 self.ctx
                     .emit_error("letter redefined", item.1)
-                    .emit_help("previously defined here", previous.definition);
+                    .code(Code::REDEFINED)
+                    .secondary(previous.definition, "previously defined here");
             }
         }
         if list.is_empty() {
-            self.ctx.emit_error("alphabet cannot be empty", top_level);
+            self.ctx
+                .emit_error("alphabet cannot be empty", top_level)
+                .code(Code::EMPTY_SET);
         }
         self.alphabet_def = Some(top_level);
     }
@@ -286,7 +348,8 @@ impl<'a, 'b> FaCompiler<'a, 'b> {
         if let Some(previous) = self.final_states_def {
             self.ctx
                 .emit_error("final states already set", top_level)
-                .emit_help("previously defined here", previous);
+                .code(Code::ALREADY_SET)
+                .secondary(previous, "previously defined here");
         }
         let Some(list) = list.expect_set(self.ctx) else {
             return;
@@ -301,11 +364,15 @@ impl<'a, 'b> FaCompiler<'a, 'b> {
                     .insert(State(ident), StateInfo { definition: item.1 })
                     .is_some()
                 {
-                    self.ctx.emit_error("final state redefined", item.1);
+                    self.ctx
+                        .emit_error("final state redefined", item.1)
+                        .code(Code::REDEFINED);
                 }
             } else {
                 self.ctx
-                    .emit_error("final state not defined in set of states", item.1);
+                    .emit_error("final state not defined in set of states", item.1)
+                    .code(Code::UNDEFINED_NAME)
+                    .did_you_mean(item.1, ident, self.states.keys().map(|state| state.0));
             }
         }
         self.final_states_def = Some(top_level);
@@ -321,23 +388,51 @@ impl<'a, 'b> FaCompiler<'a, 'b> {
                 if let Some((_, previous)) = self.initial_state {
                     self.ctx
                         .emit_error("initial state already set", top_level)
-                        .emit_help("previously defined here", previous);
+                        .code(Code::ALREADY_SET)
+                        .secondary(previous, "previously defined here");
                 }
                 if self.states.contains_key(&State(ident)) {
                     self.initial_state = Some((State(ident), top_level))
                 } else {
                     self.ctx
-                        .emit_error("initial state symbol not defined as a state", src_d);
+                        .emit_error("initial state symbol not defined as a state", src_d)
+                        .code(Code::UNDEFINED_NAME)
+                        .did_you_mean(src_d, ident, self.states.keys().map(|state| state.0));
                 }
             }
-            _ => _ = self.ctx.emit_error("expected ident", src_d),
+            _ => {
+                _ = self
+                    .ctx
+                    .emit_error("expected ident", src_d)
+                    .code(Code::WRONG_ITEM)
+            }
         }
     }
+
     fn compile_transition_function(
         &mut self,
         args: Spanned<ast::Tuple<'a>>,
         function: Span,
         list: Spanned<ast::Item<'a>>,
+    ) {
+        let candidates = [
+            self.states.keys().map(|state| state.0).collect(),
+            self.alphabet.keys().map(|letter| letter.0).collect(),
+        ];
+        let mark = self.ctx.logs_mark();
+        for (args, list) in template::expand_transition(args, list, &candidates, self.ctx) {
+            for args in args.expand_sets(self.ctx) {
+                self.compile_transition(args, function, list.clone());
+            }
+        }
+        self.ctx.dedup_logs_since(mark);
+    }
+
+    fn compile_transition(
+        &mut self,
+        args: Spanned<ast::Tuple<'a>>,
+        function: Span,
+        list: Spanned<ast::Item<'a>>,
     ) {
         let list = list.set_weak();
         let Some((state, letter)) = args.as_ref().expect_fa_transition_function(self.ctx) else {
@@ -345,21 +440,33 @@ impl<'a, 'b> FaCompiler<'a, 'b> {
         };
         if !self.states.contains_key(&State(state.0)) {
             self.ctx
-                .emit_error("transition state not defined as state", state.1);
+                .emit_error("transition state not defined as state", state.1)
+                .code(Code::UNDEFINED_STATE)
+                .did_you_mean(state.1, state.0, self.states.keys().map(|state| state.0));
             return;
         };
 
+        let letter_span = letter.1;
+        let from = format!("({}, {})", state.0, letter.0.name());
+        let otherwise = matches!(letter.0, Sym::Otherwise(_));
         let letter: Option<Letter<'_>> = match letter.0 {
             Sym::Epsilon(_) => {
                 if !self.options.epsilon_moves {
-                    self.ctx.emit_error("epsilon moves not permitted", letter.1);
+                    self.ctx
+                        .emit_error("epsilon moves not permitted", letter.1)
+                        .code(Code::EPSILON_MOVE);
+                    return;
                 }
                 None
             }
+            Sym::Otherwise(_) => None,
             Sym::Ident(val) => {
                 if !self.alphabet.contains_key(&Letter(val)) {
                     self.ctx
-                        .emit_error("transition letter not defined in alphabet", letter.1);
+                        .emit_error("transition letter not defined in alphabet", letter.1)
+                        .code(Code::UNDEFINED_LETTER)
+                        .did_you_mean(letter.1, val, self.alphabet.keys().map(|letter| letter.0));
+                    return;
                 }
                 Some(Letter(val))
             }
@@ -373,12 +480,22 @@ impl<'a, 'b> FaCompiler<'a, 'b> {
 
             if !self.states.contains_key(&State(next_state.0)) {
                 self.ctx
-                    .emit_error("transition state not defined as state", next_state.1);
+                    .emit_error("transition state not defined as state", next_state.1)
+                    .code(Code::UNDEFINED_STATE)
+                    .did_you_mean(
+                        next_state.1,
+                        next_state.0,
+                        self.states.keys().map(|state| state.0),
+                    );
                 continue;
             };
 
-            let entry: &mut _ = self
-                .transitions
+            let transitions = if otherwise {
+                &mut self.otherwise
+            } else {
+                &mut self.transitions
+            };
+            let entry: &mut _ = transitions
                 .entry(TransitionFrom {
                     letter,
                     state: State(state.0),
@@ -387,8 +504,18 @@ impl<'a, 'b> FaCompiler<'a, 'b> {
             if let Some(entry) = entry.iter().next()
                 && !self.options.non_deterministic
             {
-                self.ctx.emit_error("transition already defined for this starting point (non determinism not permitted)", item.1)
-                            .emit_info("previously defined here", entry.1.transition);
+                self.ctx
+                    .emit_error(
+                        format!(
+                            "transition already defined for {from} (non determinism not permitted)"
+                        ),
+                        item.1,
+                    )
+                    .code(Code::NON_DETERMINISM)
+                    .label("conflicts here")
+                    .secondary(entry.1.transition, "first defined here")
+                    .secondary(letter_span, "starting from this letter");
+                continue;
             }
             if let Some(previous) = entry.insert(
                 Transition {
@@ -401,7 +528,8 @@ impl<'a, 'b> FaCompiler<'a, 'b> {
             ) {
                 self.ctx
                     .emit_warning("duplicate transition", item.1)
-                    .emit_info("previously defined here", previous.transition);
+                    .code(Code::DUPLICATE_TRANSITION)
+                    .secondary(previous.transition, "previously defined here");
             }
         }
     }
@@ -415,17 +543,129 @@ impl<'a> Spanned<&ast::Tuple<'a>> {
         match &self.0.0[..] {
             [
                 Spanned(ast::Item::Symbol(ast::Symbol::Ident(state)), state_span),
-                Spanned(ast::Item::Symbol(letter), letter_span),
-            ] => {
-                return Some((Spanned(state, *state_span), Spanned(*letter, *letter_span)));
+                Spanned(letter, letter_span),
+            ] if let Some(letter) = letter.as_symbol(ctx) => {
+                return Some((Spanned(state, *state_span), Spanned(letter, *letter_span)));
             }
             _ => {
-                _ = ctx.emit_error(
-                    "expected FA transition function (state, letter|epsilon)",
-                    self.1,
-                )
+                _ = ctx
+                    .emit_error(
+                        "expected FA transition function (state, letter|epsilon|{letter, ...})",
+                        self.1,
+                    )
+                    .code(Code::MALFORMED_TRANSITION)
             }
         }
         None
     }
 }
+
+#[test]
+fn letter_sets() {
+    use crate::loader::{Interner, Machine, parse_universal};
+
+    let src = "type = DFA
+Q = {q0, q1}
+E = {a..e}
+F = {q1}
+d(q0, {a..e}) = q1
+d(q1, {a, b, \"c\", d, e}) = q0";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    let Some(Machine::Fa(fa)) = parse_universal(&mut ctx) else {
+        panic!("failed to compile");
+    };
+    assert_eq!(fa.alphabet.len(), 5);
+    assert_eq!(fa.transitions.len(), 10);
+
+    let src = "type = DFA\nQ = {q0}\nE = {a, b}\nF = {q0}\nd(q0, {a, z}) = q0\nd(q0, b) = q0\nd(q0, {b}) = q0";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    assert!(parse_universal(&mut ctx).is_none());
+    let entries = ctx.logs().entries();
+    assert_eq!(&src[entries[0].span.unwrap().0..][..1], "z");
+    let conflict = &entries[1];
+    assert_eq!(conflict.label.as_deref(), Some("conflicts here"));
+    assert_eq!(conflict.labels[0].message, "first defined here");
+    let first = conflict.labels[0].span;
+    assert_eq!(src[..first.0].lines().count(), 6);
+    let starting = conflict.labels[1].span;
+    assert_eq!(starting.0, src.rfind('b').unwrap());
+
+    // both transitions are shown in one snippet
+    let rendered = ctx.logs_display().nth(1).unwrap().to_string();
+    assert!(rendered.contains("first defined here"));
+    assert!(rendered.contains("conflicts here"));
+    assert_eq!(rendered.matches("6: ").count(), 1);
+
+    // an empty set defines nothing, which is reported where it's written
+    let src = "type = DFA\nQ = {q0}\nE = {a}\nF = {q0}\nq0 = q0\nd(q0, {}) = q0\nd(q0, a) = q0";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    assert!(parse_universal(&mut ctx).is_none());
+    let errors: Vec<_> = ctx
+        .logs()
+        .entries()
+        .iter()
+        .map(|entry| {
+            (
+                entry.code,
+                &src[entry.span.unwrap().0..entry.span.unwrap().1],
+            )
+        })
+        .collect();
+    assert_eq!(errors, [(Some(Code::EMPTY_SET), "{}")]);
+}
+
+#[test]
+fn expansions_report_once() {
+    use crate::loader::{Interner, parse_universal};
+
+    let src = "type = NFA\nQ = {q0, q1}\nE = {a, b, c}\nF = {q1}\nd(q0, {a..c}) = {q1, q2}\nd(q1, a) = q2";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner).resilient();
+    parse_universal(&mut ctx);
+    let undefined = ctx
+        .logs()
+        .entries()
+        .iter()
+        .filter(|entry| entry.code == Some(Code::UNDEFINED_STATE))
+        .map(|entry| src[..entry.span.unwrap().0].lines().count())
+        .collect::<Vec<_>>();
+    assert_eq!(undefined, [5, 6]);
+}
+
+#[test]
+fn otherwise() {
+    use crate::loader::{Interner, Machine, parse_universal};
+
+    let interner = Interner::default();
+    let compile = |src| {
+        let mut ctx = Context::new(src, &interner);
+        let Some(Machine::Fa(fa)) = parse_universal(&mut ctx) else {
+            panic!("failed to compile");
+        };
+        let codes: Vec<_> = ctx.logs().entries().iter().map(|e| e.code).collect();
+        (fa, codes)
+    };
+
+    // explicit transitions win, every other letter takes the otherwise one
+    let (fa, codes) = compile(
+        "type = DFA\nQ = {q0, q1}\nE = {a, b, c}\nF = {q1}\nd(q0, a) = q0\nd(q0, *) = q1\nd(q1, *) = q1",
+    );
+    assert!(!codes.contains(&Some(Code::OTHERWISE_NEVER_APPLIES)));
+    assert_eq!(fa.transitions.len(), 6);
+    for (from, to) in &fa.transitions {
+        let expected = match (from.state.0, from.letter.unwrap().0) {
+            ("q0", "a") => "q0",
+            _ => "q1",
+        };
+        assert_eq!(to.len(), 1);
+        assert_eq!(to[0].state.0, expected);
+    }
+
+    let (fa, codes) =
+        compile("type = DFA\nQ = {q0}\nE = {a, b}\nF = {q0}\nd(q0, {a, b}) = q0\nd(q0, *) = q0");
+    assert!(codes.contains(&Some(Code::OTHERWISE_NEVER_APPLIES)));
+    assert_eq!(fa.transitions.len(), 2);
+}
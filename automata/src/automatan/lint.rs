@@ -0,0 +1,458 @@
+//! Warnings about compiled machines which are valid but likely not what was
+//! meant, like states which can never be entered.
+//!
+//! Every warning is a [`Lint`] with a name, which `allow = {...}` and
+//! `deny = {...}` items use to silence it or turn it into an error. The lints
+//! in this module only run on machines which compiled without errors.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    str::FromStr,
+};
+
+use super::*;
+
+use crate::loader::{
+    Span,
+    codes::Code,
+    log::{LogEntry, LogSink},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Lint {
+    /// A list mixing comma and space delimited items.
+    InconsistentCommas,
+    /// An item which wasn't defined and got a default value.
+    Defaults,
+    /// A machine without transitions.
+    NoTransitions,
+    /// The same transition defined twice.
+    DuplicateTransitions,
+    /// An otherwise transition on a state with transitions for every letter.
+    OtherwiseNeverApplies,
+    /// A template argument which doesn't match any defined name.
+    UnmatchedTemplates,
+    /// A CFG variable without productions.
+    VariablesWithoutProductions,
+    /// Epsilon next to other units of a production.
+    MisplacedEpsilon,
+    /// The same production defined twice.
+    DuplicateProductions,
+    /// An empty terminal string.
+    EmptyTerminals,
+    /// A state no transition path from the initial state leads to.
+    UnreachableStates,
+    /// A reachable state from which no final state can be reached.
+    DeadStates,
+    /// An alphabet letter no transition reads.
+    UnusedLetters,
+    /// A stack or tape symbol no transition reads or writes.
+    UnusedSymbols,
+    /// A final state no transition path from the initial state leads to.
+    UnreachableFinalStates,
+    /// A DFA state without a transition for some letter.
+    NonTotalDfa,
+    /// A TM state which isn't final but has no transitions, so the machine
+    /// rejects once it gets there.
+    StuckStates,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 17] = [
+        Lint::InconsistentCommas,
+        Lint::Defaults,
+        Lint::NoTransitions,
+        Lint::DuplicateTransitions,
+        Lint::OtherwiseNeverApplies,
+        Lint::UnmatchedTemplates,
+        Lint::VariablesWithoutProductions,
+        Lint::MisplacedEpsilon,
+        Lint::DuplicateProductions,
+        Lint::EmptyTerminals,
+        Lint::UnreachableStates,
+        Lint::DeadStates,
+        Lint::UnusedLetters,
+        Lint::UnusedSymbols,
+        Lint::UnreachableFinalStates,
+        Lint::NonTotalDfa,
+        Lint::StuckStates,
+    ];
+
+    /// The identifier of the lint, like `unreachable_states`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::InconsistentCommas => "inconsistent_commas",
+            Lint::Defaults => "defaults",
+            Lint::NoTransitions => "no_transitions",
+            Lint::DuplicateTransitions => "duplicate_transitions",
+            Lint::OtherwiseNeverApplies => "otherwise_never_applies",
+            Lint::UnmatchedTemplates => "unmatched_templates",
+            Lint::VariablesWithoutProductions => "variables_without_productions",
+            Lint::MisplacedEpsilon => "misplaced_epsilon",
+            Lint::DuplicateProductions => "duplicate_productions",
+            Lint::EmptyTerminals => "empty_terminals",
+            Lint::UnreachableStates => "unreachable_states",
+            Lint::DeadStates => "dead_states",
+            Lint::UnusedLetters => "unused_letters",
+            Lint::UnusedSymbols => "unused_symbols",
+            Lint::UnreachableFinalStates => "unreachable_final_states",
+            Lint::NonTotalDfa => "non_total_dfa",
+            Lint::StuckStates => "stuck_states",
+        }
+    }
+
+    pub fn code(self) -> Code {
+        match self {
+            Lint::InconsistentCommas => Code::INCONSISTENT_COMMAS,
+            Lint::Defaults => Code::DEFAULTED,
+            Lint::NoTransitions => Code::NO_TRANSITIONS,
+            Lint::DuplicateTransitions => Code::DUPLICATE_TRANSITION,
+            Lint::OtherwiseNeverApplies => Code::OTHERWISE_NEVER_APPLIES,
+            Lint::UnmatchedTemplates => Code::UNMATCHED_TEMPLATE,
+            Lint::VariablesWithoutProductions => Code::VARIABLE_WITHOUT_PRODUCTIONS,
+            Lint::MisplacedEpsilon => Code::MISPLACED_EPSILON,
+            Lint::DuplicateProductions => Code::DUPLICATE_PRODUCTION,
+            Lint::EmptyTerminals => Code::EMPTY_TERMINAL,
+            Lint::UnreachableStates => Code::UNREACHABLE_STATE,
+            Lint::DeadStates => Code::DEAD_STATE,
+            Lint::UnusedLetters => Code::UNUSED_LETTER,
+            Lint::UnusedSymbols => Code::UNUSED_SYMBOL,
+            Lint::UnreachableFinalStates => Code::UNREACHABLE_FINAL_STATE,
+            Lint::NonTotalDfa => Code::NON_TOTAL_DFA,
+            Lint::StuckStates => Code::STUCK_STATE,
+        }
+    }
+
+    /// The lint reporting entries with `code`, if it's the code of a warning.
+    pub fn from_code(code: Code) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.code() == code)
+    }
+
+    fn emit(self, sink: &mut impl LogSink, msg: impl Into<String>, span: Span) -> &mut LogEntry {
+        sink.emit_warning(msg, span)
+            .code(self.code())
+            .note(format!("reported by the {} lint", self.name()))
+    }
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UnknownLint;
+
+impl Display for UnknownLint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown lint")
+    }
+}
+
+impl FromStr for Lint {
+    type Err = UnknownLint;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Lint::ALL
+            .into_iter()
+            .find(|lint| lint.name() == s)
+            .ok_or(UnknownLint)
+    }
+}
+
+/// Lints a finite automaton, `total` asks for a transition on every letter
+/// from every state like a DFA should have.
+pub fn fa(fa: &fa::Fa, total: bool, sink: &mut impl LogSink) {
+    states(
+        sink,
+        fa.initial_state,
+        &fa.states,
+        Some(&fa.final_states),
+        fa.transitions
+            .iter()
+            .flat_map(|(from, to)| to.iter().map(|to| (from.state, to.state))),
+    );
+    letters(
+        sink,
+        &fa.alphabet,
+        fa.transitions.keys().filter_map(|from| from.letter),
+    );
+
+    if total {
+        let mut letters: Vec<_> = fa.alphabet.keys().collect();
+        letters.sort_by_key(|letter| letter.0);
+        for (state, info) in sorted(&fa.states) {
+            let missing: Vec<_> = letters
+                .iter()
+                .filter(|letter| {
+                    !fa.transitions.contains_key(&fa::TransitionFrom {
+                        state,
+                        letter: Some(***letter),
+                    })
+                })
+                .map(|letter| format!("{:?}", letter.0))
+                .collect();
+            if !missing.is_empty() {
+                Lint::NonTotalDfa
+                    .emit(
+                        sink,
+                        "DFA transition function is not total",
+                        info.definition,
+                    )
+                    .label(format!("no transition for {}", missing.join(", ")))
+                    .help("add the missing transitions, or an otherwise transition");
+            }
+        }
+    }
+}
+
+/// Lints the finite automaton of a regex, only its letters come from the
+/// source so only they are checked.
+pub fn regex(fa: &fa::Fa, sink: &mut impl LogSink) {
+    letters(
+        sink,
+        &fa.alphabet,
+        fa.transitions.keys().filter_map(|from| from.letter),
+    );
+}
+
+pub fn pda(pda: &pda::Pda, sink: &mut impl LogSink) {
+    states(
+        sink,
+        pda.initial_state,
+        &pda.states,
+        pda.final_states.as_ref(),
+        pda.transitions
+            .iter()
+            .flat_map(|(from, to)| to.iter().map(|to| (from.state, to.state))),
+    );
+    letters(
+        sink,
+        &pda.alphabet,
+        pda.transitions.keys().filter_map(|from| from.letter),
+    );
+    symbols(
+        sink,
+        &pda.symbols,
+        std::iter::once(pda.initial_stack)
+            .chain(pda.transitions.keys().map(|from| from.symbol))
+            .chain(
+                pda.transitions
+                    .values()
+                    .flatten()
+                    .flat_map(|to| to.stack.iter().copied()),
+            ),
+        "stack",
+    );
+}
+
+pub fn tm(tm: &tm::Tm, sink: &mut impl LogSink) {
+    states(
+        sink,
+        tm.initial_state,
+        &tm.states,
+        Some(&tm.final_states),
+        tm.transitions
+            .iter()
+            .flat_map(|(from, to)| to.iter().map(|to| (from.state, to.state))),
+    );
+    symbols(
+        sink,
+        &tm.symbols,
+        std::iter::once(tm.blank_symbol)
+            .chain(tm.transitions.keys().map(|from| from.symbol))
+            .chain(tm.transitions.values().flatten().map(|to| to.symbol)),
+        "tape",
+    );
+
+    let moving: HashSet<_> = tm.transitions.keys().map(|from| from.state).collect();
+    for (state, info) in sorted(&tm.states) {
+        if !moving.contains(&state) && !tm.final_states.contains_key(&state) {
+            Lint::StuckStates
+                .emit(sink, "non final state has no transitions", info.definition)
+                .note("the machine halts and rejects when it enters this state");
+        }
+    }
+}
+
+/// Reachability lints over the states, `final_states` is `None` for machines
+/// accepting by something else, like a PDA accepting by empty stack.
+fn states<'a>(
+    sink: &mut impl LogSink,
+    initial_state: State<'a>,
+    states: &HashMap<State<'a>, StateInfo>,
+    final_states: Option<&HashMap<State<'a>, StateInfo>>,
+    edges: impl Iterator<Item = (State<'a>, State<'a>)>,
+) {
+    let mut forward: HashMap<_, Vec<_>> = HashMap::new();
+    let mut backward: HashMap<_, Vec<_>> = HashMap::new();
+    for (from, to) in edges {
+        forward.entry(from).or_default().push(to);
+        backward.entry(to).or_default().push(from);
+    }
+
+    let reachable = closure([initial_state], &forward);
+    for (state, info) in sorted(states) {
+        if reachable.contains(&state) {
+            continue;
+        }
+        match final_states.and_then(|final_states| final_states.get(&state)) {
+            Some(final_info) => {
+                Lint::UnreachableFinalStates
+                    .emit(sink, "final state is unreachable", info.definition)
+                    .secondary(final_info.definition, "marked final here")
+                    .note("the machine never accepts by entering it");
+            }
+            None => {
+                Lint::UnreachableStates
+                    .emit(sink, "state is unreachable", info.definition)
+                    .note("no transitions lead to it from the initial state");
+            }
+        }
+    }
+
+    // without final states every state is dead, which is reported elsewhere
+    let Some(final_states) = final_states.filter(|final_states| !final_states.is_empty()) else {
+        return;
+    };
+    let live = closure(final_states.keys().copied(), &backward);
+    for (state, info) in sorted(states) {
+        if reachable.contains(&state) && !live.contains(&state) {
+            Lint::DeadStates
+                .emit(sink, "dead state", info.definition)
+                .note("no final state can be reached from it");
+        }
+    }
+}
+
+fn letters<'a>(
+    sink: &mut impl LogSink,
+    alphabet: &HashMap<Letter<'a>, LetterInfo>,
+    used: impl Iterator<Item = Letter<'a>>,
+) {
+    let used: HashSet<_> = used.collect();
+    let mut unused: Vec<_> = alphabet
+        .iter()
+        .filter(|(letter, _)| !used.contains(*letter))
+        .map(|(_, info)| info.definition)
+        .collect();
+    unused.sort_by_key(|span| (span.2.0, span.0));
+    for span in unused {
+        Lint::UnusedLetters
+            .emit(sink, "letter is never used", span)
+            .note("no transition reads it");
+    }
+}
+
+fn symbols<'a>(
+    sink: &mut impl LogSink,
+    symbols: &HashMap<Symbol<'a>, SymbolInfo>,
+    used: impl Iterator<Item = Symbol<'a>>,
+    kind: &str,
+) {
+    let used: HashSet<_> = used.collect();
+    let mut unused: Vec<_> = symbols
+        .iter()
+        .filter(|(symbol, _)| !used.contains(*symbol))
+        .map(|(_, info)| info.definition)
+        .collect();
+    unused.sort_by_key(|span| (span.2.0, span.0));
+    for span in unused {
+        Lint::UnusedSymbols
+            .emit(sink, format!("{kind} symbol is never used"), span)
+            .note("no transition reads or writes it");
+    }
+}
+
+/// The states reachable from `start` along `edges`, including `start`.
+fn closure<'a>(
+    start: impl IntoIterator<Item = State<'a>>,
+    edges: &HashMap<State<'a>, Vec<State<'a>>>,
+) -> HashSet<State<'a>> {
+    let mut seen: HashSet<_> = start.into_iter().collect();
+    let mut stack: Vec<_> = seen.iter().copied().collect();
+    while let Some(state) = stack.pop() {
+        for next in edges.get(&state).into_iter().flatten() {
+            if seen.insert(*next) {
+                stack.push(*next);
+            }
+        }
+    }
+    seen
+}
+
+/// The states in source order, so lints are reported deterministically.
+fn sorted<'a, 'm>(
+    states: &'m HashMap<State<'a>, StateInfo>,
+) -> impl Iterator<Item = (State<'a>, &'m StateInfo)> {
+    let mut states: Vec<_> = states.iter().map(|(state, info)| (*state, info)).collect();
+    states.sort_by_key(|(_, info)| (info.definition.2.0, info.definition.0));
+    states.into_iter()
+}
+
+#[test]
+fn lints() {
+    use crate::loader::{Context, Interner, parse_universal};
+
+    let linted = |src: &str| {
+        let interner = Interner::default();
+        let mut ctx = Context::new(src, &interner);
+        assert!(parse_universal(&mut ctx).is_some());
+        ctx.logs()
+            .entries()
+            .iter()
+            .filter_map(|entry| {
+                let lint = Lint::ALL
+                    .into_iter()
+                    .find(|lint| entry.code == Some(lint.code()))?;
+                let Span(start, end, _) = entry.span?;
+                Some(format!("{} {}", lint.name(), &src[start..end]))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let dfa = "type = DFA
+Q = {q0, q1, q2, q3, trap}
+E = {a, b, c}
+F = {q1, q3}
+q0 = q0
+d(q0, a) = q1
+d(q1, a) = q0
+d(q0, b) = trap
+d(trap, {a, b}) = trap
+d(q2, a) = q1";
+    assert_eq!(
+        linted(dfa),
+        [
+            "unreachable_states q2",
+            "unreachable_final_states q3",
+            "dead_states trap",
+            "unused_letters c",
+            "non_total_dfa q0",
+            "non_total_dfa q1",
+            "non_total_dfa q2",
+            "non_total_dfa q3",
+            "non_total_dfa trap",
+        ]
+    );
+    assert!(
+        linted(&dfa.replace("DFA", "NFA"))
+            .iter()
+            .all(|lint| !lint.starts_with("non_total"))
+    );
+
+    let tm = "type = TM
+Q = {q0, q1, acc}
+T = {B, a, x}
+F = {acc}
+q0 = q0
+B = B
+d(q0, a) = (q1, a, R)
+d(q0, B) = (acc, B, ~)";
+    assert_eq!(
+        linted(tm),
+        ["dead_states q1", "unused_symbols x", "stuck_states q1"]
+    );
+}
@@ -1,9 +1,22 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, hash::Hash};
 
-use crate::loader::Span;
+use crate::{
+    delta_lower,
+    loader::{
+        Context, Span, Spanned,
+        ast::{Item, Table, Tuple},
+        codes::Code,
+        log::LogSink,
+    },
+};
 
+pub mod cfg;
 pub mod fa;
+pub mod lint;
+pub mod owned;
 pub mod pda;
+pub mod regex;
+pub mod template;
 pub mod tm;
 
 #[derive(Clone, Copy, Debug)]
@@ -53,3 +66,70 @@ pub struct SymbolInfo {
 pub struct LetterInfo {
     pub definition: Span,
 }
+
+/// Where a transition of a machine being compiled was defined.
+#[derive(Clone)]
+struct TransitionInfo {
+    pub transition: Span,
+    pub function: Span,
+}
+
+/// Compiles every cell of a transition table with `transition`, the way the
+/// equivalent `d(row, column) = cell` line would be.
+fn compile_table<'a, C: AsMut<Context<'a>>>(
+    compiler: &mut C,
+    table: Table<'a>,
+    mut transition: impl FnMut(&mut C, Spanned<Tuple<'a>>, Span, Spanned<Item<'a>>),
+) {
+    if !matches!(table.name.0, delta_lower!(pat)) {
+        compiler
+            .as_mut()
+            .emit_error(
+                format!(
+                    "unknown function {:?}, expected transition function ( {} )",
+                    table.name.0,
+                    delta_lower!(str)
+                ),
+                table.name.1,
+            )
+            .code(Code::UNEXPECTED_FUNCTION)
+            .did_you_mean(table.name.1, table.name.0, delta_lower!(arr));
+        return;
+    }
+    for (args, function, list) in table.transitions() {
+        transition(compiler, args, function, list);
+    }
+}
+
+/// Gives every letter without a transition from where a `d(state, *, ...)`
+/// transition starts that transition, so the compiled machine only has
+/// explicit ones. `on` is the start of a transition on the given letter.
+fn expand_otherwise<'a, From: Eq + Hash, To: Clone + Eq + Hash>(
+    ctx: &mut Context<'a>,
+    otherwise: HashMap<From, HashMap<To, TransitionInfo>>,
+    transitions: &mut HashMap<From, HashMap<To, TransitionInfo>>,
+    alphabet: &HashMap<Letter<'a>, LetterInfo>,
+    on: impl Fn(&From, Letter<'a>) -> From,
+) {
+    for (from, fallback) in otherwise {
+        if fallback.is_empty() {
+            continue;
+        }
+        let mut applies = false;
+        for letter in alphabet.keys() {
+            let entry = transitions.entry(on(&from, *letter)).or_default();
+            if entry.is_empty() {
+                entry.extend(fallback.clone());
+                applies = true;
+            }
+        }
+        if !applies {
+            let info = fallback.values().next().unwrap();
+            ctx.emit_warning(
+                "otherwise transition never applies, every letter already has a transition",
+                info.function,
+            )
+            .code(Code::OTHERWISE_NEVER_APPLIES);
+        }
+    }
+}
@@ -0,0 +1,540 @@
+//! Counterparts of the compiled machines which own their names instead of
+//! borrowing them from the source, so they can be cached, sent between
+//! threads or deserialized from any JSON.
+//!
+//! They serialize the same as the borrowed machines. Convert with `From` and
+//! back with `borrowed` to use the analyses and simulators on them.
+
+use std::collections::HashMap;
+
+use crate::{dual_enum_serde, dual_struct_serde};
+
+use super::{LetterInfo, StateInfo, SymbolInfo};
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct State(pub String);
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Symbol(pub String);
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Letter(pub String);
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Variable(pub String);
+
+macro_rules! names {
+    ($($Owned:ident => $Borrowed:path),* $(,)?) => {
+        $(
+            impl From<$Borrowed> for $Owned {
+                fn from(value: $Borrowed) -> Self {
+                    Self(value.0.to_string())
+                }
+            }
+
+            impl $Owned {
+                pub fn borrowed(&self) -> $Borrowed {
+                    $Borrowed(&self.0)
+                }
+            }
+        )*
+    };
+}
+
+names! {
+    State => super::State<'_>,
+    Symbol => super::Symbol<'_>,
+    Letter => super::Letter<'_>,
+    Variable => super::cfg::Variable<'_>,
+}
+
+/// Converts the keys and values of `map` into a map of another kind.
+fn convert<'m, K: 'm, V: 'm, K2: Eq + std::hash::Hash, V2>(
+    map: &'m HashMap<K, V>,
+    key: impl Fn(&'m K) -> K2,
+    value: impl Fn(&'m V) -> V2,
+) -> HashMap<K2, V2> {
+    map.iter().map(|(k, v)| (key(k), value(v))).collect()
+}
+
+pub mod fa {
+    use super::*;
+    use crate::automatan::fa;
+
+    dual_struct_serde! {
+        #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+        pub struct TransitionFrom {
+            pub state: State,
+            pub letter: Option<Letter>,
+        }
+    }
+
+    dual_struct_serde! {
+        #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+        pub struct TransitionTo {
+            pub state: State,
+
+            pub transition: crate::loader::Span,
+            pub function: crate::loader::Span,
+        }
+    }
+
+    dual_struct_serde! { {#[serde_with::serde_as]}
+        #[derive(Clone, Debug)]
+        pub struct Fa {
+            pub initial_state: State,
+            pub states: HashMap<State, StateInfo>,
+            pub alphabet: HashMap<Letter, LetterInfo>,
+            pub final_states: HashMap<State, StateInfo>,
+
+            #[serde_as(as = "serde_with::Seq<(_, _)>")]
+            pub transitions: HashMap<TransitionFrom, Vec<TransitionTo>>,
+        }
+    }
+
+    impl From<&fa::Fa<'_>> for Fa {
+        fn from(fa: &fa::Fa<'_>) -> Self {
+            Self {
+                initial_state: fa.initial_state.into(),
+                states: convert(&fa.states, |&k| k.into(), Clone::clone),
+                alphabet: convert(&fa.alphabet, |&k| k.into(), Clone::clone),
+                final_states: convert(&fa.final_states, |&k| k.into(), Clone::clone),
+                transitions: convert(
+                    &fa.transitions,
+                    |from| TransitionFrom {
+                        state: from.state.into(),
+                        letter: from.letter.map(Into::into),
+                    },
+                    |to| {
+                        to.iter()
+                            .map(|to| TransitionTo {
+                                state: to.state.into(),
+                                transition: to.transition,
+                                function: to.function,
+                            })
+                            .collect()
+                    },
+                ),
+            }
+        }
+    }
+
+    impl Fa {
+        pub fn borrowed(&self) -> fa::Fa<'_> {
+            fa::Fa {
+                initial_state: self.initial_state.borrowed(),
+                states: convert(&self.states, State::borrowed, Clone::clone),
+                alphabet: convert(&self.alphabet, Letter::borrowed, Clone::clone),
+                final_states: convert(&self.final_states, State::borrowed, Clone::clone),
+                transitions: convert(
+                    &self.transitions,
+                    |from| fa::TransitionFrom {
+                        state: from.state.borrowed(),
+                        letter: from.letter.as_ref().map(Letter::borrowed),
+                    },
+                    |to| {
+                        to.iter()
+                            .map(|to| fa::TransitionTo {
+                                state: to.state.borrowed(),
+                                transition: to.transition,
+                                function: to.function,
+                            })
+                            .collect()
+                    },
+                ),
+            }
+        }
+    }
+}
+
+pub mod pda {
+    use super::*;
+    use crate::automatan::pda;
+
+    dual_struct_serde! {
+        #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+        pub struct TransitionFrom {
+            pub state: State,
+            pub letter: Option<Letter>,
+            pub symbol: Symbol,
+        }
+    }
+
+    dual_struct_serde! {
+        #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+        pub struct TransitionTo {
+            pub state: State,
+            pub stack: Vec<Symbol>,
+
+            pub transition: crate::loader::Span,
+            pub function: crate::loader::Span,
+        }
+    }
+
+    dual_struct_serde! { {#[serde_with::serde_as]}
+        #[derive(Clone, Debug)]
+        pub struct Pda {
+            pub initial_state: State,
+            pub initial_stack: Symbol,
+            pub states: HashMap<State, StateInfo>,
+            pub symbols: HashMap<Symbol, SymbolInfo>,
+            pub alphabet: HashMap<Letter, LetterInfo>,
+            pub final_states: Option<HashMap<State, StateInfo>>,
+
+            #[serde_as(as = "serde_with::Seq<(_, _)>")]
+            pub transitions: HashMap<TransitionFrom, Vec<TransitionTo>>,
+        }
+    }
+
+    impl From<&pda::Pda<'_>> for Pda {
+        fn from(pda: &pda::Pda<'_>) -> Self {
+            Self {
+                initial_state: pda.initial_state.into(),
+                initial_stack: pda.initial_stack.into(),
+                states: convert(&pda.states, |&k| k.into(), Clone::clone),
+                symbols: convert(&pda.symbols, |&k| k.into(), Clone::clone),
+                alphabet: convert(&pda.alphabet, |&k| k.into(), Clone::clone),
+                final_states: pda
+                    .final_states
+                    .as_ref()
+                    .map(|states| convert(states, |&k| k.into(), Clone::clone)),
+                transitions: convert(
+                    &pda.transitions,
+                    |from| TransitionFrom {
+                        state: from.state.into(),
+                        letter: from.letter.map(Into::into),
+                        symbol: from.symbol.into(),
+                    },
+                    |to| {
+                        to.iter()
+                            .map(|to| TransitionTo {
+                                state: to.state.into(),
+                                stack: to.stack.iter().map(|&symbol| symbol.into()).collect(),
+                                transition: to.transition,
+                                function: to.function,
+                            })
+                            .collect()
+                    },
+                ),
+            }
+        }
+    }
+
+    impl Pda {
+        pub fn borrowed(&self) -> pda::Pda<'_> {
+            pda::Pda {
+                initial_state: self.initial_state.borrowed(),
+                initial_stack: self.initial_stack.borrowed(),
+                states: convert(&self.states, State::borrowed, Clone::clone),
+                symbols: convert(&self.symbols, Symbol::borrowed, Clone::clone),
+                alphabet: convert(&self.alphabet, Letter::borrowed, Clone::clone),
+                final_states: self
+                    .final_states
+                    .as_ref()
+                    .map(|states| convert(states, State::borrowed, Clone::clone)),
+                transitions: convert(
+                    &self.transitions,
+                    |from| pda::TransitionFrom {
+                        state: from.state.borrowed(),
+                        letter: from.letter.as_ref().map(Letter::borrowed),
+                        symbol: from.symbol.borrowed(),
+                    },
+                    |to| {
+                        to.iter()
+                            .map(|to| pda::TransitionTo {
+                                state: to.state.borrowed(),
+                                stack: to.stack.iter().map(Symbol::borrowed).collect(),
+                                transition: to.transition,
+                                function: to.function,
+                            })
+                            .collect()
+                    },
+                ),
+            }
+        }
+    }
+}
+
+pub mod tm {
+    use super::*;
+    use crate::automatan::tm::{self, Direction};
+
+    dual_struct_serde! {
+        #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+        pub struct TransitionFrom {
+            pub state: State,
+            pub symbol: Symbol,
+        }
+    }
+
+    dual_struct_serde! {
+        #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+        pub struct TransitionTo {
+            pub state: State,
+            pub symbol: Symbol,
+            pub direction: Direction,
+
+            pub transition: crate::loader::Span,
+            pub function: crate::loader::Span,
+        }
+    }
+
+    dual_struct_serde! { {#[serde_with::serde_as]}
+        #[derive(Clone, Debug)]
+        pub struct Tm {
+            pub initial_state: State,
+            pub blank_symbol: Symbol,
+            pub states: HashMap<State, StateInfo>,
+            pub symbols: HashMap<Symbol, SymbolInfo>,
+            pub final_states: HashMap<State, StateInfo>,
+
+            #[serde_as(as = "serde_with::Seq<(_, _)>")]
+            pub transitions: HashMap<TransitionFrom, Vec<TransitionTo>>,
+        }
+    }
+
+    impl From<&tm::Tm<'_>> for Tm {
+        fn from(tm: &tm::Tm<'_>) -> Self {
+            Self {
+                initial_state: tm.initial_state.into(),
+                blank_symbol: tm.blank_symbol.into(),
+                states: convert(&tm.states, |&k| k.into(), Clone::clone),
+                symbols: convert(&tm.symbols, |&k| k.into(), Clone::clone),
+                final_states: convert(&tm.final_states, |&k| k.into(), Clone::clone),
+                transitions: convert(
+                    &tm.transitions,
+                    |from| TransitionFrom {
+                        state: from.state.into(),
+                        symbol: from.symbol.into(),
+                    },
+                    |to| {
+                        to.iter()
+                            .map(|to| TransitionTo {
+                                state: to.state.into(),
+                                symbol: to.symbol.into(),
+                                direction: to.direction,
+                                transition: to.transition,
+                                function: to.function,
+                            })
+                            .collect()
+                    },
+                ),
+            }
+        }
+    }
+
+    impl Tm {
+        pub fn borrowed(&self) -> tm::Tm<'_> {
+            tm::Tm {
+                initial_state: self.initial_state.borrowed(),
+                blank_symbol: self.blank_symbol.borrowed(),
+                states: convert(&self.states, State::borrowed, Clone::clone),
+                symbols: convert(&self.symbols, Symbol::borrowed, Clone::clone),
+                final_states: convert(&self.final_states, State::borrowed, Clone::clone),
+                transitions: convert(
+                    &self.transitions,
+                    |from| tm::TransitionFrom {
+                        state: from.state.borrowed(),
+                        symbol: from.symbol.borrowed(),
+                    },
+                    |to| {
+                        to.iter()
+                            .map(|to| tm::TransitionTo {
+                                state: to.state.borrowed(),
+                                symbol: to.symbol.borrowed(),
+                                direction: to.direction,
+                                transition: to.transition,
+                                function: to.function,
+                            })
+                            .collect()
+                    },
+                ),
+            }
+        }
+    }
+}
+
+pub mod cfg {
+    use super::*;
+    use crate::automatan::cfg::{self, VariableInfo};
+
+    dual_enum_serde! {
+        {#[serde(tag = "type", content = "value")] #[serde(rename_all = "snake_case")]}
+        #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+        pub enum Unit {
+            Variable(Variable),
+            Terminal(Letter),
+        }
+    }
+
+    dual_struct_serde! {
+        #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+        pub struct Production {
+            pub body: Vec<Unit>,
+
+            pub production: crate::loader::Span,
+            pub rule: crate::loader::Span,
+        }
+    }
+
+    dual_struct_serde! {
+        #[derive(Clone, Debug)]
+        pub struct Cfg {
+            pub start: Variable,
+            pub variables: HashMap<Variable, VariableInfo>,
+            pub alphabet: HashMap<Letter, LetterInfo>,
+            pub productions: HashMap<Variable, Vec<Production>>,
+        }
+    }
+
+    impl From<&cfg::Cfg<'_>> for Cfg {
+        fn from(cfg: &cfg::Cfg<'_>) -> Self {
+            Self {
+                start: cfg.start.into(),
+                variables: convert(&cfg.variables, |&k| k.into(), Clone::clone),
+                alphabet: convert(&cfg.alphabet, |&k| k.into(), Clone::clone),
+                productions: convert(
+                    &cfg.productions,
+                    |&k| k.into(),
+                    |productions| {
+                        productions
+                            .iter()
+                            .map(|production| Production {
+                                body: production
+                                    .body
+                                    .iter()
+                                    .map(|unit| match *unit {
+                                        cfg::Unit::Variable(variable) => {
+                                            Unit::Variable(variable.into())
+                                        }
+                                        cfg::Unit::Terminal(letter) => {
+                                            Unit::Terminal(letter.into())
+                                        }
+                                    })
+                                    .collect(),
+                                production: production.production,
+                                rule: production.rule,
+                            })
+                            .collect()
+                    },
+                ),
+            }
+        }
+    }
+
+    impl Cfg {
+        pub fn borrowed(&self) -> cfg::Cfg<'_> {
+            cfg::Cfg {
+                start: self.start.borrowed(),
+                variables: convert(&self.variables, Variable::borrowed, Clone::clone),
+                alphabet: convert(&self.alphabet, Letter::borrowed, Clone::clone),
+                productions: convert(&self.productions, Variable::borrowed, |productions| {
+                    productions
+                        .iter()
+                        .map(|production| cfg::Production {
+                            body: production
+                                .body
+                                .iter()
+                                .map(|unit| match unit {
+                                    Unit::Variable(variable) => {
+                                        cfg::Unit::Variable(variable.borrowed())
+                                    }
+                                    Unit::Terminal(letter) => {
+                                        cfg::Unit::Terminal(letter.borrowed())
+                                    }
+                                })
+                                .collect(),
+                            production: production.production,
+                            rule: production.rule,
+                        })
+                        .collect()
+                }),
+            }
+        }
+    }
+}
+
+dual_enum_serde! {
+    {#[serde(tag = "type")] #[serde(rename_all = "snake_case")]}
+    #[derive(Clone, Debug)]
+    pub enum Machine {
+        Fa(fa::Fa),
+        Pda(pda::Pda),
+        Tm(tm::Tm),
+        Cfg(cfg::Cfg),
+    }
+}
+
+impl From<&crate::loader::Machine<'_>> for Machine {
+    fn from(machine: &crate::loader::Machine<'_>) -> Self {
+        use crate::loader::Machine as Borrowed;
+        match machine {
+            Borrowed::Fa(fa) => Machine::Fa(fa.into()),
+            Borrowed::Pda(pda) => Machine::Pda(pda.into()),
+            Borrowed::Tm(tm) => Machine::Tm(tm.into()),
+            Borrowed::Cfg(cfg) => Machine::Cfg(cfg.into()),
+        }
+    }
+}
+
+impl Machine {
+    pub fn borrowed(&self) -> crate::loader::Machine<'_> {
+        use crate::loader::Machine as Borrowed;
+        match self {
+            Machine::Fa(fa) => Borrowed::Fa(fa.borrowed()),
+            Machine::Pda(pda) => Borrowed::Pda(pda.borrowed()),
+            Machine::Tm(tm) => Borrowed::Tm(tm.borrowed()),
+            Machine::Cfg(cfg) => Borrowed::Cfg(cfg.borrowed()),
+        }
+    }
+}
+
+#[test]
+fn owned_machines() {
+    use crate::loader::{Context, Interner, parse_universal};
+
+    let src = "type = DFA\nQ = {q0, q1}\nE = {a, b}\nF = {q1}\nq0 = q0\nd(q0, {a, b}) = q1\nd(q1, {a, b}) = q1";
+    let owned = {
+        let interner = Interner::default();
+        let mut ctx = Context::new(src, &interner);
+        Machine::from(&parse_universal(&mut ctx).unwrap())
+    };
+    // nothing is borrowed from the source any more
+    let owned = std::thread::spawn(move || owned).join().unwrap();
+    let crate::loader::Machine::Fa(fa) = owned.borrowed() else {
+        panic!("expected a finite automaton");
+    };
+    assert_eq!(fa.initial_state, super::State("q0"));
+    assert_eq!(fa.transitions.len(), 4);
+
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(&owned).unwrap();
+        // escaped names can't be borrowed from the JSON
+        let json = json.replace("\"q1\"", "\"q\\u0031\"");
+        let Machine::Fa(fa) = serde_json::from_str::<Machine>(&json).unwrap() else {
+            panic!("expected a finite automaton");
+        };
+        assert!(fa.final_states.contains_key(&State("q1".into())));
+    }
+}
@@ -5,7 +5,8 @@ use crate::{
     loader::{
         Context, INITIAL_STACK, INITIAL_STATE, Spanned,
         ast::{self, Symbol as Sym},
-        log::LogSink,
+        codes::Code,
+        log::{Applicability, LogSink},
     },
     sigma_upper,
 };
@@ -41,11 +42,6 @@ struct Transition<'a> {
     pub stack: Vec<Symbol<'a>>,
 }
 
-struct TransitionInfo {
-    pub transition: Span,
-    pub function: Span,
-}
-
 dual_struct_serde! { {#[serde_with::serde_as]}
     #[derive(Clone, Debug)]
     pub struct Pda<'a> {
@@ -96,6 +92,8 @@ pub struct PdaCompiler<'a, 'b> {
     final_states_def: Option<Span>,
 
     transitions: HashMap<TransitionFrom<'a>, HashMap<Transition<'a>, TransitionInfo>>,
+    /// `d(state, *)` transitions, keyed without a letter.
+    otherwise: HashMap<TransitionFrom<'a>, HashMap<Transition<'a>, TransitionInfo>>,
 }
 
 impl<'a> Pda<'a> {
@@ -120,6 +118,12 @@ macro_rules! accept_final {
     };
 }
 
+impl<'a, 'b> AsMut<Context<'a>> for PdaCompiler<'a, 'b> {
+    fn as_mut(&mut self) -> &mut Context<'a> {
+        self.ctx
+    }
+}
+
 impl<'a, 'b> PdaCompiler<'a, 'b> {
     pub fn new(ctx: &'b mut Context<'a>, options: Options) -> Self {
         Self {
@@ -138,6 +142,7 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
             final_states: Default::default(),
             final_states_def: Default::default(),
             transitions: Default::default(),
+            otherwise: Default::default(),
         }
     }
 
@@ -150,34 +155,46 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
         }
 
         if self.states_def.is_none() {
+            let (at, line) = self.ctx.append_line("Q = {...}");
             self.ctx
                 .emit_error_locless("states never defined")
-                .emit_help_logless("add: Q = {...}");
+                .code(Code::NEVER_DEFINED)
+                .help("add: Q = {...}")
+                .suggest(at, line, Applicability::HasPlaceholders);
         }
 
         if self.alphabet_def.is_none() {
+            let (at, line) = self.ctx.append_line("E = {...}");
             self.ctx
                 .emit_error_locless("alphabet never defined")
-                .emit_help_logless("add: E = {...}")
-                .emit_info_logless(concat!("E can be ", sigma_upper!(str)));
+                .code(Code::NEVER_DEFINED)
+                .help("add: E = {...}")
+                .suggest(at, line, Applicability::HasPlaceholders)
+                .note(concat!("E can be ", sigma_upper!(str)));
         }
 
         if self.symbols_def.is_none() {
+            let (at, line) = self.ctx.append_line("G = {...}");
             self.ctx
                 .emit_error_locless("stack symbols never defined")
-                .emit_help_logless("add: G = {...}")
-                .emit_info_logless(concat!("G can be ", gamma_upper!(str)));
+                .code(Code::NEVER_DEFINED)
+                .help("add: G = {...}")
+                .suggest(at, line, Applicability::HasPlaceholders)
+                .note(concat!("G can be ", gamma_upper!(str)));
         }
 
         if self.accept_by.is_none() {
+            let (at, line) = self.ctx.append_line("accept = N|F");
             self.ctx
                 .emit_error_locless("accept by never defined")
-                .emit_help_logless("add: accept = N|F")
-                .emit_info_logless(concat!(
+                .code(Code::NEVER_DEFINED)
+                .help("add: accept = N|F")
+                .suggest(at, line, Applicability::HasPlaceholders)
+                .note(concat!(
                     "accept by empty stack N can be ",
                     accept_empty!(str)
                 ))
-                .emit_info_logless(concat!(
+                .note(concat!(
                     "accept by final state F can be ",
                     accept_final!(str)
                 ));
@@ -186,65 +203,125 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
         if self.final_states_def.is_none()
             && matches!(self.accept_by, Some((AcceptBy::FinalState, _)))
         {
+            let (at, line) = self.ctx.append_line("F = {...}");
             self.ctx
                 .emit_error_locless("final states never defined")
-                .emit_help_logless("add: F = {...}");
+                .code(Code::NEVER_DEFINED)
+                .help("add: F = {...}")
+                .suggest(at, line, Applicability::HasPlaceholders);
         } else if let (Some((AcceptBy::EmptyStack, empty)), Some(states)) =
             (self.accept_by, self.final_states_def)
         {
+            let (states_line, empty_line) =
+                (self.ctx.whole_lines(states), self.ctx.whole_lines(empty));
             self.ctx
                 .emit_error_locless("final states defined alongside accept by empty stack")
-                .emit_help("either remote to accept by empty stack", states)
-                .emit_help("or remote to accept by final state", empty);
+                .code(Code::INVALID_ACCEPT_BY)
+                .secondary(states, "either remove to accept by empty stack")
+                .suggest(states_line, "", Applicability::MaybeIncorrect)
+                .secondary(empty, "or remove to accept by final state")
+                .suggest(empty_line, "", Applicability::MaybeIncorrect);
         }
 
         let initial_state = match self.initial_state {
-            Some(some) => some.0,
+            Some(some) => Some(some.0),
             None => {
                 if self.states.contains_key(&State("q0")) {
+                    let (at, line) = self.ctx.append_line(&format!("{INITIAL_STATE} = q0"));
                     self.ctx
                         .emit_warning_locless("initial state not defined, defaulting to 'q0'")
-                        .emit_help_logless(format!("add: {INITIAL_STATE} = q0"));
+                        .code(Code::DEFAULTED)
+                        .help(format!("add: {INITIAL_STATE} = q0"))
+                        .suggest(at, line, Applicability::MachineApplicable);
+                    Some(State("q0"))
                 } else {
+                    let (at, line) = self.ctx.append_line(&format!("{INITIAL_STATE} = ..."));
                     self.ctx
                         .emit_error_locless("initial state not defined")
-                        .emit_help_logless(format!("add: {INITIAL_STATE} = ..."));
+                        .code(Code::NEVER_DEFINED)
+                        .help(format!("add: {INITIAL_STATE} = ..."))
+                        .suggest(at, line, Applicability::HasPlaceholders);
+                    None
                 }
-                State("q0")
             }
         };
 
         let initial_stack = match self.initial_stack {
-            Some(some) => some.0,
+            Some(some) => Some(some.0),
             None => {
                 if self.symbols.contains_key(&Symbol("Z0")) {
+                    let (at, line) = self.ctx.append_line(&format!("{INITIAL_STACK} = Z0"));
                     self.ctx
                         .emit_warning_locless(
                             "initial stack symbol not defined, defaulting to 'Z0'",
                         )
-                        .emit_help_logless(format!("add: {INITIAL_STACK} = Z0"));
+                        .code(Code::DEFAULTED)
+                        .help(format!("add: {INITIAL_STACK} = Z0"))
+                        .suggest(at, line, Applicability::MachineApplicable);
+                    Some(Symbol("Z0"))
                 } else {
+                    let (at, line) = self.ctx.append_line(&format!("{INITIAL_STACK} = ..."));
                     self.ctx
                         .emit_error_locless("initial stack symbol not defined")
-                        .emit_help_logless(format!("add: {INITIAL_STACK} = ..."));
+                        .code(Code::NEVER_DEFINED)
+                        .help(format!("add: {INITIAL_STACK} = ..."))
+                        .suggest(at, line, Applicability::HasPlaceholders);
+                    None
                 }
-                Symbol("Z0")
             }
         };
 
+        // a deterministic machine always takes an epsilon move, so there are no
+        // letters left for an otherwise transition from the same place
+        let mut otherwise = std::mem::take(&mut self.otherwise);
+        if !self.options.non_deterministic {
+            otherwise.retain(|from, fallback| {
+                let Some(epsilon) = self
+                    .transitions
+                    .get(from)
+                    .and_then(|epsilon| epsilon.values().next())
+                else {
+                    return true;
+                };
+                if let Some(info) = fallback.values().next() {
+                    self.ctx
+                        .emit_warning(
+                            "otherwise transition never applies, the epsilon move is taken instead",
+                            info.transition,
+                        )
+                        .code(Code::OTHERWISE_NEVER_APPLIES)
+                        .secondary(epsilon.transition, "epsilon move defined here");
+                }
+                false
+            });
+        }
+        expand_otherwise(
+            self.ctx,
+            otherwise,
+            &mut self.transitions,
+            &self.alphabet,
+            |from, letter| TransitionFrom {
+                letter: Some(letter),
+                ..from.clone()
+            },
+        );
+
         if self.transitions.is_empty() {
             self.ctx
                 .emit_warning_locless("no transitions defined")
-                .emit_help_logless(
-                    "consider defining one: d(state, letter|epsilon, symbol) = (state, [symbol]) | {(state, [symbol]), ...}",
-                )
-                .emit_info_logless(concat!("d can be ", delta_lower!(str)))
-                .emit_info_logless(concat!("epsilon can be ", epsilon!(str)));
+                .code(Code::NO_TRANSITIONS)
+                .help("consider defining one: d(state, letter|epsilon, symbol) = (state, [symbol]) | {(state, [symbol]), ...}")
+                .note(concat!("d can be ", delta_lower!(str)))
+                .note(concat!("epsilon can be ", epsilon!(str)));
         }
 
-        if self.ctx.contains_errors() {
+        if self.ctx.should_abort() {
             return None;
         }
+        // even resilient compilation can't build a machine without these
+        let (Some(initial_state), Some(initial_stack)) = (initial_state, initial_stack) else {
+            return None;
+        };
 
         let final_states =
             matches!(self.accept_by, Some((AcceptBy::FinalState, _))).then_some(self.final_states);
@@ -279,6 +356,13 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
     fn compile_top_level(&mut self, element: ast::TopLevel<'a>, span: Span) {
         use Spanned as S;
         use ast::TopLevel as TL;
+        let element = match element {
+            TL::Item(name, item) => match template::expand_item(item, self.ctx) {
+                Some(item) => TL::Item(name, item),
+                None => return,
+            },
+            element => element,
+        };
         match element {
             TL::Item(S("accept", _), item) => self.compile_accept_by(item, span),
             TL::Item(S("Q", _), list) => self.compile_states(list, span),
@@ -288,26 +372,36 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
             TL::Item(S(INITIAL_STATE, _), item) => self.compile_initial_state(item, span),
             TL::Item(S(INITIAL_STACK, _), item) => self.compile_initial_stack(item, span),
             TL::Item(S(name, dest_s), _) => {
-                self.ctx.emit_error(format!("unknown item {name:?}, expected states | stack symbols | alphabet | accept by | final states | initial state | initial stack"), dest_s);
+                self.ctx.emit_error(format!("unknown item {name:?}, expected states | stack symbols | alphabet | accept by | final states | initial state | initial stack"), dest_s)
+                    .code(Code::UNKNOWN_ITEM)
+                    .did_you_mean(dest_s, name, ["accept", "Q", "F", INITIAL_STATE, INITIAL_STACK]
+                            .into_iter()
+                            .chain(gamma_upper!(arr))
+                            .chain(sigma_upper!(arr)));
             }
 
             TL::TransitionFunc(S((S(delta_lower!(pat), _), args), func), list) => {
                 self.compile_transition_function(args, func, list)
             }
-            TL::TransitionFunc(S((S(name, _), _), dest_s), _) => {
-                self.ctx.emit_error(
-                    format!(
-                        "unknown function {name:?}, expected transition function ( {} )",
-                        delta_lower!(str)
-                    ),
-                    dest_s,
-                );
+            TL::TransitionFunc(S((S(name, name_s), _), dest_s), _) => {
+                self.ctx
+                    .emit_error(
+                        format!(
+                            "unknown function {name:?}, expected transition function ( {} )",
+                            delta_lower!(str)
+                        ),
+                        dest_s,
+                    )
+                    .code(Code::UNEXPECTED_FUNCTION)
+                    .did_you_mean(name_s, name, delta_lower!(arr));
             }
 
             TL::ProductionRule(_, _) => {
-                self.ctx.emit_error("unexpected production rule", span);
+                self.ctx
+                    .emit_error("unexpected production rule", span)
+                    .code(Code::UNEXPECTED_FUNCTION);
             }
-            TL::Table() => _ = self.ctx.emit_error("unexpected table", span),
+            TL::Table(table) => compile_table(self, table, Self::compile_transition_function),
         }
     }
 
@@ -315,7 +409,8 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
         if let Some((_, previous)) = self.accept_by {
             self.ctx
                 .emit_error("accept by already set", top_level)
-                .emit_info("previously defined here", previous);
+                .code(Code::ALREADY_SET)
+                .secondary(previous, "previously defined here");
         }
         let Some(by) = item.expect_ident(self.ctx) else {
             return;
@@ -325,7 +420,9 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
             accept_empty!(pat) => AcceptBy::EmptyStack,
             accept_final!(pat) => AcceptBy::FinalState,
             _ => {
-                self.ctx.emit_error("invalid accept by", item.1);
+                self.ctx
+                    .emit_error("invalid accept by", item.1)
+                    .code(Code::INVALID_ACCEPT_BY);
                 return;
             }
         };
@@ -337,7 +434,8 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
         if let Some(previous) = self.states_def {
             self.ctx
                 .emit_error("states already set", top_level)
-                .emit_info("previously defined here", previous);
+                .code(Code::ALREADY_SET)
+                .secondary(previous, "previously defined here");
         }
         let Some(list) = list.expect_set(self.ctx) else {
             return;
@@ -352,12 +450,15 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
             {
                 self.ctx
                     .emit_error("state redefined", item.1)
-                    .emit_info("previously defined here", previous.definition);
+                    .code(Code::REDEFINED)
+                    .secondary(previous.definition, "previously defined here");
             }
         }
 
         if list.is_empty() {
-            self.ctx.emit_error("states cannot be empty", top_level);
+            self.ctx
+                .emit_error("states cannot be empty", top_level)
+                .code(Code::EMPTY_SET);
         }
         self.states_def = Some(top_level);
     }
@@ -366,13 +467,14 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
         if let Some(previous) = self.symbols_def {
             self.ctx
                 .emit_error("stack symbols already set", top_level)
-                .emit_info("previously defined here", previous);
+                .code(Code::ALREADY_SET)
+                .secondary(previous, "previously defined here");
         }
         let Some(list) = list.expect_set(self.ctx) else {
             return;
         };
         for item in list {
-            let Some(ident) = item.expect_ident(self.ctx) else {
+            let Some(ident) = item.expect_ident_or_string(self.ctx) else {
                 continue;
             };
             if let Some(previous) = self
@@ -381,12 +483,15 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
             {
                 self.ctx
                     .emit_error("stack symbol redefined", item.1)
-                    .emit_info("previously defined here", previous.definition);
+                    .code(Code::REDEFINED)
+                    .secondary(previous.definition, "previously defined here");
             }
         }
 
         if list.is_empty() {
-            self.ctx.emit_error("states cannot be empty", top_level);
+            self.ctx
+                .emit_error("states cannot be empty", top_level)
+                .code(Code::EMPTY_SET);
         }
         self.symbols_def = Some(top_level);
     }
@@ -395,19 +500,27 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
         if let Some(previous) = self.alphabet_def {
             self.ctx
                 .emit_error("alphabet already set", top_level)
-                .emit_info("previously defined here", previous);
+                .code(Code::ALREADY_SET)
+                .secondary(previous, "previously defined here");
         }
         let Some(list) = list.expect_set(self.ctx) else {
             return;
         };
         for item in list {
-            let Some(ident) = item.expect_ident(self.ctx) else {
+            let Some(ident) = item.expect_ident_or_string(self.ctx) else {
                 continue;
             };
 
-            if ident.chars().count() != 1 {
+            if ident.is_empty() {
                 self.ctx
-                    .emit_error("letter cannot be longer than one char", item.1);
+                    .emit_error("letter cannot be empty", item.1)
+                    .code(Code::INVALID_LETTER);
+                continue;
+            } else if ident.chars().count() != 1 {
+                self.ctx
+                    .emit_error("letter cannot be longer than one char", item.1)
+                    .code(Code::INVALID_LETTER);
+                continue;
             }
 
             if let Some(previous) = self
@@ -416,11 +529,14 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
             {
                 self.ctx
                     .emit_error("letter redefined", item.1)
-                    .emit_help("previously defined here", previous.definition);
+                    .code(Code::REDEFINED)
+                    .secondary(previous.definition, "previously defined here");
             }
         }
         if list.is_empty() {
-            self.ctx.emit_error("alphabet cannot be empty", top_level);
+            self.ctx
+                .emit_error("alphabet cannot be empty", top_level)
+                .code(Code::EMPTY_SET);
         }
         self.alphabet_def = Some(top_level);
     }
@@ -429,7 +545,8 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
         if let Some(previous) = self.final_states_def {
             self.ctx
                 .emit_error("final states already set", top_level)
-                .emit_help("previously defined here", previous);
+                .code(Code::ALREADY_SET)
+                .secondary(previous, "previously defined here");
         }
         let Some(list) = list.expect_set(self.ctx) else {
             return;
@@ -444,11 +561,15 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
                     .insert(State(ident), StateInfo { definition: item.1 })
                     .is_some()
                 {
-                    self.ctx.emit_error("final state redefined", item.1);
+                    self.ctx
+                        .emit_error("final state redefined", item.1)
+                        .code(Code::REDEFINED);
                 }
             } else {
                 self.ctx
-                    .emit_error("final state not defined in set of states", item.1);
+                    .emit_error("final state not defined in set of states", item.1)
+                    .code(Code::UNDEFINED_NAME)
+                    .did_you_mean(item.1, ident, self.states.keys().map(|state| state.0));
             }
         }
         self.final_states_def = Some(top_level);
@@ -464,16 +585,24 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
                 if let Some((_, previous)) = self.initial_state {
                     self.ctx
                         .emit_error("initial state already set", top_level)
-                        .emit_help("previously defined here", previous);
+                        .code(Code::ALREADY_SET)
+                        .secondary(previous, "previously defined here");
                 }
                 if self.states.contains_key(&State(ident)) {
                     self.initial_state = Some((State(ident), top_level))
                 } else {
                     self.ctx
-                        .emit_error("initial state symbol not defined as a state", src_d);
+                        .emit_error("initial state symbol not defined as a state", src_d)
+                        .code(Code::UNDEFINED_NAME)
+                        .did_you_mean(src_d, ident, self.states.keys().map(|state| state.0));
                 }
             }
-            _ => _ = self.ctx.emit_error("expected ident", src_d),
+            _ => {
+                _ = self
+                    .ctx
+                    .emit_error("expected ident", src_d)
+                    .code(Code::WRONG_ITEM)
+            }
         }
     }
 
@@ -482,21 +611,29 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
         Spanned(src, src_d): Spanned<ast::Item<'a>>,
         top_level: Span,
     ) {
-        match src {
-            ast::Item::Symbol(Sym::Ident(ident)) => {
+        match src.as_symbol(self.ctx) {
+            Some(Sym::Ident(ident)) => {
                 if let Some((_, previous)) = self.initial_stack {
                     self.ctx
                         .emit_error("initial stack symbol already set", top_level)
-                        .emit_help("previously defined here", previous);
+                        .code(Code::ALREADY_SET)
+                        .secondary(previous, "previously defined here");
                 }
                 if self.symbols.contains_key(&Symbol(ident)) {
                     self.initial_stack = Some((Symbol(ident), top_level))
                 } else {
                     self.ctx
-                        .emit_error("initial stack symbol not defined as a state", src_d);
+                        .emit_error("initial stack symbol not defined as a state", src_d)
+                        .code(Code::UNDEFINED_NAME)
+                        .did_you_mean(src_d, ident, self.symbols.keys().map(|symbol| symbol.0));
                 }
             }
-            _ => _ = self.ctx.emit_error("expected ident", src_d),
+            _ => {
+                _ = self
+                    .ctx
+                    .emit_error("expected ident or string", src_d)
+                    .code(Code::WRONG_ITEM)
+            }
         }
     }
 
@@ -505,6 +642,26 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
         args: Spanned<ast::Tuple<'a>>,
         function: Span,
         list: Spanned<ast::Item<'a>>,
+    ) {
+        let candidates = [
+            self.states.keys().map(|state| state.0).collect(),
+            self.alphabet.keys().map(|letter| letter.0).collect(),
+            self.symbols.keys().map(|symbol| symbol.0).collect(),
+        ];
+        let mark = self.ctx.logs_mark();
+        for (args, list) in template::expand_transition(args, list, &candidates, self.ctx) {
+            for args in args.expand_sets(self.ctx) {
+                self.compile_transition(args, function, list.clone());
+            }
+        }
+        self.ctx.dedup_logs_since(mark);
+    }
+
+    fn compile_transition(
+        &mut self,
+        args: Spanned<ast::Tuple<'a>>,
+        function: Span,
+        list: Spanned<ast::Item<'a>>,
     ) {
         let list = list.set_weak();
         let Some((state, letter, stack_symbol)) =
@@ -514,28 +671,42 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
         };
         if !self.states.contains_key(&State(state.0)) {
             self.ctx
-                .emit_error("transition state not defined as state", state.1);
+                .emit_error("transition state not defined as state", state.1)
+                .code(Code::UNDEFINED_STATE)
+                .did_you_mean(state.1, state.0, self.states.keys().map(|state| state.0));
             return;
         };
         if !self.symbols.contains_key(&Symbol(stack_symbol.0)) {
-            self.ctx.emit_error(
-                "transition stack symbol not defined as stack symbol",
-                stack_symbol.1,
-            );
+            self.ctx
+                .emit_error(
+                    "transition stack symbol not defined as stack symbol",
+                    stack_symbol.1,
+                )
+                .code(Code::UNDEFINED_SYMBOL);
             return;
         };
 
+        let letter_span = letter.1;
+        let from = format!("({}, {}, {})", state.0, letter.0.name(), stack_symbol.0);
+        let otherwise = matches!(letter.0, Sym::Otherwise(_));
         let letter: Option<Letter<'_>> = match letter.0 {
             Sym::Epsilon(_) => {
                 if !self.options.epsilon_moves {
-                    self.ctx.emit_error("epsilon moves not permitted", letter.1);
+                    self.ctx
+                        .emit_error("epsilon moves not permitted", letter.1)
+                        .code(Code::EPSILON_MOVE);
+                    return;
                 }
                 None
             }
+            Sym::Otherwise(_) => None,
             Sym::Ident(val) => {
                 if !self.alphabet.contains_key(&Letter(val)) {
                     self.ctx
-                        .emit_error("transition letter not defined in alphabet", letter.1);
+                        .emit_error("transition letter not defined in alphabet", letter.1)
+                        .code(Code::UNDEFINED_LETTER)
+                        .did_you_mean(letter.1, val, self.alphabet.keys().map(|letter| letter.0));
+                    return;
                 }
                 Some(Letter(val))
             }
@@ -551,10 +722,17 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
 
             if !self.states.contains_key(&State(next_state.0)) {
                 self.ctx
-                    .emit_error("transition state not defined as state", next_state.1);
+                    .emit_error("transition state not defined as state", next_state.1)
+                    .code(Code::UNDEFINED_STATE)
+                    .did_you_mean(
+                        next_state.1,
+                        next_state.0,
+                        self.states.keys().map(|state| state.0),
+                    );
                 continue;
             };
 
+            let mut valid = true;
             let stack: Vec<_> = stack
                 .iter()
                 .rev()
@@ -562,19 +740,36 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
                     if matches!(symbol.0, ast::Item::Symbol(Sym::Epsilon(_))) {
                         return None;
                     }
-                    let ident = symbol.expect_ident(self.ctx)?;
+                    let Some(ident) = symbol.expect_ident_or_string(self.ctx) else {
+                        valid = false;
+                        return None;
+                    };
 
                     if !self.symbols.contains_key(&Symbol(ident)) {
                         self.ctx
-                            .emit_error("transition stack symbol not defined", symbol.1);
+                            .emit_error("transition stack symbol not defined", symbol.1)
+                            .code(Code::UNDEFINED_SYMBOL)
+                            .did_you_mean(
+                                symbol.1,
+                                ident,
+                                self.symbols.keys().map(|symbol| symbol.0),
+                            );
+                        valid = false;
                         return None;
                     };
                     Some(Symbol(ident))
                 })
                 .collect();
+            if !valid {
+                continue;
+            }
 
-            let entry: &mut _ = self
-                .transitions
+            let transitions = if otherwise {
+                &mut self.otherwise
+            } else {
+                &mut self.transitions
+            };
+            let entry: &mut _ = transitions
                 .entry(TransitionFrom {
                     letter,
                     state: State(state.0),
@@ -584,8 +779,21 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
             if let Some(entry) = entry.iter().next()
                 && !self.options.non_deterministic
             {
-                self.ctx.emit_error("transition already defined for this starting point (non determinism not permitted)", item.1)
-                            .emit_info("previously defined here", entry.1.transition);
+                self.ctx
+                    .emit_error(
+                        format!(
+                            "transition already defined for {from} (non determinism not permitted)"
+                        ),
+                        item.1,
+                    )
+                    .code(Code::NON_DETERMINISM)
+                    .label("conflicts here")
+                    .secondary(entry.1.transition, "first defined here")
+                    .secondary(
+                        letter_span.join(stack_symbol.1),
+                        "starting from this letter and stack symbol",
+                    );
+                continue;
             }
             if let Some(previous) = entry.insert(
                 Transition {
@@ -599,7 +807,8 @@ impl<'a, 'b> PdaCompiler<'a, 'b> {
             ) {
                 self.ctx
                     .emit_warning("duplicate transition", item.1)
-                    .emit_info("previously defined here", previous.transition);
+                    .code(Code::DUPLICATE_TRANSITION)
+                    .secondary(previous.transition, "previously defined here");
             }
         }
     }
@@ -613,20 +822,23 @@ impl<'a, 'b> Spanned<&'b ast::Tuple<'a>> {
         match &self.0.0[..] {
             [
                 Spanned(ast::Item::Symbol(ast::Symbol::Ident(state)), state_span),
-                Spanned(ast::Item::Symbol(letter), letter_span),
-                Spanned(ast::Item::Symbol(ast::Symbol::Ident(symbol)), symbol_span),
-            ] => {
+                Spanned(letter, letter_span),
+                Spanned(symbol, symbol_span),
+            ] if let Some(letter) = letter.as_symbol(ctx)
+                && let Some(ast::Symbol::Ident(symbol)) = symbol.as_symbol(ctx) =>
+            {
                 return Some((
                     Spanned(state, *state_span),
-                    Spanned(*letter, *letter_span),
+                    Spanned(letter, *letter_span),
                     Spanned(symbol, *symbol_span),
                 ));
             }
             _ => {
                 _ = ctx.emit_error(
-                    "expected PDA transition function (state, letter|epsilon, symbol)",
+                    "expected PDA transition function (state, letter|epsilon|{letter, ...}, symbol|{symbol, ...})",
                     self.1,
                 )
+                .code(Code::MALFORMED_TRANSITION)
             }
         }
         None
@@ -642,8 +854,71 @@ impl<'a, 'b> Spanned<&'b ast::Tuple<'a>> {
             ] => {
                 return Some((Spanned(state, *state_span), list.list_weak()));
             }
-            _ => _ = ctx.emit_error("expected PDA transition (state, symbol|[symbol])", self.1),
+            _ => {
+                _ = ctx
+                    .emit_error("expected PDA transition (state, symbol|[symbol])", self.1)
+                    .code(Code::MALFORMED_TRANSITION)
+            }
         }
         None
     }
 }
+
+#[test]
+fn otherwise() {
+    use crate::loader::{Interner, Machine, parse_universal};
+
+    let src = "type = DPDA
+Q = {q0, q1}
+E = {a, b}
+G = {Z0}
+accept = F
+F = {q1}
+d(q0, a, Z0) = (q0, Z0)
+d(q0, *, Z0) = (q1, Z0)";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    let Some(Machine::Pda(pda)) = parse_universal(&mut ctx) else {
+        panic!("failed to compile");
+    };
+    assert_eq!(pda.transitions.len(), 2);
+    for (from, to) in &pda.transitions {
+        let expected = match from.letter.unwrap().0 {
+            "a" => "q0",
+            _ => "q1",
+        };
+        assert_eq!(from.symbol.0, "Z0");
+        assert_eq!(to[0].state.0, expected);
+    }
+
+    // a deterministic machine with epsilon moves always takes them, so the
+    // otherwise transition from the same place never applies
+    let src = "Q = {q0, q1}
+E = {a, b}
+G = {Z0}
+accept = F
+F = {q1}
+q0 = q0
+z0 = Z0
+d(q0, ~, Z0) = (q1, Z0)
+d(q0, *, Z0) = (q0, Z0)";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    let items: Vec<_> = crate::loader::parser::Parser::new(&mut ctx).collect();
+    let options = Options {
+        non_deterministic: false,
+        epsilon_moves: true,
+    };
+    let Some(pda) = Pda::compile(items.into_iter(), &mut ctx, options) else {
+        panic!("failed to compile");
+    };
+    assert_eq!(pda.transitions.len(), 1);
+    assert!(pda.transitions.keys().all(|from| from.letter.is_none()));
+    let codes: Vec<_> = ctx
+        .logs()
+        .entries()
+        .iter()
+        .map(|entry| entry.code)
+        .collect();
+    assert_eq!(codes, [Some(Code::OTHERWISE_NEVER_APPLIES)]);
+}
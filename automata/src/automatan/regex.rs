@@ -0,0 +1,579 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use super::*;
+
+use crate::{
+    loader::{
+        Context, Spanned,
+        ast::{self, Regex, TopLevel},
+        codes::Code,
+        log::{Applicability, LogSink},
+    },
+    sigma_upper,
+};
+
+use super::fa::{Fa, TransitionFrom, TransitionTo};
+
+/// Upper bound on the states a single complement or intersection may produce
+/// while determinizing.
+const MAX_DETERMINIZED_STATES: usize = 4096;
+
+/// Upper bound on letters a class range may introduce when no alphabet is given.
+const MAX_INFERRED_RANGE: u32 = 256;
+
+impl<'a> Fa<'a> {
+    pub fn compile_regex(
+        items: impl Iterator<Item = Spanned<ast::TopLevel<'a>>>,
+        ctx: &mut Context<'a>,
+    ) -> Option<Fa<'a>> {
+        RegexCompiler::new(ctx).compile(items)
+    }
+}
+
+pub struct RegexCompiler<'a, 'b> {
+    ctx: &'b mut Context<'a>,
+
+    alphabet: HashMap<Letter<'a>, LetterInfo>,
+    alphabet_def: Option<Span>,
+
+    regex: Option<(Spanned<Regex<'a>>, Span)>,
+}
+
+#[derive(Clone, Copy)]
+struct Fragment {
+    start: usize,
+    end: usize,
+}
+
+#[derive(Default)]
+struct Builder<'a> {
+    states: Vec<Span>,
+    transitions: Vec<Vec<(Option<Letter<'a>>, usize, Span)>>,
+}
+
+impl<'a> Builder<'a> {
+    fn state(&mut self, definition: Span) -> usize {
+        self.states.push(definition);
+        self.transitions.push(Vec::new());
+        self.states.len() - 1
+    }
+
+    fn transition(&mut self, from: usize, letter: Option<Letter<'a>>, to: usize, span: Span) {
+        self.transitions[from].push((letter, to, span));
+    }
+
+    fn closure(&self, states: impl IntoIterator<Item = usize>) -> BTreeSet<usize> {
+        let mut closure = BTreeSet::new();
+        let mut stack: Vec<_> = states.into_iter().collect();
+        while let Some(state) = stack.pop() {
+            if closure.insert(state) {
+                stack.extend(
+                    self.transitions[state]
+                        .iter()
+                        .filter(|(letter, _, _)| letter.is_none())
+                        .map(|(_, to, _)| *to),
+                );
+            }
+        }
+        closure
+    }
+
+    fn step(&self, states: &BTreeSet<usize>, letter: Letter<'a>) -> BTreeSet<usize> {
+        self.closure(states.iter().flat_map(|state| {
+            self.transitions[*state]
+                .iter()
+                .filter(move |(l, _, _)| *l == Some(letter))
+                .map(|(_, to, _)| *to)
+        }))
+    }
+}
+
+impl<'a, 'b> RegexCompiler<'a, 'b> {
+    pub fn new(ctx: &'b mut Context<'a>) -> Self {
+        Self {
+            ctx,
+
+            alphabet: Default::default(),
+            alphabet_def: Default::default(),
+            regex: Default::default(),
+        }
+    }
+
+    pub fn compile(
+        mut self,
+        items: impl Iterator<Item = Spanned<ast::TopLevel<'a>>>,
+    ) -> Option<Fa<'a>> {
+        for Spanned(element, span) in items {
+            self.compile_top_level(element, span);
+        }
+
+        let Some((regex, function)) = self.regex.take() else {
+            self.ctx
+                .emit_error_locless("regex never defined")
+                .code(Code::NEVER_DEFINED)
+                .help("add: regex = r\"...\"");
+            return None;
+        };
+
+        if self.alphabet_def.is_none() {
+            self.infer_alphabet(regex.as_ref());
+        }
+
+        let mut builder = Builder::default();
+        let fragment = self.build(&mut builder, regex.as_ref());
+
+        if self.ctx.should_abort() {
+            return None;
+        }
+
+        Some(self.finish(builder, fragment, function))
+    }
+
+    fn compile_top_level(&mut self, element: TopLevel<'a>, span: Span) {
+        use Spanned as S;
+        use ast::TopLevel as TL;
+        match element {
+            TL::Item(S(sigma_upper!(pat), _), list) => self.compile_alphabet(list, span),
+            TL::Item(S("regex", _), item) => self.compile_regex(item, span),
+            TL::Item(S(name, dest_s), _) => {
+                self.ctx
+                    .emit_error(
+                        format!("unknown item {name:?}, expected alphabet | regex"),
+                        dest_s,
+                    )
+                    .code(Code::UNKNOWN_ITEM)
+                    .did_you_mean(dest_s, name, ["regex"].into_iter().chain(sigma_upper!(arr)));
+            }
+
+            TL::TransitionFunc(_, _) => {
+                self.ctx
+                    .emit_error("unexpected transition function", span)
+                    .code(Code::UNEXPECTED_FUNCTION)
+                    .help("regex machines are defined by: regex = r\"...\"");
+            }
+            TL::ProductionRule(_, _) => {
+                self.ctx
+                    .emit_error("unexpected production rule", span)
+                    .code(Code::UNEXPECTED_FUNCTION);
+            }
+            TL::Table(_) => {
+                _ = self
+                    .ctx
+                    .emit_error("unexpected table", span)
+                    .code(Code::UNEXPECTED_FUNCTION)
+            }
+        }
+    }
+
+    fn compile_alphabet(&mut self, list: Spanned<ast::Item<'a>>, top_level: Span) {
+        if let Some(previous) = self.alphabet_def {
+            self.ctx
+                .emit_error("alphabet already set", top_level)
+                .code(Code::ALREADY_SET)
+                .secondary(previous, "previously defined here");
+        }
+        let Some(list) = list.expect_set(self.ctx) else {
+            return;
+        };
+        for item in list {
+            let Some(ident) = item.expect_ident_or_string(self.ctx) else {
+                continue;
+            };
+
+            if ident.is_empty() {
+                self.ctx
+                    .emit_error("letter cannot be empty", item.1)
+                    .code(Code::INVALID_LETTER);
+                continue;
+            } else if ident.chars().count() != 1 {
+                self.ctx
+                    .emit_error("letter cannot be longer than one char", item.1)
+                    .code(Code::INVALID_LETTER);
+                continue;
+            }
+
+            if let Some(previous) = self
+                .alphabet
+                .insert(Letter(ident), LetterInfo { definition: item.1 })
+            {
+                self.ctx
+                    .emit_error("letter redefined", item.1)
+                    .code(Code::REDEFINED)
+                    .secondary(previous.definition, "previously defined here");
+            }
+        }
+        if list.is_empty() {
+            self.ctx
+                .emit_error("alphabet cannot be empty", top_level)
+                .code(Code::EMPTY_SET);
+        }
+        self.alphabet_def = Some(top_level);
+    }
+
+    fn compile_regex(&mut self, item: Spanned<ast::Item<'a>>, top_level: Span) {
+        if let Some((_, previous)) = self.regex {
+            self.ctx
+                .emit_error("regex already set", top_level)
+                .code(Code::ALREADY_SET)
+                .secondary(previous, "previously defined here");
+        }
+        let Some(regex) = item.expect_regex(self.ctx) else {
+            return;
+        };
+        self.regex = Some((Spanned(regex.0.clone(), regex.1), item.1));
+    }
+
+    /// Without an explicit alphabet the alphabet is every letter the regex mentions.
+    fn infer_alphabet(&mut self, Spanned(regex, span): Spanned<&Regex<'a>>) {
+        match regex {
+            Regex::Terminal(terminal) => {
+                self.alphabet
+                    .entry(Letter(terminal))
+                    .or_insert(LetterInfo { definition: span });
+            }
+            Regex::Match {
+                complement: false,
+                patterns,
+            } => {
+                for pattern in patterns {
+                    if *pattern.end() as u32 - *pattern.start() as u32 >= MAX_INFERRED_RANGE {
+                        let (at, line) = self.ctx.append_line("E = {...}");
+                        self.ctx
+                            .emit_error("class range too large to infer an alphabet from", span)
+                            .code(Code::REGEX_ALPHABET)
+                            .help("add: E = {...}")
+                            .suggest(at, line, Applicability::HasPlaceholders);
+                        continue;
+                    }
+                    for c in pattern.clone() {
+                        self.alphabet
+                            .entry(Letter(self.ctx.intern(c.encode_utf8(&mut [0; 4]))))
+                            .or_insert(LetterInfo { definition: span });
+                    }
+                }
+            }
+            Regex::Match {
+                complement: true, ..
+            } => {}
+            Regex::Concat(items) | Regex::Union(items) | Regex::Intersection(items) => {
+                for item in items {
+                    self.infer_alphabet(item.as_ref());
+                }
+            }
+            Regex::Star(inner) | Regex::Plus(inner) | Regex::Complement(inner) => {
+                self.infer_alphabet(inner.as_ref().as_ref())
+            }
+        }
+    }
+
+    fn require_alphabet(&mut self, what: &str, span: Span) {
+        if self.alphabet_def.is_none() {
+            let (at, line) = self.ctx.append_line("E = {...}");
+            self.ctx
+                .emit_error(format!("{what} requires an explicit alphabet"), span)
+                .code(Code::REGEX_ALPHABET)
+                .help("add: E = {...}")
+                .suggest(at, line, Applicability::HasPlaceholders);
+        }
+    }
+
+    fn letters(&self) -> Vec<Letter<'a>> {
+        let mut letters: Vec<_> = self.alphabet.keys().copied().collect();
+        letters.sort_by_key(|letter| letter.0);
+        letters
+    }
+
+    fn build(
+        &mut self,
+        builder: &mut Builder<'a>,
+        Spanned(regex, span): Spanned<&Regex<'a>>,
+    ) -> Fragment {
+        match regex {
+            Regex::Terminal(terminal) => {
+                let letter = match self.alphabet.get_key_value(&Letter(terminal)) {
+                    Some((letter, _)) => Some(*letter),
+                    None => {
+                        self.ctx
+                            .emit_error("regex letter not defined in alphabet", span)
+                            .code(Code::REGEX_UNDEFINED_LETTER)
+                            .did_you_mean(
+                                span,
+                                terminal,
+                                self.alphabet.keys().map(|letter| letter.0),
+                            );
+                        None
+                    }
+                };
+                let start = builder.state(span);
+                let end = builder.state(span);
+                // an undefined letter matches nothing instead of widening the alphabet
+                let Some(letter) = letter else {
+                    return Fragment { start, end };
+                };
+                builder.transition(start, Some(letter), end, span);
+                Fragment { start, end }
+            }
+            Regex::Match {
+                complement,
+                patterns,
+            } => {
+                if *complement {
+                    self.require_alphabet(
+                        if patterns.is_empty() {
+                            "matching any letter"
+                        } else {
+                            "a negated class"
+                        },
+                        span,
+                    );
+                }
+                let start = builder.state(span);
+                let end = builder.state(span);
+                for letter in self.letters() {
+                    let mut chars = letter.0.chars();
+                    let matches = chars.next().is_some_and(|c| {
+                        chars.next().is_none() && patterns.iter().any(|p| p.contains(&c))
+                    });
+                    if matches != *complement {
+                        builder.transition(start, Some(letter), end, span);
+                    }
+                }
+                Fragment { start, end }
+            }
+            Regex::Concat(items) => {
+                let start = builder.state(span);
+                let mut end = start;
+                for item in items {
+                    let fragment = self.build(builder, item.as_ref());
+                    builder.transition(end, None, fragment.start, item.1);
+                    end = fragment.end;
+                }
+                Fragment { start, end }
+            }
+            Regex::Union(items) => {
+                let start = builder.state(span);
+                let end = builder.state(span);
+                for item in items {
+                    let fragment = self.build(builder, item.as_ref());
+                    builder.transition(start, None, fragment.start, item.1);
+                    builder.transition(fragment.end, None, end, item.1);
+                }
+                Fragment { start, end }
+            }
+            Regex::Star(inner) | Regex::Plus(inner) => {
+                let start = builder.state(span);
+                let end = builder.state(span);
+                let fragment = self.build(builder, inner.as_ref().as_ref());
+                builder.transition(start, None, fragment.start, span);
+                builder.transition(fragment.end, None, fragment.start, span);
+                builder.transition(fragment.end, None, end, span);
+                if matches!(regex, Regex::Star(_)) {
+                    builder.transition(start, None, end, span);
+                }
+                Fragment { start, end }
+            }
+            Regex::Intersection(items) => {
+                let fragments: Vec<_> = items
+                    .iter()
+                    .map(|item| self.build(builder, item.as_ref()))
+                    .collect();
+                self.determinize(builder, &fragments, span, |accepting| {
+                    accepting.iter().all(|a| *a)
+                })
+            }
+            Regex::Complement(inner) => {
+                self.require_alphabet("complement", span);
+                let fragment = self.build(builder, inner.as_ref().as_ref());
+                self.determinize(builder, &[fragment], span, |accepting| !accepting[0])
+            }
+        }
+    }
+
+    /// Runs the subset construction over the product of `fragments`, producing
+    /// a fragment which accepts wherever `accept` holds for the component states.
+    fn determinize(
+        &mut self,
+        builder: &mut Builder<'a>,
+        fragments: &[Fragment],
+        span: Span,
+        accept: impl Fn(&[bool]) -> bool,
+    ) -> Fragment {
+        let letters = self.letters();
+        let initial: Vec<_> = fragments
+            .iter()
+            .map(|fragment| builder.closure([fragment.start]))
+            .collect();
+
+        let start = builder.state(span);
+        let end = builder.state(span);
+
+        let mut seen = HashMap::new();
+        seen.insert(initial.clone(), start);
+        let mut queue = VecDeque::from([(initial, start)]);
+
+        while let Some((sets, state)) = queue.pop_front() {
+            let accepting: Vec<_> = sets
+                .iter()
+                .zip(fragments)
+                .map(|(set, fragment)| set.contains(&fragment.end))
+                .collect();
+            if accept(&accepting) {
+                builder.transition(state, None, end, span);
+            }
+
+            for letter in &letters {
+                let next: Vec<_> = sets.iter().map(|set| builder.step(set, *letter)).collect();
+                let to = match seen.get(&next) {
+                    Some(to) => *to,
+                    None => {
+                        if seen.len() >= MAX_DETERMINIZED_STATES {
+                            self.ctx
+                                .emit_error("regex too complex to determinize", span)
+                                .code(Code::REGEX_TOO_COMPLEX)
+                                .note(format!("exceeded {MAX_DETERMINIZED_STATES} states"));
+                            return Fragment { start, end };
+                        }
+                        let to = builder.state(span);
+                        seen.insert(next.clone(), to);
+                        queue.push_back((next, to));
+                        to
+                    }
+                };
+                builder.transition(state, Some(*letter), to, span);
+            }
+        }
+
+        Fragment { start, end }
+    }
+
+    /// Keeps only the states reachable from `fragment.start`, naming them in the order they are found.
+    fn finish(self, builder: Builder<'a>, fragment: Fragment, function: Span) -> Fa<'a> {
+        let mut names = HashMap::new();
+        let mut queue = VecDeque::from([fragment.start]);
+        names.insert(fragment.start, State("q0"));
+        while let Some(state) = queue.pop_front() {
+            for (_, to, _) in &builder.transitions[state] {
+                if !names.contains_key(to) {
+                    names.insert(*to, State(self.ctx.intern(&format!("q{}", names.len()))));
+                    queue.push_back(*to);
+                }
+            }
+        }
+
+        let mut transitions: HashMap<TransitionFrom<'a>, Vec<TransitionTo<'a>>> = HashMap::new();
+        for (from, name) in &names {
+            for (letter, to, span) in &builder.transitions[*from] {
+                let entry = transitions
+                    .entry(TransitionFrom {
+                        state: *name,
+                        letter: *letter,
+                    })
+                    .or_default();
+                if entry.iter().all(|existing| existing.state != names[to]) {
+                    entry.push(TransitionTo {
+                        state: names[to],
+                        transition: *span,
+                        function,
+                    });
+                }
+            }
+        }
+
+        let mut final_states = HashMap::new();
+        if let Some(end) = names.get(&fragment.end) {
+            final_states.insert(
+                *end,
+                StateInfo {
+                    definition: builder.states[fragment.end],
+                },
+            );
+        }
+
+        Fa {
+            initial_state: names[&fragment.start],
+            states: names
+                .iter()
+                .map(|(index, name)| {
+                    (
+                        *name,
+                        StateInfo {
+                            definition: builder.states[*index],
+                        },
+                    )
+                })
+                .collect(),
+            alphabet: self.alphabet,
+            final_states,
+            transitions,
+        }
+    }
+}
+
+#[test]
+fn regex() {
+    use crate::loader::Interner;
+
+    fn accepts(src: &str, input: &str) -> bool {
+        let interner = Interner::default();
+        let mut ctx = Context::new(src, &interner);
+        let Some(crate::loader::Machine::Fa(fa)) = crate::loader::parse_universal(&mut ctx) else {
+            panic!("{src:?} failed to compile");
+        };
+        let step = |states: &Vec<State>, letter: Option<&str>| {
+            let mut next = Vec::new();
+            let mut stack = Vec::new();
+            for state in states {
+                let from = TransitionFrom {
+                    state: *state,
+                    letter: letter.map(Letter),
+                };
+                stack.extend(fa.transitions.get(&from).into_iter().flatten());
+            }
+            while let Some(to) = stack.pop() {
+                if !next.contains(&to.state) {
+                    next.push(to.state);
+                    let from = TransitionFrom {
+                        state: to.state,
+                        letter: None,
+                    };
+                    stack.extend(fa.transitions.get(&from).into_iter().flatten());
+                }
+            }
+            next
+        };
+        let mut states = vec![fa.initial_state];
+        states.extend(step(&states, None));
+        for c in input.chars() {
+            states = step(&states, Some(c.encode_utf8(&mut [0; 4])));
+        }
+        states
+            .iter()
+            .any(|state| fa.final_states.contains_key(state))
+    }
+
+    let src = "type = REGEX\nregex = r\"(a|b)*abb\"";
+    assert!(accepts(src, "abb"));
+    assert!(accepts(src, "babaabb"));
+    assert!(!accepts(src, "abba"));
+
+    let src = "type = REGEX\nE = {a, b}\nregex = r\"!(.*aa.*)\"";
+    assert!(accepts(src, "ababa"));
+    assert!(!accepts(src, "baab"));
+
+    let src = "type = REGEX\nE = {a, b, c}\nregex = r\"[a-c]+&(.*b.*)\"";
+    assert!(accepts(src, "acb"));
+    assert!(!accepts(src, "acca"));
+
+    for malformed in [
+        r#"r"(ab""#,
+        r#"r"*a""#,
+        r#"r"[z-a]""#,
+        r#"r"a)""#,
+        r#"r"\q""#,
+    ] {
+        let src = format!("type = REGEX\nregex = {malformed}");
+        let interner = Interner::default();
+        let mut ctx = Context::new(&src, &interner);
+        assert!(crate::loader::parse_universal(&mut ctx).is_none());
+    }
+}
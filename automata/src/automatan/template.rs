@@ -0,0 +1,498 @@
+//! Expansion of templated names such as `q{0..6}` and `d(q{i}, a) = q{(i+1)%7}`.
+//!
+//! Templates are expanded by the compilers before anything else looks at an
+//! item, every expanded name keeps the span of the template it came from.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::loader::{
+    Context, Span, Spanned,
+    ast::{BinOp, Expr, Item, List, ListKind, Segment, Symbol, Template, Tuple},
+    codes::Code,
+    log::LogSink,
+};
+
+/// The most names a single template or transition line may expand to.
+const MAX_EXPANSION: usize = 4096;
+
+type Bindings<'a> = BTreeMap<&'a str, i64>;
+
+struct Error(String, Span);
+
+/// Expands the templates in an item that isn't a transition function, where
+/// no variables can be bound.
+///
+/// A template naming several values becomes one list element per value, on its
+/// own it becomes a list. Elements of a list which fail to evaluate are left
+/// out, `None` if the item itself does.
+pub fn expand_item<'a>(
+    item: Spanned<Item<'a>>,
+    ctx: &mut Context<'a>,
+) -> Option<Spanned<Item<'a>>> {
+    if !has_template(&item.0) {
+        return Some(item);
+    }
+    let bindings = Bindings::new();
+    match item {
+        Spanned(Item::List(List(items, kind)), span) => {
+            let mut expanded = Vec::with_capacity(items.len());
+            for item in items {
+                let result = match &item.0 {
+                    Item::Template(template) => names(template, item.1, &bindings, ctx),
+                    _ => substitute(item, &bindings, ctx).map(|item| vec![item]),
+                };
+                match result {
+                    Ok(items) => expanded.extend(items),
+                    Err(Error(msg, error)) => {
+                        ctx.emit_error(msg, error).code(Code::TEMPLATE_EVALUATION);
+                    }
+                }
+            }
+            Some(Spanned(Item::List(List(expanded, kind)), span))
+        }
+        item => match substitute(item, &bindings, ctx) {
+            Ok(item) => Some(item),
+            Err(Error(msg, error)) => {
+                ctx.emit_error(msg, error).code(Code::TEMPLATE_EVALUATION);
+                None
+            }
+        },
+    }
+}
+
+/// Expands a transition function line into one line per value of the
+/// variables it uses.
+///
+/// Variables are bound by arguments naming them on their own, like `q{i}`,
+/// and take every value for which that argument is one of the `candidates`
+/// for its position. Arguments naming several values, like `q{0..6}`, expand
+/// to one line per value.
+pub fn expand_transition<'a>(
+    args: Spanned<Tuple<'a>>,
+    dest: Spanned<Item<'a>>,
+    candidates: &[Vec<&'a str>],
+    ctx: &mut Context<'a>,
+) -> Vec<(Spanned<Tuple<'a>>, Spanned<Item<'a>>)> {
+    if !args.0.0.iter().any(|arg| has_template(&arg.0)) && !has_template(&dest.0) {
+        return vec![(args, dest)];
+    }
+
+    let mut bound = BTreeSet::new();
+    let mut joined = vec![Bindings::new()];
+    for (arg, candidates) in args.0.0.iter().zip(candidates) {
+        let Item::Template(template) = &arg.0 else {
+            continue;
+        };
+        let Some(binds) = binds(template) else {
+            continue;
+        };
+        if binds.is_empty() {
+            continue;
+        }
+
+        let mut matches = BTreeSet::new();
+        for name in candidates {
+            match_segments(&template.0, name, Bindings::new(), &mut matches);
+        }
+        if matches.is_empty() {
+            ctx.emit_warning("template doesn't match any defined name", arg.1)
+                .code(Code::UNMATCHED_TEMPLATE);
+        }
+
+        joined = joined
+            .iter()
+            .flat_map(|left| {
+                matches.iter().filter_map(move |right| {
+                    let mut merged = left.clone();
+                    for (var, value) in right {
+                        if *merged.entry(var).or_insert(*value) != *value {
+                            return None;
+                        }
+                    }
+                    Some(merged)
+                })
+            })
+            .collect();
+        bound.extend(binds);
+    }
+
+    let mut vars = Vec::new();
+    for arg in &args.0.0 {
+        item_vars(&arg.0, &mut vars);
+    }
+    item_vars(&dest.0, &mut vars);
+    if let Some(Spanned(var, span)) = vars.iter().find(|var| !bound.contains(var.0)) {
+        ctx.emit_error(
+            format!("cannot infer the values of variable {var:?}"),
+            *span,
+        )
+        .code(Code::UNINFERRABLE_VARIABLE)
+        .help(
+            "use it on its own in an argument, like q{i}, to take the values of the defined names",
+        );
+        return Vec::new();
+    }
+
+    let mut expanded = Vec::new();
+    for bindings in &joined {
+        match expand_line(&args, &dest, bindings, &mut expanded, ctx) {
+            Ok(()) => {}
+            Err(Error(msg, span)) => {
+                let entry = ctx.emit_error(msg, span).code(Code::TEMPLATE_EVALUATION);
+                if !bindings.is_empty() {
+                    let with = bindings
+                        .iter()
+                        .map(|(var, value)| format!("{var} = {value}"))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    entry.note(format!("with {with}"));
+                }
+                return Vec::new();
+            }
+        }
+    }
+    expanded
+}
+
+fn expand_line<'a>(
+    args: &Spanned<Tuple<'a>>,
+    dest: &Spanned<Item<'a>>,
+    bindings: &Bindings<'a>,
+    expanded: &mut Vec<(Spanned<Tuple<'a>>, Spanned<Item<'a>>)>,
+    ctx: &Context<'a>,
+) -> Result<(), Error> {
+    let mut tuples = vec![Vec::new()];
+    for arg in &args.0.0 {
+        let values = match &arg.0 {
+            Item::Template(template) => names(template, arg.1, bindings, ctx)?,
+            _ => vec![substitute(arg.clone(), bindings, ctx)?],
+        };
+        tuples = tuples
+            .iter()
+            .flat_map(|tuple| {
+                values.iter().map(move |value| {
+                    let mut tuple = tuple.clone();
+                    tuple.push(value.clone());
+                    tuple
+                })
+            })
+            .collect();
+    }
+
+    let dest = substitute(dest.clone(), bindings, ctx)?;
+    if expanded.len() + tuples.len() > MAX_EXPANSION {
+        return Err(Error(
+            format!("transition expands to more than {MAX_EXPANSION} transitions"),
+            args.1,
+        ));
+    }
+    expanded.extend(
+        tuples
+            .into_iter()
+            .map(|tuple| (Spanned(Tuple(tuple), args.1), dest.clone())),
+    );
+    Ok(())
+}
+
+fn has_template(item: &Item) -> bool {
+    match item {
+        Item::Template(_) => true,
+        Item::Tuple(Tuple(items)) | Item::List(List(items, _)) => {
+            items.iter().any(|item| has_template(&item.0))
+        }
+        Item::Symbol(_) | Item::String(_) | Item::Regex(_) => false,
+    }
+}
+
+/// Replaces every template in `item` by the names it expands to.
+fn substitute<'a>(
+    Spanned(item, span): Spanned<Item<'a>>,
+    bindings: &Bindings<'a>,
+    ctx: &Context<'a>,
+) -> Result<Spanned<Item<'a>>, Error> {
+    let item = match item {
+        Item::Template(template) => {
+            let mut names = names(&template, span, bindings, ctx)?;
+            if names.len() == 1 {
+                names.pop().unwrap().0
+            } else {
+                Item::List(List(names, ListKind::BraceComma))
+            }
+        }
+        Item::List(List(items, kind)) => {
+            let mut expanded = Vec::with_capacity(items.len());
+            for Spanned(item, span) in items {
+                match item {
+                    Item::Template(template) => {
+                        expanded.extend(names(&template, span, bindings, ctx)?)
+                    }
+                    item => expanded.push(substitute(Spanned(item, span), bindings, ctx)?),
+                }
+            }
+            Item::List(List(expanded, kind))
+        }
+        Item::Tuple(Tuple(items)) => Item::Tuple(Tuple(
+            items
+                .into_iter()
+                .map(|item| substitute(item, bindings, ctx))
+                .collect::<Result<_, _>>()?,
+        )),
+        item @ (Item::Symbol(_) | Item::String(_) | Item::Regex(_)) => item,
+    };
+    Ok(Spanned(item, span))
+}
+
+/// Every name `template` expands to, as symbols.
+fn names<'a>(
+    template: &Template<'a>,
+    span: Span,
+    bindings: &Bindings<'a>,
+    ctx: &Context<'a>,
+) -> Result<Vec<Spanned<Item<'a>>>, Error> {
+    let mut names = vec![String::new()];
+    for Spanned(segment, _) in &template.0 {
+        let parts = match segment {
+            Segment::Literal(literal) => vec![literal.to_string()],
+            Segment::Expr(expr) => values(expr, bindings)?
+                .into_iter()
+                .map(|value| value.to_string())
+                .collect(),
+        };
+        if names.len() * parts.len() > MAX_EXPANSION {
+            return Err(Error(
+                format!("template expands to more than {MAX_EXPANSION} names"),
+                span,
+            ));
+        }
+        names = names
+            .iter()
+            .flat_map(|name| parts.iter().map(move |part| format!("{name}{part}")))
+            .collect();
+    }
+    Ok(names
+        .into_iter()
+        .map(|name| Spanned(Item::Symbol(Symbol::Ident(ctx.intern(&name))), span))
+        .collect())
+}
+
+/// The values of the expression of a `{...}` segment, ranges included.
+fn values<'a>(expr: &Spanned<Expr<'a>>, bindings: &Bindings<'a>) -> Result<Vec<i64>, Error> {
+    let Expr::Range(start, end) = &expr.0 else {
+        return Ok(vec![eval(expr, bindings)?]);
+    };
+    let (start, end) = (eval(start, bindings)?, eval(end, bindings)?);
+    if start > end {
+        return Err(Error(
+            format!("range start {start} is greater than its end {end}"),
+            expr.1,
+        ));
+    }
+    if end.abs_diff(start) >= MAX_EXPANSION as u64 {
+        return Err(Error(
+            format!("range has more than {MAX_EXPANSION} values"),
+            expr.1,
+        ));
+    }
+    Ok((start..=end).collect())
+}
+
+/// Evaluates an expression, `/` and `%` round towards negative infinity so
+/// `(i-1)%7` stays in `0..7`.
+fn eval<'a>(
+    Spanned(expr, span): &Spanned<Expr<'a>>,
+    bindings: &Bindings<'a>,
+) -> Result<i64, Error> {
+    let overflow = || Error("integer overflow".into(), *span);
+    match expr {
+        Expr::Int(int) => Ok(*int),
+        Expr::Var(var) => bindings
+            .get(var)
+            .copied()
+            .ok_or_else(|| Error(format!("unbound variable {var:?}"), *span)),
+        Expr::Neg(expr) => eval(expr, bindings)?.checked_neg().ok_or_else(overflow),
+        Expr::Binary(op, lhs, rhs) => {
+            let (lhs, rhs_value) = (eval(lhs, bindings)?, eval(rhs, bindings)?);
+            match op {
+                BinOp::Div | BinOp::Rem if rhs_value == 0 => {
+                    return Err(Error("division by zero".into(), rhs.1));
+                }
+                BinOp::Add => lhs.checked_add(rhs_value),
+                BinOp::Sub => lhs.checked_sub(rhs_value),
+                BinOp::Mul => lhs.checked_mul(rhs_value),
+                BinOp::Div => lhs.checked_div_euclid(rhs_value),
+                BinOp::Rem => lhs.checked_rem_euclid(rhs_value),
+            }
+            .ok_or_else(overflow)
+        }
+        Expr::Range(_, _) => Err(Error(
+            "ranges can only be used as a whole part, like q{0..6}".into(),
+            *span,
+        )),
+    }
+}
+
+fn expr_vars<'a>(Spanned(expr, span): &Spanned<Expr<'a>>, vars: &mut Vec<Spanned<&'a str>>) {
+    match expr {
+        Expr::Int(_) => {}
+        Expr::Var(var) => vars.push(Spanned(var, *span)),
+        Expr::Neg(expr) => expr_vars(expr, vars),
+        Expr::Binary(_, lhs, rhs) | Expr::Range(lhs, rhs) => {
+            expr_vars(lhs, vars);
+            expr_vars(rhs, vars);
+        }
+    }
+}
+
+fn item_vars<'a>(item: &Item<'a>, vars: &mut Vec<Spanned<&'a str>>) {
+    match item {
+        Item::Template(template) => {
+            for segment in &template.0 {
+                if let Segment::Expr(expr) = &segment.0 {
+                    expr_vars(expr, vars);
+                }
+            }
+        }
+        Item::Tuple(Tuple(items)) | Item::List(List(items, _)) => {
+            for item in items {
+                item_vars(&item.0, vars);
+            }
+        }
+        Item::Symbol(_) | Item::String(_) | Item::Regex(_) => {}
+    }
+}
+
+/// The variables a template binds when matched against a name, or `None` if
+/// it can't be matched because an expression uses a variable it doesn't bind
+/// before that expression.
+fn binds<'a>(template: &Template<'a>) -> Option<BTreeSet<&'a str>> {
+    let mut binds = BTreeSet::new();
+    for segment in &template.0 {
+        match &segment.0 {
+            Segment::Literal(_) => {}
+            Segment::Expr(Spanned(Expr::Var(var), _)) => _ = binds.insert(*var),
+            Segment::Expr(expr) => {
+                let mut vars = Vec::new();
+                expr_vars(expr, &mut vars);
+                if vars.iter().any(|var| !binds.contains(var.0)) {
+                    return None;
+                }
+            }
+        }
+    }
+    Some(binds)
+}
+
+/// Collects every way `segments` can spell `name`.
+fn match_segments<'a>(
+    segments: &[Spanned<Segment<'a>>],
+    name: &str,
+    bindings: Bindings<'a>,
+    matches: &mut BTreeSet<Bindings<'a>>,
+) {
+    let Some((first, rest)) = segments.split_first() else {
+        if name.is_empty() {
+            matches.insert(bindings);
+        }
+        return;
+    };
+    match &first.0 {
+        Segment::Literal(literal) => {
+            if let Some(name) = name.strip_prefix(literal) {
+                match_segments(rest, name, bindings, matches);
+            }
+        }
+        Segment::Expr(Spanned(Expr::Var(var), _)) => {
+            for (end, c) in name.char_indices() {
+                if !(c.is_ascii_digit() || (end == 0 && c == '-')) {
+                    break;
+                }
+                let (number, name) = name.split_at(end + 1);
+                // only canonical numbers, so `q{i}` doesn't match `q07`
+                let Some(value) = number
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|value| value.to_string() == number)
+                else {
+                    continue;
+                };
+                if bindings.get(var).is_some_and(|bound| *bound != value) {
+                    continue;
+                }
+                let mut bindings = bindings.clone();
+                bindings.insert(var, value);
+                match_segments(rest, name, bindings, matches);
+            }
+        }
+        Segment::Expr(expr) => {
+            let Ok(values) = values(expr, &bindings) else {
+                return;
+            };
+            for value in values {
+                if let Some(name) = name.strip_prefix(&value.to_string()) {
+                    match_segments(rest, name, bindings.clone(), matches);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn templates() {
+    use crate::automatan::{Letter, State, fa::TransitionFrom};
+    use crate::loader::{Interner, Machine, parse_universal};
+
+    let src = "type = DFA
+Q = {q{0..6}}
+E = {a, b}
+F = {q0}
+d(q{i}, a) = q{(i+1)%7}
+d(q{i}, b) = q{(i-1)%7}";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    let Some(Machine::Fa(fa)) = parse_universal(&mut ctx) else {
+        panic!("failed to compile");
+    };
+    assert_eq!(fa.states.len(), 7);
+    assert_eq!(fa.transitions.len(), 14);
+    let next = |state, letter| {
+        let from = TransitionFrom {
+            state: State(state),
+            letter: Some(Letter(letter)),
+        };
+        fa.transitions[&from][0].state.0
+    };
+    assert_eq!(next("q6", "a"), "q0");
+    assert_eq!(next("q0", "b"), "q6");
+
+    let line = src.lines().nth(4).unwrap();
+    let start = src.find(line).unwrap();
+    assert!(
+        fa.transitions
+            .values()
+            .flatten()
+            .any(|to| to.function.0 == start)
+    );
+
+    for malformed in ["d(q0, a) = q{k}", "d(q{i}, a) = q{i/0}", "Q = {q{3..1}}"] {
+        let src = format!("type = DFA\nQ = {{q0}}\nE = {{a}}\nF = {{q0}}\n{malformed}");
+        let interner = Interner::default();
+        let mut ctx = Context::new(&src, &interner);
+        assert!(parse_universal(&mut ctx).is_none());
+    }
+
+    // only the element failing to evaluate is left out
+    let src = "type = DFA\nQ = {q{1/0}, q0, q1}\nE = {a}\nF = {q1}\nq0 = q0\nd(q0, a) = q1\nd(q1, a) = q0";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner).resilient();
+    let Some(Machine::Fa(fa)) = parse_universal(&mut ctx) else {
+        panic!("expected a partial machine");
+    };
+    assert_eq!(fa.states.len(), 2);
+    let codes: Vec<_> = ctx
+        .logs()
+        .entries()
+        .iter()
+        .map(|entry| entry.code)
+        .collect();
+    assert_eq!(codes, [Some(Code::TEMPLATE_EVALUATION)]);
+}
@@ -5,7 +5,8 @@ use crate::{
     loader::{
         BLANK_SYMBOL, Context, INITIAL_STATE, Spanned,
         ast::{self, Symbol as Sym},
-        log::LogSink,
+        codes::Code,
+        log::{Applicability, LogSink},
     },
 };
 dual_struct_serde! {
@@ -21,11 +22,11 @@ dual_struct_serde! {
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Direction {
-    #[serde(rename = "<")]
+    #[cfg_attr(feature = "serde", serde(rename = "<"))]
     Left,
-    #[serde(rename = ">")]
+    #[cfg_attr(feature = "serde", serde(rename = ">"))]
     Right,
-    #[serde(rename = "_")]
+    #[cfg_attr(feature = "serde", serde(rename = "_"))]
     None,
 }
 
@@ -72,11 +73,6 @@ struct Transition<'a> {
     pub direction: Direction,
 }
 
-struct TransitionInfo {
-    pub transition: Span,
-    pub function: Span,
-}
-
 impl<'a> Tm<'a> {
     pub fn compile(
         items: impl Iterator<Item = Spanned<ast::TopLevel<'a>>>,
@@ -106,6 +102,12 @@ pub struct TmCompiler<'a, 'b> {
     transitions: HashMap<TransitionFrom<'a>, HashMap<Transition<'a>, TransitionInfo>>,
 }
 
+impl<'a, 'b> AsMut<Context<'a>> for TmCompiler<'a, 'b> {
+    fn as_mut(&mut self) -> &mut Context<'a> {
+        self.ctx
+    }
+}
+
 impl<'a, 'b> TmCompiler<'a, 'b> {
     pub fn new(ctx: &'b mut Context<'a>, options: Options) -> Self {
         Self {
@@ -133,55 +135,75 @@ impl<'a, 'b> TmCompiler<'a, 'b> {
         }
 
         if self.final_states_def.is_none() {
+            let (at, line) = self.ctx.append_line("F = {...}");
             self.ctx
                 .emit_error_locless("final states never defined")
-                .emit_help_logless("add: F = {...}");
+                .code(Code::NEVER_DEFINED)
+                .help("add: F = {...}")
+                .suggest(at, line, Applicability::HasPlaceholders);
         }
 
         let initial_state = match self.initial_state {
-            Some(some) => some.0,
+            Some(some) => Some(some.0),
             None => {
                 if self.states.contains_key(&State("q0")) {
+                    let (at, line) = self.ctx.append_line(&format!("{INITIAL_STATE} = q0"));
                     self.ctx
                         .emit_warning_locless("initial state not defined, defaulting to 'q0'")
-                        .emit_help_logless(format!("add: {INITIAL_STATE} = q0"));
+                        .code(Code::DEFAULTED)
+                        .help(format!("add: {INITIAL_STATE} = q0"))
+                        .suggest(at, line, Applicability::MachineApplicable);
+                    Some(State("q0"))
                 } else {
+                    let (at, line) = self.ctx.append_line(&format!("{INITIAL_STATE} = ..."));
                     self.ctx
                         .emit_error_locless("initial state not defined")
-                        .emit_help_logless(format!("add: {BLANK_SYMBOL} = ..."));
+                        .code(Code::NEVER_DEFINED)
+                        .help(format!("add: {INITIAL_STATE} = ..."))
+                        .suggest(at, line, Applicability::HasPlaceholders);
+                    None
                 }
-                State("q0")
             }
         };
 
         let blank_symbol = match self.blank_symbol {
-            Some(some) => some.0,
+            Some(some) => Some(some.0),
             None => {
                 if self.symbols.contains_key(&Symbol("B")) {
+                    let (at, line) = self.ctx.append_line(&format!("{BLANK_SYMBOL} = B"));
                     self.ctx
                         .emit_warning_locless("blank symbol not defined, defaulting to 'B'")
-                        .emit_help_logless(format!("add: {BLANK_SYMBOL} = B"));
+                        .code(Code::DEFAULTED)
+                        .help(format!("add: {BLANK_SYMBOL} = B"))
+                        .suggest(at, line, Applicability::MachineApplicable);
+                    Some(Symbol("B"))
                 } else {
+                    let (at, line) = self.ctx.append_line(&format!("{BLANK_SYMBOL} = ..."));
                     self.ctx
                         .emit_error_locless("blank symbol not defined")
-                        .emit_help_logless(format!("add: {BLANK_SYMBOL} = ..."));
+                        .code(Code::NEVER_DEFINED)
+                        .help(format!("add: {BLANK_SYMBOL} = ..."))
+                        .suggest(at, line, Applicability::HasPlaceholders);
+                    None
                 }
-                Symbol("B")
             }
         };
 
         if self.transitions.is_empty() {
             self.ctx
                 .emit_warning_locless("no transitions defined")
-                .emit_help_logless(
-                    "consider defining one: d(state, symbol) = (state, symbol, direction) | {(state, symbol, direction), ...}",
-                )
-                .emit_info_logless(concat!("d can be ", delta_lower!(str)));
+                .code(Code::NO_TRANSITIONS)
+                .help("consider defining one: d(state, symbol) = (state, symbol, direction) | {(state, symbol, direction), ...}")
+                .note(concat!("d can be ", delta_lower!(str)));
         }
 
-        if self.ctx.contains_errors() {
+        if self.ctx.should_abort() {
             return None;
         }
+        // even resilient compilation can't build a machine without these
+        let (Some(initial_state), Some(blank_symbol)) = (initial_state, blank_symbol) else {
+            return None;
+        };
 
         Some(Tm {
             initial_state,
@@ -213,6 +235,13 @@ impl<'a, 'b> TmCompiler<'a, 'b> {
     fn compile_top_level(&mut self, element: ast::TopLevel<'a>, span: Span) {
         use Spanned as S;
         use ast::TopLevel as TL;
+        let element = match element {
+            TL::Item(name, item) => match template::expand_item(item, self.ctx) {
+                Some(item) => TL::Item(name, item),
+                None => return,
+            },
+            element => element,
+        };
         match element {
             TL::Item(S("Q", _), list) => self.compile_states(list, span),
             TL::Item(S(gamma_upper!(pat), _), list) => self.compile_symbols(list, span),
@@ -220,26 +249,33 @@ impl<'a, 'b> TmCompiler<'a, 'b> {
             TL::Item(S(INITIAL_STATE, _), item) => self.compile_initial_state(item, span),
             TL::Item(S(BLANK_SYMBOL, _), item) => self.compile_blank_symbol(item, span),
             TL::Item(S(name, dest_s), _) => {
-                self.ctx.emit_error(format!("unknown item {name:?}, expected states | symbols | final states | initial state | blank symbol"), dest_s);
+                self.ctx.emit_error(format!("unknown item {name:?}, expected states | symbols | final states | initial state | blank symbol"), dest_s)
+                    .code(Code::UNKNOWN_ITEM)
+                    .did_you_mean(dest_s, name, ["Q", "F", INITIAL_STATE, BLANK_SYMBOL].into_iter().chain(gamma_upper!(arr)));
             }
 
             TL::TransitionFunc(S((S(delta_lower!(pat), _), args), func), list) => {
                 self.compile_transition_function(args, func, list)
             }
-            TL::TransitionFunc(S((S(name, _), _), dest_s), _) => {
-                self.ctx.emit_error(
-                    format!(
-                        "unknown function {name:?}, expected transition function ( {} )",
-                        delta_lower!(str)
-                    ),
-                    dest_s,
-                );
+            TL::TransitionFunc(S((S(name, name_s), _), dest_s), _) => {
+                self.ctx
+                    .emit_error(
+                        format!(
+                            "unknown function {name:?}, expected transition function ( {} )",
+                            delta_lower!(str)
+                        ),
+                        dest_s,
+                    )
+                    .code(Code::UNEXPECTED_FUNCTION)
+                    .did_you_mean(name_s, name, delta_lower!(arr));
             }
 
             TL::ProductionRule(_, _) => {
-                self.ctx.emit_error("unexpected production rule", span);
+                self.ctx
+                    .emit_error("unexpected production rule", span)
+                    .code(Code::UNEXPECTED_FUNCTION);
             }
-            TL::Table() => _ = self.ctx.emit_error("unexpected table", span),
+            TL::Table(table) => compile_table(self, table, Self::compile_transition_function),
         }
     }
 
@@ -247,7 +283,8 @@ impl<'a, 'b> TmCompiler<'a, 'b> {
         if let Some(previous) = self.states_def {
             self.ctx
                 .emit_error("states already set", top_level)
-                .emit_info("previously defined here", previous);
+                .code(Code::ALREADY_SET)
+                .secondary(previous, "previously defined here");
         }
         let Some(list) = list.expect_set(self.ctx) else {
             return;
@@ -262,12 +299,15 @@ impl<'a, 'b> TmCompiler<'a, 'b> {
             {
                 self.ctx
                     .emit_error("state redefined", item.1)
-                    .emit_info("previously defined here", previous.definition);
+                    .code(Code::REDEFINED)
+                    .secondary(previous.definition, "previously defined here");
             }
         }
 
         if list.is_empty() {
-            self.ctx.emit_error("states cannot be empty", top_level);
+            self.ctx
+                .emit_error("states cannot be empty", top_level)
+                .code(Code::EMPTY_SET);
         }
         self.states_def = Some(top_level);
     }
@@ -276,13 +316,14 @@ impl<'a, 'b> TmCompiler<'a, 'b> {
         if let Some(previous) = self.symbols_def {
             self.ctx
                 .emit_error("stack symbols already set", top_level)
-                .emit_info("previously defined here", previous);
+                .code(Code::ALREADY_SET)
+                .secondary(previous, "previously defined here");
         }
         let Some(list) = list.expect_set(self.ctx) else {
             return;
         };
         for item in list {
-            let Some(ident) = item.expect_ident(self.ctx) else {
+            let Some(ident) = item.expect_ident_or_string(self.ctx) else {
                 continue;
             };
             if let Some(previous) = self
@@ -291,12 +332,15 @@ impl<'a, 'b> TmCompiler<'a, 'b> {
             {
                 self.ctx
                     .emit_error("stack symbol redefined", item.1)
-                    .emit_info("previously defined here", previous.definition);
+                    .code(Code::REDEFINED)
+                    .secondary(previous.definition, "previously defined here");
             }
         }
 
         if list.is_empty() {
-            self.ctx.emit_error("states cannot be empty", top_level);
+            self.ctx
+                .emit_error("states cannot be empty", top_level)
+                .code(Code::EMPTY_SET);
         }
         self.symbols_def = Some(top_level);
     }
@@ -305,7 +349,8 @@ impl<'a, 'b> TmCompiler<'a, 'b> {
         if let Some(previous) = self.final_states_def {
             self.ctx
                 .emit_error("final states already set", top_level)
-                .emit_help("previously defined here", previous);
+                .code(Code::ALREADY_SET)
+                .secondary(previous, "previously defined here");
         }
         let Some(list) = list.expect_set(self.ctx) else {
             return;
@@ -320,11 +365,15 @@ impl<'a, 'b> TmCompiler<'a, 'b> {
                     .insert(State(ident), StateInfo { definition: item.1 })
                     .is_some()
                 {
-                    self.ctx.emit_error("final state redefined", item.1);
+                    self.ctx
+                        .emit_error("final state redefined", item.1)
+                        .code(Code::REDEFINED);
                 }
             } else {
                 self.ctx
-                    .emit_error("final state not defined in set of states", item.1);
+                    .emit_error("final state not defined in set of states", item.1)
+                    .code(Code::UNDEFINED_NAME)
+                    .did_you_mean(item.1, ident, self.states.keys().map(|state| state.0));
             }
         }
         self.final_states_def = Some(top_level);
@@ -340,16 +389,24 @@ impl<'a, 'b> TmCompiler<'a, 'b> {
                 if let Some((_, previous)) = self.initial_state {
                     self.ctx
                         .emit_error("initial state already set", top_level)
-                        .emit_help("previously defined here", previous);
+                        .code(Code::ALREADY_SET)
+                        .secondary(previous, "previously defined here");
                 }
                 if self.states.contains_key(&State(ident)) {
                     self.initial_state = Some((State(ident), top_level))
                 } else {
                     self.ctx
-                        .emit_error("initial state symbol not defined as a state", src_d);
+                        .emit_error("initial state symbol not defined as a state", src_d)
+                        .code(Code::UNDEFINED_NAME)
+                        .did_you_mean(src_d, ident, self.states.keys().map(|state| state.0));
                 }
             }
-            _ => _ = self.ctx.emit_error("expected ident", src_d),
+            _ => {
+                _ = self
+                    .ctx
+                    .emit_error("expected ident", src_d)
+                    .code(Code::WRONG_ITEM)
+            }
         }
     }
 
@@ -358,21 +415,29 @@ impl<'a, 'b> TmCompiler<'a, 'b> {
         Spanned(src, src_d): Spanned<ast::Item<'a>>,
         top_level: Span,
     ) {
-        match src {
-            ast::Item::Symbol(Sym::Ident(ident)) => {
+        match src.as_symbol(self.ctx) {
+            Some(Sym::Ident(ident)) => {
                 if let Some((_, previous)) = self.blank_symbol {
                     self.ctx
                         .emit_error("blank symbol already set", top_level)
-                        .emit_help("previously defined here", previous);
+                        .code(Code::ALREADY_SET)
+                        .secondary(previous, "previously defined here");
                 }
                 if self.symbols.contains_key(&Symbol(ident)) {
                     self.blank_symbol = Some((Symbol(ident), top_level))
                 } else {
                     self.ctx
-                        .emit_error("blank symbol not defined as a symbol", src_d);
+                        .emit_error("blank symbol not defined as a symbol", src_d)
+                        .code(Code::UNDEFINED_NAME)
+                        .did_you_mean(src_d, ident, self.symbols.keys().map(|symbol| symbol.0));
                 }
             }
-            _ => _ = self.ctx.emit_error("expected ident", src_d),
+            _ => {
+                _ = self
+                    .ctx
+                    .emit_error("expected ident or string", src_d)
+                    .code(Code::WRONG_ITEM)
+            }
         }
     }
 
@@ -381,6 +446,25 @@ impl<'a, 'b> TmCompiler<'a, 'b> {
         args: Spanned<ast::Tuple<'a>>,
         function: Span,
         list: Spanned<ast::Item<'a>>,
+    ) {
+        let candidates = [
+            self.states.keys().map(|state| state.0).collect(),
+            self.symbols.keys().map(|symbol| symbol.0).collect(),
+        ];
+        let mark = self.ctx.logs_mark();
+        for (args, list) in template::expand_transition(args, list, &candidates, self.ctx) {
+            for args in args.expand_sets(self.ctx) {
+                self.compile_transition(args, function, list.clone());
+            }
+        }
+        self.ctx.dedup_logs_since(mark);
+    }
+
+    fn compile_transition(
+        &mut self,
+        args: Spanned<ast::Tuple<'a>>,
+        function: Span,
+        list: Spanned<ast::Item<'a>>,
     ) {
         let list = list.set_weak();
         let Some((from_state, from_tape)) = args.as_ref().expect_tm_transition_function(self.ctx)
@@ -389,16 +473,30 @@ impl<'a, 'b> TmCompiler<'a, 'b> {
         };
         if !self.states.contains_key(&State(from_state.0)) {
             self.ctx
-                .emit_error("transition state not defined as state", from_state.1);
+                .emit_error("transition state not defined as state", from_state.1)
+                .code(Code::UNDEFINED_STATE)
+                .did_you_mean(
+                    from_state.1,
+                    from_state.0,
+                    self.states.keys().map(|state| state.0),
+                );
             return;
         };
         if !self.symbols.contains_key(&Symbol(from_tape.0)) {
-            self.ctx.emit_error(
-                "transition tape symbol not defined as tape symbol",
-                from_tape.1,
-            );
+            self.ctx
+                .emit_error(
+                    "transition tape symbol not defined as tape symbol",
+                    from_tape.1,
+                )
+                .code(Code::UNDEFINED_SYMBOL)
+                .did_you_mean(
+                    from_tape.1,
+                    from_tape.0,
+                    self.symbols.keys().map(|symbol| symbol.0),
+                );
             return;
         };
+        let from = format!("({}, {})", from_state.0, from_tape.0);
 
         for item in list {
             let Some((to_state, to_tape, direction)) = item
@@ -410,7 +508,13 @@ impl<'a, 'b> TmCompiler<'a, 'b> {
 
             if !self.states.contains_key(&State(to_state.0)) {
                 self.ctx
-                    .emit_error("transition state not defined as state", to_state.1);
+                    .emit_error("transition state not defined as state", to_state.1)
+                    .code(Code::UNDEFINED_STATE)
+                    .did_you_mean(
+                        to_state.1,
+                        to_state.0,
+                        self.states.keys().map(|state| state.0),
+                    );
                 continue;
             };
 
@@ -424,8 +528,18 @@ impl<'a, 'b> TmCompiler<'a, 'b> {
             if let Some(entry) = entry.iter().next()
                 && !self.options.non_deterministic
             {
-                self.ctx.emit_error("transition already defined for this starting point (non determinism not permitted)", item.1)
-                            .emit_info("previously defined here", entry.1.transition);
+                self.ctx
+                    .emit_error(
+                        format!(
+                            "transition already defined for {from} (non determinism not permitted)"
+                        ),
+                        item.1,
+                    )
+                    .code(Code::NON_DETERMINISM)
+                    .label("conflicts here")
+                    .secondary(entry.1.transition, "first defined here")
+                    .secondary(from_tape.1, "starting from this tape symbol");
+                continue;
             }
             if let Some(previous) = entry.insert(
                 Transition {
@@ -440,7 +554,8 @@ impl<'a, 'b> TmCompiler<'a, 'b> {
             ) {
                 self.ctx
                     .emit_warning("duplicate transition", item.1)
-                    .emit_info("previously defined here", previous.transition);
+                    .code(Code::DUPLICATE_TRANSITION)
+                    .secondary(previous.transition, "previously defined here");
             }
         }
     }
@@ -454,11 +569,18 @@ impl<'a> Spanned<&ast::Tuple<'a>> {
         match &self.0.0[..] {
             [
                 Spanned(ast::Item::Symbol(ast::Symbol::Ident(state)), state_span),
-                Spanned(ast::Item::Symbol(ast::Symbol::Ident(tape)), tape_span),
-            ] => {
-                return Some((Spanned(state, *state_span), Spanned(*tape, *tape_span)));
+                Spanned(tape, tape_span),
+            ] if let Some(ast::Symbol::Ident(tape)) = tape.as_symbol(ctx) => {
+                return Some((Spanned(state, *state_span), Spanned(tape, *tape_span)));
+            }
+            _ => {
+                _ = ctx
+                    .emit_error(
+                        "expected TM transition function (state, symbol|{symbol, ...})",
+                        self.1,
+                    )
+                    .code(Code::MALFORMED_TRANSITION)
             }
-            _ => _ = ctx.emit_error("expected TM transition function (state, symbol)", self.1),
         }
         None
     }
@@ -470,32 +592,35 @@ impl<'a> Spanned<&ast::Tuple<'a>> {
         match &self.0.0[..] {
             [
                 Spanned(ast::Item::Symbol(ast::Symbol::Ident(state)), state_span),
-                Spanned(ast::Item::Symbol(ast::Symbol::Ident(tape)), tape_span),
+                Spanned(tape, tape_span),
                 Spanned(ast::Item::Symbol(direction), direction_span),
-            ] => {
+            ] if let Some(ast::Symbol::Ident(tape)) = tape.as_symbol(ctx) => {
                 let direction = match direction {
                     ast::Symbol::Ident("left" | "L" | "<") => Direction::Left,
                     ast::Symbol::Ident("right" | "R" | ">") => Direction::Right,
                     ast::Symbol::Epsilon(_) | ast::Symbol::Ident("~") => Direction::None,
-                    ast::Symbol::Ident(ident) => {
+                    ast::Symbol::Ident(ident) | ast::Symbol::Otherwise(ident) => {
                         ctx.emit_error(
                             format!("invalid direction specified '{ident}'"),
                             *direction_span,
-                        );
+                        )
+                        .code(Code::INVALID_DIRECTION);
                         Direction::None
                     }
                 };
                 return Some((
                     Spanned(state, *state_span),
-                    Spanned(*tape, *tape_span),
+                    Spanned(tape, *tape_span),
                     Spanned(direction, *direction_span),
                 ));
             }
             _ => {
-                _ = ctx.emit_error(
-                    "expected TM transition function (state, symbol, direction)",
-                    self.1,
-                )
+                _ = ctx
+                    .emit_error(
+                        "expected TM transition function (state, symbol, direction)",
+                        self.1,
+                    )
+                    .code(Code::MALFORMED_TRANSITION)
             }
         }
         None
@@ -1,5 +1,6 @@
 pub mod automatan;
 pub mod loader;
+pub mod sim;
 
 #[macro_export]
 macro_rules! dual_struct_serde {
@@ -1,6 +1,6 @@
-use std::{borrow::Cow, ops::Range};
+use std::{borrow::Cow, ops::RangeInclusive};
 
-use super::Spanned;
+use super::{Span, Spanned};
 
 #[derive(Clone, Debug)]
 pub enum ListKind {
@@ -17,15 +17,57 @@ pub struct Tuple<'a>(pub Vec<Spanned<Item<'a>>>);
 #[derive(Clone, Copy, Debug)]
 pub enum Symbol<'a> {
     Epsilon(&'a str),
+    /// The fallback letter of a transition, `*` or `otherwise`.
+    Otherwise(&'a str),
     Ident(&'a str),
 }
 
+impl<'a> Symbol<'a> {
+    /// The symbol as written.
+    pub fn name(&self) -> &'a str {
+        match self {
+            Symbol::Epsilon(name) | Symbol::Otherwise(name) | Symbol::Ident(name) => name,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Item<'a> {
     Symbol(Symbol<'a>),
     String(Cow<'a, str>),
+    Regex(Regex<'a>),
     Tuple(Tuple<'a>),
     List(List<'a>),
+    Template(Template<'a>),
+}
+
+/// A name built from literal parts and `{expr}` parts, like `q{(i+1)%7}`.
+#[derive(Clone, Debug)]
+pub struct Template<'a>(pub Vec<Spanned<Segment<'a>>>);
+
+#[derive(Clone, Debug)]
+pub enum Segment<'a> {
+    Literal(&'a str),
+    Expr(Spanned<Expr<'a>>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Clone, Debug)]
+pub enum Expr<'a> {
+    Int(i64),
+    Var(&'a str),
+    Neg(Box<Spanned<Expr<'a>>>),
+    Binary(BinOp, Box<Spanned<Expr<'a>>>, Box<Spanned<Expr<'a>>>),
+    /// Inclusive on both ends, only allowed as the whole expression of a segment.
+    Range(Box<Spanned<Expr<'a>>>, Box<Spanned<Expr<'a>>>),
 }
 
 #[derive(Clone, Debug)]
@@ -33,14 +75,14 @@ pub enum Regex<'a> {
     Terminal(&'a str),
     Match {
         complement: bool,
-        patterns: Vec<Range<char>>,
+        patterns: Vec<RangeInclusive<char>>,
     },
-    Concat(Vec<Regex<'a>>),
-    Star(Box<Regex<'a>>),
-    Plus(Box<Regex<'a>>),
-    Union(Vec<Regex<'a>>),
-    Intersection(Vec<Regex<'a>>),
-    Complement(Box<Regex<'a>>),
+    Concat(Vec<Spanned<Regex<'a>>>),
+    Star(Box<Spanned<Regex<'a>>>),
+    Plus(Box<Spanned<Regex<'a>>>),
+    Union(Vec<Spanned<Regex<'a>>>),
+    Intersection(Vec<Spanned<Regex<'a>>>),
+    Complement(Box<Spanned<Regex<'a>>>),
 }
 
 #[derive(Clone, Debug)]
@@ -56,6 +98,88 @@ pub enum ProductionUnit<'a> {
 #[derive(Clone, Debug)]
 pub struct ProductionGroup<'a>(pub Vec<Spanned<ProductionUnit<'a>>>);
 
+#[derive(Clone, Debug)]
+pub struct TableRow<'a> {
+    pub label: Spanned<Item<'a>>,
+    pub cells: Vec<Spanned<Option<Item<'a>>>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Table<'a> {
+    pub name: Spanned<&'a str>,
+    pub columns: Vec<Spanned<Item<'a>>>,
+    pub rows: Vec<TableRow<'a>>,
+}
+
+impl<'a> Table<'a> {
+    /// Expands every non empty cell into the arguments, function span and
+    /// destination an equivalent `name(row, column...) = cell` line would have.
+    ///
+    /// Tuple columns are flattened into the arguments, so `(a, Z0)` as a column
+    /// and `q0` as a row becomes `(q0, a, Z0)`.
+    pub fn transitions(
+        &self,
+    ) -> impl Iterator<Item = (Spanned<Tuple<'a>>, Span, Spanned<Item<'a>>)> + '_ {
+        self.rows.iter().flat_map(move |row| {
+            row.cells
+                .iter()
+                .zip(&self.columns)
+                .filter_map(move |(cell, column)| {
+                    let item = cell.0.clone()?;
+                    let mut args = vec![row.label.clone()];
+                    match &column.0 {
+                        Item::Tuple(tuple) => args.extend(tuple.0.iter().cloned()),
+                        _ => args.push(column.clone()),
+                    }
+                    Some((
+                        Spanned(Tuple(args), column.1),
+                        cell.1,
+                        Spanned(item, cell.1),
+                    ))
+                })
+        })
+    }
+}
+
+impl<'a> Spanned<Tuple<'a>> {
+    /// Expands every set argument, like the `{a, b}` of `(q0, {a, b})`, into
+    /// one tuple per element. Each element keeps its own span, an empty set is
+    /// an error.
+    pub fn expand_sets(self, ctx: &mut Context<'a>) -> Vec<Spanned<Tuple<'a>>> {
+        let Spanned(Tuple(args), span) = self;
+        let mut expanded = vec![Vec::new()];
+        for arg in args {
+            let elements = match arg {
+                Spanned(
+                    Item::List(List(elements, ListKind::Brace | ListKind::BraceComma)),
+                    set,
+                ) => {
+                    if elements.is_empty() {
+                        ctx.emit_error("empty set in transition argument", set)
+                            .code(Code::EMPTY_SET);
+                    }
+                    elements
+                }
+                arg => vec![arg],
+            };
+            expanded = expanded
+                .into_iter()
+                .flat_map(|prefix| {
+                    elements.iter().map(move |element| {
+                        let mut args = prefix.clone();
+                        args.push(element.clone());
+                        args
+                    })
+                })
+                .collect();
+        }
+        expanded
+            .into_iter()
+            .map(|args| Spanned(Tuple(args), span))
+            .collect()
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum TopLevel<'a> {
     Item(Spanned<&'a str>, Spanned<Item<'a>>),
@@ -67,18 +191,57 @@ pub enum TopLevel<'a> {
         Spanned<ProductionGroup<'a>>,
         Spanned<Vec<Spanned<ProductionGroup<'a>>>>,
     ),
-    Table(),
+    Table(Table<'a>),
 }
 
-use crate::loader::{Context, log::LogSink};
+use crate::loader::{
+    Context,
+    codes::Code,
+    log::{Applicability, LogSink},
+};
+
+impl<'a> Item<'a> {
+    /// The symbol an ident or string literal names. Strings are never epsilon,
+    /// which is what lets `"~"` be used as a letter.
+    pub fn as_symbol(&self, ctx: &Context<'a>) -> Option<Symbol<'a>> {
+        match self {
+            Item::Symbol(symbol) => Some(*symbol),
+            Item::String(Cow::Borrowed(string)) => Some(Symbol::Ident(string)),
+            Item::String(Cow::Owned(string)) => Some(Symbol::Ident(ctx.intern(string))),
+            _ => None,
+        }
+    }
+}
 
 impl<'a> Spanned<Item<'a>> {
     pub fn expect_symbol(&self, ctx: &mut Context<'a>) -> Option<Symbol<'a>> {
         match &self.0 {
             Item::Symbol(sym) => return Some(*sym),
-            Item::Tuple(_) => _ = ctx.emit_error("expected ident found tuple", self.1),
-            Item::List(_) => _ = ctx.emit_error("expected ident found list", self.1),
-            Item::String(_) => _ = ctx.emit_error("expected ident found string", self.1),
+            Item::Tuple(_) => {
+                _ = ctx
+                    .emit_error("expected ident found tuple", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::List(_) => {
+                _ = ctx
+                    .emit_error("expected ident found list", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::String(_) => {
+                _ = ctx
+                    .emit_error("expected ident found string", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Regex(_) => {
+                _ = ctx
+                    .emit_error("expected ident found regex", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Template(_) => {
+                _ = ctx
+                    .emit_error("expected ident found template", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
         }
         None
     }
@@ -87,11 +250,85 @@ impl<'a> Spanned<Item<'a>> {
         match &self.0 {
             Item::Symbol(Symbol::Ident(ident)) => return Some(ident),
             Item::Symbol(Symbol::Epsilon(_)) => {
-                _ = ctx.emit_error("expected ident found epsilon", self.1)
+                _ = ctx
+                    .emit_error("expected ident found epsilon", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Symbol(Symbol::Otherwise(_)) => {
+                _ = ctx
+                    .emit_error("expected ident found otherwise", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Tuple(_) => {
+                _ = ctx
+                    .emit_error("expected ident found tuple", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::List(_) => {
+                _ = ctx
+                    .emit_error("expected ident found list", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::String(_) => {
+                _ = ctx
+                    .emit_error("expected ident found string", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Regex(_) => {
+                _ = ctx
+                    .emit_error("expected ident found regex", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Template(_) => {
+                _ = ctx
+                    .emit_error("expected ident found template", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+        }
+        None
+    }
+
+    /// Like [`Self::expect_ident`] but also accepts string literals, for letters
+    /// and symbols which can't be written as an ident.
+    pub fn expect_ident_or_string(&self, ctx: &mut Context<'a>) -> Option<&'a str> {
+        match &self.0 {
+            Item::Symbol(Symbol::Ident(ident)) => return Some(ident),
+            Item::String(Cow::Borrowed(string)) => return Some(string),
+            Item::String(Cow::Owned(string)) => return Some(ctx.intern(string)),
+            Item::Symbol(Symbol::Epsilon(repr)) => {
+                _ = ctx
+                    .emit_error("expected ident or string found epsilon", self.1)
+                    .code(Code::WRONG_ITEM)
+                    .help("quote it to use it literally: \"~\"")
+                    .suggest(self.1, format!("{repr:?}"), Applicability::MaybeIncorrect)
+            }
+            Item::Symbol(Symbol::Otherwise(repr)) => {
+                _ = ctx
+                    .emit_error("expected ident or string found otherwise", self.1)
+                    .code(Code::WRONG_ITEM)
+                    .help("quote it to use it literally: \"*\"")
+                    .suggest(self.1, format!("{repr:?}"), Applicability::MaybeIncorrect)
+            }
+            Item::Tuple(_) => {
+                _ = ctx
+                    .emit_error("expected ident or string found tuple", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::List(_) => {
+                _ = ctx
+                    .emit_error("expected ident or string found list", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Regex(_) => {
+                _ = ctx
+                    .emit_error("expected ident or string found regex", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Template(_) => {
+                _ = ctx
+                    .emit_error("expected ident or string found template", self.1)
+                    .code(Code::WRONG_ITEM)
             }
-            Item::Tuple(_) => _ = ctx.emit_error("expected ident found tuple", self.1),
-            Item::List(_) => _ = ctx.emit_error("expected ident found list", self.1),
-            Item::String(_) => _ = ctx.emit_error("expected ident found string", self.1),
         }
         None
     }
@@ -99,13 +336,40 @@ impl<'a> Spanned<Item<'a>> {
     pub fn expect_set(&self, ctx: &mut Context<'a>) -> Option<&[Spanned<Item<'a>>]> {
         match &self.0 {
             Item::Symbol(Symbol::Ident(_)) => {
-                _ = ctx.emit_error("expected set found ident", self.1)
+                _ = ctx
+                    .emit_error("expected set found ident", self.1)
+                    .code(Code::WRONG_ITEM)
             }
             Item::Symbol(Symbol::Epsilon(_)) => {
-                _ = ctx.emit_error("expected set found epsilon", self.1)
+                _ = ctx
+                    .emit_error("expected set found epsilon", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Symbol(Symbol::Otherwise(_)) => {
+                _ = ctx
+                    .emit_error("expected set found otherwise", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Tuple(_) => {
+                _ = ctx
+                    .emit_error("expected set found tuple", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::String(_) => {
+                _ = ctx
+                    .emit_error("expected set found string", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Regex(_) => {
+                _ = ctx
+                    .emit_error("expected set found regex", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Template(_) => {
+                _ = ctx
+                    .emit_error("expected set found template", self.1)
+                    .code(Code::WRONG_ITEM)
             }
-            Item::Tuple(_) => _ = ctx.emit_error("expected set found tuple", self.1),
-            Item::String(_) => _ = ctx.emit_error("expected set found string", self.1),
             Item::List(list) => return Some(&list.0),
         }
         None
@@ -114,13 +378,40 @@ impl<'a> Spanned<Item<'a>> {
     pub fn expect_list(&self, ctx: &mut Context<'a>) -> Option<&[Spanned<Item<'a>>]> {
         match &self.0 {
             Item::Symbol(Symbol::Ident(_)) => {
-                _ = ctx.emit_error("expected list found ident", self.1)
+                _ = ctx
+                    .emit_error("expected list found ident", self.1)
+                    .code(Code::WRONG_ITEM)
             }
             Item::Symbol(Symbol::Epsilon(_)) => {
-                _ = ctx.emit_error("expected list found epsilon", self.1)
+                _ = ctx
+                    .emit_error("expected list found epsilon", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Symbol(Symbol::Otherwise(_)) => {
+                _ = ctx
+                    .emit_error("expected list found otherwise", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Tuple(_) => {
+                _ = ctx
+                    .emit_error("expected list found tuple", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::String(_) => {
+                _ = ctx
+                    .emit_error("expected list found string", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Regex(_) => {
+                _ = ctx
+                    .emit_error("expected list found regex", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Template(_) => {
+                _ = ctx
+                    .emit_error("expected list found template", self.1)
+                    .code(Code::WRONG_ITEM)
             }
-            Item::Tuple(_) => _ = ctx.emit_error("expected list found tuple", self.1),
-            Item::String(_) => _ = ctx.emit_error("expected list found string", self.1),
             Item::List(list) => return Some(&list.0),
         }
         None
@@ -140,17 +431,91 @@ impl<'a> Spanned<Item<'a>> {
         }
     }
 
+    pub fn expect_regex(&self, ctx: &mut Context<'a>) -> Option<Spanned<&Regex<'a>>> {
+        match &self.0 {
+            Item::Symbol(Symbol::Ident(_)) => {
+                _ = ctx
+                    .emit_error("expected regex found ident", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Symbol(Symbol::Epsilon(_)) => {
+                _ = ctx
+                    .emit_error("expected regex found epsilon", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Symbol(Symbol::Otherwise(_)) => {
+                _ = ctx
+                    .emit_error("expected regex found otherwise", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Tuple(_) => {
+                _ = ctx
+                    .emit_error("expected regex found tuple", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::List(_) => {
+                _ = ctx
+                    .emit_error("expected regex found list", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::String(_) => {
+                ctx.emit_error("expected regex found string", self.1)
+                    .code(Code::WRONG_ITEM)
+                    .help("prefix the string with 'r': r\"...\"")
+                    .suggest(
+                        Span(self.1.0, self.1.0, self.1.2),
+                        "r",
+                        Applicability::MaybeIncorrect,
+                    );
+            }
+            Item::Regex(regex) => return Some(Spanned(regex, self.1)),
+            Item::Template(_) => {
+                _ = ctx
+                    .emit_error("expected regex found template", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+        }
+        None
+    }
+
     pub fn expect_tuple(&self, ctx: &mut Context<'a>) -> Option<Spanned<&Tuple<'a>>> {
         match &self.0 {
             Item::Symbol(Symbol::Ident(_)) => {
-                _ = ctx.emit_error("expected tuple found ident", self.1)
+                _ = ctx
+                    .emit_error("expected tuple found ident", self.1)
+                    .code(Code::WRONG_ITEM)
             }
             Item::Symbol(Symbol::Epsilon(_)) => {
-                _ = ctx.emit_error("expected tuple found epsilon", self.1)
+                _ = ctx
+                    .emit_error("expected tuple found epsilon", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Symbol(Symbol::Otherwise(_)) => {
+                _ = ctx
+                    .emit_error("expected tuple found otherwise", self.1)
+                    .code(Code::WRONG_ITEM)
             }
             Item::Tuple(tuple) => return Some(Spanned(tuple, self.1)),
-            Item::List(_) => _ = ctx.emit_error("expected tuple found list", self.1),
-            Item::String(_) => _ = ctx.emit_error("expected tuple found string", self.1),
+            Item::List(_) => {
+                _ = ctx
+                    .emit_error("expected tuple found list", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::String(_) => {
+                _ = ctx
+                    .emit_error("expected tuple found string", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Regex(_) => {
+                _ = ctx
+                    .emit_error("expected tuple found regex", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
+            Item::Template(_) => {
+                _ = ctx
+                    .emit_error("expected tuple found template", self.1)
+                    .code(Code::WRONG_ITEM)
+            }
         }
         None
     }
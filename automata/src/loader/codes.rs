@@ -0,0 +1,678 @@
+//! Stable codes for diagnostics and the catalogue explaining them.
+//!
+//! Every [`LogEntry`](super::log::LogEntry) reported on its own, as opposed
+//! to a note attached to another entry, carries a code like `A0012`. Codes
+//! never change meaning once assigned, so they can be searched for and
+//! looked up with `automata explain A0012` even if the message is reworded.
+
+use std::{fmt::Display, str::FromStr};
+
+/// A diagnostic code, displayed as `A` followed by four digits.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Code(u16);
+
+struct Entry {
+    code: Code,
+    summary: &'static str,
+    explanation: &'static str,
+}
+
+macro_rules! codes {
+    ($($name:ident = $number:literal, $summary:literal, $explanation:literal;)*) => {
+        impl Code {
+            $(pub const $name: Code = Code($number);)*
+        }
+
+        const CATALOGUE: &[Entry] = &[$(Entry {
+            code: Code::$name,
+            summary: $summary,
+            explanation: $explanation,
+        }),*];
+    };
+}
+
+impl Code {
+    pub fn number(self) -> u16 {
+        self.0
+    }
+
+    /// Every code in the catalogue, in order.
+    pub fn all() -> impl Iterator<Item = Code> {
+        CATALOGUE.iter().map(|entry| entry.code)
+    }
+
+    fn entry(self) -> &'static Entry {
+        CATALOGUE
+            .iter()
+            .find(|entry| entry.code == self)
+            .expect("codes are only constructed from the catalogue")
+    }
+
+    /// A one line description, like `transition letter not defined in
+    /// alphabet`.
+    pub fn summary(self) -> &'static str {
+        self.entry().summary
+    }
+
+    /// A longer explanation with examples.
+    pub fn explanation(self) -> &'static str {
+        self.entry().explanation
+    }
+}
+
+impl Display for Code {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "A{:04}", self.0)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UnknownCode;
+
+impl Display for UnknownCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown diagnostic code")
+    }
+}
+
+impl FromStr for Code {
+    type Err = UnknownCode;
+
+    /// Parses codes like `A0012`, the `A` and leading zeros are optional.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.strip_prefix(['A', 'a']).unwrap_or(s);
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(UnknownCode);
+        }
+        let number = digits.parse::<u16>().map_err(|_| UnknownCode)?;
+        Code::all().find(|code| code.0 == number).ok_or(UnknownCode)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Code {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+codes! {
+    LEXER = 1, "text that isn't a token",
+r#"The file contains text which can't start any token, or a string or block
+comment which is never closed.
+
+    Q = {q0, q1} $
+                 ^ `$` isn't part of any token
+
+Remove the character, or put it in a string if it's meant to be a letter:
+
+    E = {a, "$"}"#;
+
+    INVALID_ESCAPE = 2, "invalid escape in a string",
+r#"A backslash in a string must be followed by one of the escapes
+\n \t \r \0 \\ \" \' or \u{...} with the hex value of a unicode scalar.
+
+    E = {"\q"}
+
+Escape the backslash to use it literally:
+
+    E = {"\\q"}"#;
+
+    UNEXPECTED_TOKEN = 3, "unexpected token",
+r#"The parser found a token where it can't appear. The message lists what
+would be valid instead.
+
+    Q = = {q0}
+
+Each line is either a definition `name = value`, a transition
+`d(args) = value`, a production `S -> ...` or a table."#;
+
+    UNCLOSED = 4, "bracket is never closed",
+r#"A `(`, `{` or `[` isn't closed. Brackets can span lines, a line continues
+the open bracket unless it has a `=`, `->` or `|` outside of its own
+brackets before closing the open one, which makes it a new item instead.
+
+    Q = {q0,
+    d(q0, a) = q1
+
+Close the bracket, on the same line or one continuing it:
+
+    Q = {q0,
+         q1}
+    d(q0, a) = q1"#;
+
+    INCONSISTENT_COMMAS = 5, "inconsistent comma delimiting",
+r#"The items of a list are either all delimited by commas or all delimited by
+spaces, mixing both is usually a missing comma.
+
+    Q = {q0, q1 q2}
+
+Use commas everywhere, `automata check --fix` does this for you:
+
+    Q = {q0, q1, q2}"#;
+
+    INVALID_INTEGER = 6, "invalid integer in a template",
+r#"An integer in a template expression doesn't fit in 64 bits.
+
+    Q = {q{0..99999999999999999999}}
+
+Use a smaller integer."#;
+
+    INVALID_RANGE = 7, "invalid letter range",
+r#"A letter range `{first..last}` needs single letter bounds with the first
+not coming after the last, and can have at most 4096 letters.
+
+    E = {z..a}
+
+Write the bounds in order:
+
+    E = {a..z}"#;
+
+    EMPTY_PRODUCTION = 8, "empty production",
+r#"A production rule or a group in one has nothing in it. Use epsilon to
+produce the empty string.
+
+    S -> a S b |
+
+Write epsilon explicitly:
+
+    S -> a S b | ~"#;
+
+    MALFORMED_TABLE = 9, "malformed table",
+r#"A transition table needs at least one column, and every row needs exactly
+one cell per column.
+
+    d  | a  b
+    q0 | q1
+
+Fill in every cell, `-` marks a missing transition:
+
+    d  | a  b
+    q0 | q1 -"#;
+
+    INCLUDE_FAILED = 10, "file cannot be included",
+r#"An `include` directive names a file which can't be read, because it
+doesn't exist or the source has no access to other files.
+
+    include "missing.txt"
+
+Check the path, it's relative to the including file."#;
+
+    INCLUDE_CYCLE = 11, "include cycle",
+r#"A file includes itself, directly or through other files, which would
+never end.
+
+    // a.txt
+    include "b.txt"
+    // b.txt
+    include "a.txt"
+
+Move what both files need into a third file which both include."#;
+
+    UNDEFINED_LETTER = 12, "transition letter not defined in alphabet",
+r#"A transition reads a letter which isn't in the alphabet. Every letter a
+transition reads must be defined in E.
+
+    E = {a, b}
+    d(q0, c) = q1
+
+Add the letter to the alphabet, or fix the transition:
+
+    E = {a, b, c}
+    d(q0, c) = q1"#;
+
+    UNDEFINED_STATE = 13, "transition state not defined as state",
+r#"A transition starts from or goes to a state which isn't in Q.
+
+    Q = {q0, q1}
+    d(q0, a) = q2
+
+Add the state, or fix the transition:
+
+    Q = {q0, q1, q2}
+    d(q0, a) = q2"#;
+
+    UNDEFINED_SYMBOL = 14, "transition symbol not defined",
+r#"A transition uses a stack symbol of a PDA which isn't in G, or a tape
+symbol of a TM which isn't in its symbols.
+
+    G = {Z0, A}
+    d(q0, a, B) = (q0, [A B])
+
+Add the symbol, or fix the transition:
+
+    G = {Z0, A, B}"#;
+
+    NON_DETERMINISM = 15, "non determinism not permitted",
+r#"A deterministic machine (DFA, DPDA or TM) has two transitions from the
+same state reading the same input, so what it does next isn't determined.
+
+    type = DFA
+    d(q0, a) = q1
+    d(q0, a) = q2
+
+Remove one of the transitions, or use the non deterministic type (NFA,
+NPDA or NTM) if this is intended."#;
+
+    DUPLICATE_TRANSITION = 16, "duplicate transition",
+r#"The same transition is defined twice, the second has no effect.
+
+    d(q0, a) = {q1, q2}
+    d(q0, a) = q1
+
+Remove the duplicate."#;
+
+    EPSILON_MOVE = 17, "epsilon moves not permitted",
+r#"A deterministic machine can't have transitions which read no input.
+
+    type = DFA
+    d(q0, ~) = q1
+
+Use an NFA or NPDA for epsilon moves, or read a letter."#;
+
+    MALFORMED_TRANSITION = 18, "malformed transition",
+r#"The arguments or result of a transition don't have the shape the machine
+expects:
+
+    FA   d(state, letter) = state
+    PDA  d(state, letter, symbol) = (state, [symbols...])
+    TM   d(state, symbol) = (state, symbol, L|R)
+
+Letters and symbols can also be epsilon or a set like {a, b}."#;
+
+    INVALID_DIRECTION = 19, "invalid tape direction",
+r#"The direction a TM moves its head must be `L` or `R` (`<` and `>` work
+too).
+
+    d(q0, a) = (q1, b, Left)
+
+Use the short form:
+
+    d(q0, a) = (q1, b, L)"#;
+
+    WRONG_ITEM = 20, "item of the wrong kind",
+r#"A definition has a value of the wrong kind, like a set where a single
+name is expected.
+
+    q0 = {q0}
+
+Use a value of the kind named in the message:
+
+    q0 = q0"#;
+
+    MISSING_TYPE = 21, "missing type",
+r#"The first line of a machine file must say which kind of machine it is.
+
+    Q = {q0}
+
+Add the type first:
+
+    type = DFA
+    Q = {q0}"#;
+
+    UNKNOWN_TYPE = 22, "unknown machine type",
+r#"The type must be one of DFA, NFA, DPDA, NPDA, TM, NTM, REGEX or CFG.
+
+    type = automaton"#;
+
+    UNKNOWN_ITEM = 23, "unknown item",
+r#"A definition names something the machine doesn't have. The message
+lists the names each machine accepts.
+
+    type = DFA
+    G = {Z0}
+
+Stack symbols only exist for PDAs, remove the line or change the type."#;
+
+    UNEXPECTED_FUNCTION = 24, "unexpected function",
+r#"A transition, production or table is used by a machine which has no
+such thing, like productions in a DFA or a function other than d.
+
+    type = DFA
+    f(q0, a) = q1
+
+Transitions are written with d (or δ):
+
+    d(q0, a) = q1"#;
+
+    ALREADY_SET = 25, "definition already set",
+r#"Something is defined twice, the second definition is ignored.
+
+    Q = {q0, q1}
+    Q = {q2}
+
+Merge the definitions:
+
+    Q = {q0, q1, q2}"#;
+
+    REDEFINED = 26, "name redefined",
+r#"A set lists the same name twice.
+
+    Q = {q0, q1, q0}
+
+Remove the repeated name."#;
+
+    EMPTY_SET = 27, "set cannot be empty",
+r#"A machine needs at least one state, letter or variable.
+
+    E = {}
+
+A set in the arguments of a transition stands for one transition per
+element, so an empty one would define no transitions at all:
+
+    d(q0, {}) = q1"#;
+
+    INVALID_LETTER = 28, "invalid letter",
+r#"Letters are single characters, strings longer than one character or
+empty strings can't be letters.
+
+    E = {ab}
+
+Split it into letters:
+
+    E = {a, b}"#;
+
+    NEVER_DEFINED = 29, "required definition missing",
+r#"The machine is missing a definition it can't do without, like its states
+or alphabet.
+
+    type = DFA
+    E = {a}
+
+Add the missing definition:
+
+    Q = {q0}"#;
+
+    DEFAULTED = 30, "definition defaulted",
+r#"An optional definition is missing so its default is used, like `q0` for
+the initial state, `Z0` for the initial stack symbol and `B` for the blank
+symbol.
+
+Define it explicitly to silence this:
+
+    q0 = q0"#;
+
+    UNDEFINED_NAME = 31, "name not defined",
+r#"A definition refers to a name which isn't defined, like a final state
+which isn't a state.
+
+    Q = {q0, q1}
+    F = {q2}
+
+Define the name, or fix the reference:
+
+    F = {q1}"#;
+
+    NO_TRANSITIONS = 32, "no transitions defined",
+r#"The machine has no transitions, so it can only accept trivially. This is
+the `no_transitions` lint.
+
+    type = DFA
+    Q = {q0}
+    E = {a}
+    F = {q0}
+
+Add transitions:
+
+    d(q0, a) = q0"#;
+
+    INVALID_ACCEPT_BY = 33, "invalid accept by",
+r#"A PDA accepts either by final state or by empty stack. Final states only
+mean something when accepting by final state.
+
+    accept = empty
+    F = {q1}
+
+Remove F, or accept by final state:
+
+    accept = final"#;
+
+    OTHERWISE_NEVER_APPLIES = 34, "otherwise transition never applies",
+r#"An otherwise transition `*` only applies to letters without a transition
+of their own, here every letter already has one.
+
+    E = {a, b}
+    d(q0, a) = q1
+    d(q0, b) = q0
+    d(q0, *) = q2
+
+Remove the otherwise transition."#;
+
+    UNMATCHED_TEMPLATE = 35, "template doesn't match any defined name",
+r#"A template like `q{i}` is matched against the defined names to find the
+values of its variables, and this one matches none.
+
+    Q = {q0, q1}
+    d(p{i}, a) = q{i}
+
+Check the template against the names it should match."#;
+
+    UNINFERRABLE_VARIABLE = 36, "cannot infer template variable",
+r#"Variables of a template are inferred from the arguments of a transition,
+a variable only used in its result has no values.
+
+    d(q0, a) = q{i}
+
+Use the variable in the arguments too:
+
+    d(q{i}, a) = q{i + 1}"#;
+
+    TEMPLATE_EVALUATION = 37, "template cannot be evaluated",
+r#"A template expression can't be evaluated, because it divides by zero,
+overflows, uses an unbound variable or expands to too many names.
+
+    Q = {q{1 / 0}}"#;
+
+    REGEX_SYNTAX = 38, "invalid regex",
+r#"A regex string isn't a valid regular expression, the message says what's
+wrong.
+
+    regex = r"(a|b"
+
+Close the group:
+
+    regex = r"(a|b)""#;
+
+    REGEX_ALPHABET = 39, "regex needs an explicit alphabet",
+r#"Without an alphabet the alphabet of a regex is inferred from its letters,
+which doesn't work for wildcards, negated classes or large ranges.
+
+    regex = r"a.b"
+
+Define the alphabet:
+
+    E = {a, b, c}
+    regex = r"a.b""#;
+
+    REGEX_UNDEFINED_LETTER = 40, "regex letter not defined in alphabet",
+r#"A regex uses a letter which isn't in the alphabet.
+
+    E = {a, b}
+    regex = r"a*c"
+
+Add the letter or fix the regex."#;
+
+    REGEX_TOO_COMPLEX = 41, "regex too complex to determinize",
+r#"Turning the regex into a DFA takes too many states. Simplify the regex or
+shrink its alphabet."#;
+
+    NAME_CONFLICT = 42, "name is both a variable and a letter",
+r#"A grammar's variables and terminals must be distinct, otherwise its
+productions are ambiguous.
+
+    V = {S, a}
+    E = {a, b}
+
+Rename one of them."#;
+
+    INVALID_PRODUCTION_HEAD = 43, "invalid production head",
+r#"The head of a production, left of `->`, must be a single variable.
+
+    S a -> b
+
+Write one production per variable:
+
+    S -> b"#;
+
+    MISPLACED_EPSILON = 44, "epsilon in a non empty production",
+r#"Epsilon is the empty string so it does nothing next to other units.
+
+    S -> a ~ b
+
+Remove it:
+
+    S -> a b"#;
+
+    DUPLICATE_PRODUCTION = 45, "duplicate production",
+r#"The same production is defined twice, the second has no effect.
+
+    S -> a S | a S
+
+Remove the duplicate."#;
+
+    UNDEFINED_TERMINAL = 46, "terminal not defined in alphabet",
+r#"A production uses a terminal with a letter which isn't in the alphabet.
+
+    E = {a}
+    S -> "ab"
+
+Add the letter or fix the production."#;
+
+    EMPTY_TERMINAL = 47, "empty terminal string",
+r#"An empty string produces nothing, like epsilon.
+
+    S -> "" | a S
+
+Use epsilon instead:
+
+    S -> ~ | a S"#;
+
+    VARIABLE_WITHOUT_PRODUCTIONS = 48, "variable has no productions",
+r#"A variable has no productions so no string can be derived from it, and
+any production using it never completes.
+
+    V = {S, T}
+    S -> a T
+
+Add productions for it:
+
+    T -> b"#;
+
+    UNREACHABLE_STATE = 49, "state is unreachable",
+r#"No sequence of transitions leads from the initial state to this state, so
+its transitions never apply. This is the `unreachable_states` lint.
+
+    Q = {q0, q1, q2}
+    d(q0, a) = q1
+
+Add a transition into it, or remove it."#;
+
+    DEAD_STATE = 50, "dead state",
+r#"No final state can be reached from this state, so every input which gets
+there is rejected. This is the `dead_states` lint.
+
+    F = {q1}
+    d(q0, a) = q1
+    d(q0, b) = q2
+    d(q2, a) = q2
+
+A DFA which has to be total may need one such trap state, otherwise it can
+usually be removed."#;
+
+    UNUSED_LETTER = 51, "letter is never used",
+r#"A letter of the alphabet isn't read by any transition. This is the
+`unused_letters` lint.
+
+    E = {a, b}
+    d(q0, a) = q0
+
+Remove it from the alphabet, or add transitions reading it."#;
+
+    UNUSED_SYMBOL = 52, "symbol is never used",
+r#"A stack or tape symbol isn't read or written by any transition. This is
+the `unused_symbols` lint.
+
+    T = {z0, A, B}
+    d(q0, a, z0) = (q0, [A z0])
+
+Remove it, or add transitions using it."#;
+
+    UNREACHABLE_FINAL_STATE = 53, "final state is unreachable",
+r#"No sequence of transitions leads from the initial state to this final
+state, so the machine never accepts by entering it. This is the
+`unreachable_final_states` lint.
+
+    F = {q2}
+    d(q0, a) = q1
+
+Add a transition into it."#;
+
+    NON_TOTAL_DFA = 54, "DFA transition function is not total",
+r#"A state of a DFA has no transition for some letter, so the DFA rejects
+when it reads that letter there. This is the `non_total_dfa` lint.
+
+    E = {a, b}
+    d(q0, a) = q0
+
+Add the missing transitions, an otherwise transition covers every letter
+without one:
+
+    d(q0, otherwise) = trap"#;
+
+    STUCK_STATE = 55, "non final state has no transitions",
+r#"A TM state which isn't final has no transitions, so the machine halts and
+rejects when it enters it. This is the `stuck_states` lint.
+
+    F = {accept}
+    d(q0, a) = (q1, a, >)
+
+Add transitions for it, or make it final."#;
+
+    UNKNOWN_LINT = 56, "unknown lint",
+r#"An `allow` or `deny` item names a lint which doesn't exist.
+
+    allow = {unused_letter}
+
+Lints are named in the plural, `automata explain` on the code of a warning
+says which lint reports it:
+
+    allow = {unused_letters}"#;
+
+    NO_PRODUCTIONS = 57, "no productions defined",
+r#"A grammar has no production rules, so it derives nothing.
+
+    type = CFG
+
+Add productions for the start variable:
+
+    S -> a S b | ~"#;
+
+    INVALID_FILES = 58, "files to include are malformed",
+r#"The files given alongside the input, for its `include` directives to
+read, aren't a JSON object of paths to their contents. The input is
+compiled as if there were no other files.
+
+    {"states.txt": ["Q = {q0}"]}
+
+Give each file's contents as one string:
+
+    {"states.txt": "Q = {q0}"}"#;
+}
+
+#[test]
+fn catalogue() {
+    let mut previous = None;
+    for code in Code::all() {
+        assert!(previous < Some(code), "{code} is out of order");
+        assert_eq!(code.to_string().parse(), Ok(code));
+        assert!(!code.summary().is_empty() && !code.explanation().is_empty());
+        previous = Some(code);
+    }
+    assert_eq!("A0012".parse(), Ok(Code::UNDEFINED_LETTER));
+    assert_eq!("12".parse(), Ok(Code::UNDEFINED_LETTER));
+    assert_eq!("A9999".parse::<Code>(), Err(UnknownCode));
+    assert_eq!(
+        Code::UNDEFINED_LETTER.summary(),
+        "transition letter not defined in alphabet"
+    );
+}
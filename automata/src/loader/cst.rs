@@ -0,0 +1,286 @@
+//! A lossless concrete syntax tree.
+//!
+//! Unlike the [`ast`](super::ast), which the parser builds from the
+//! significant tokens only, the tree keeps whitespace and comments so writing
+//! it back out reproduces the source byte for byte. This is what tools which
+//! rewrite machine files, like the [formatter](super::fmt), work on.
+
+use std::fmt::Display;
+
+use crate::loader::{
+    Span, Spanned,
+    lexer::{self, Lexer, Token},
+    parser::line_continues,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LeafKind<'a> {
+    Whitespace,
+    /// A line end inside a bracket, which doesn't end the line.
+    LineEnd,
+    Comment,
+    Token(Token<'a>),
+    Error(lexer::Error),
+}
+
+/// A piece of source text, the text of all leaves in order is the source.
+#[derive(Clone, Copy, Debug)]
+pub struct Leaf<'a> {
+    pub kind: LeafKind<'a>,
+    pub text: &'a str,
+    pub span: Span,
+}
+
+impl<'a> Leaf<'a> {
+    pub fn token(&self) -> Option<Token<'a>> {
+        match self.kind {
+            LeafKind::Token(token) => Some(token),
+            _ => None,
+        }
+    }
+
+    /// Whether the leaf is whitespace, a comment or a line end in a bracket.
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self.kind,
+            LeafKind::Whitespace | LeafKind::LineEnd | LeafKind::Comment
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Node<'a> {
+    Leaf(Leaf<'a>),
+    /// Everything between a bracket and its closing bracket, `close` is `None`
+    /// if the bracket is never closed.
+    Group {
+        open: Leaf<'a>,
+        children: Vec<Node<'a>>,
+        close: Option<Leaf<'a>>,
+    },
+}
+
+impl<'a> Node<'a> {
+    pub fn leaf(&self) -> Option<&Leaf<'a>> {
+        match self {
+            Node::Leaf(leaf) => Some(leaf),
+            Node::Group { .. } => None,
+        }
+    }
+
+    pub fn token(&self) -> Option<Token<'a>> {
+        self.leaf().and_then(Leaf::token)
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        self.leaf().is_some_and(Leaf::is_trivia)
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Node::Leaf(leaf) => leaf.span,
+            Node::Group {
+                open,
+                children,
+                close,
+            } => open.span.join(
+                close
+                    .map(|close| close.span)
+                    .or_else(|| children.last().map(Node::span))
+                    .unwrap_or(open.span),
+            ),
+        }
+    }
+}
+
+impl<'a> Display for Node<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Node::Leaf(leaf) => write!(f, "{}", leaf.text),
+            Node::Group {
+                open,
+                children,
+                close,
+            } => {
+                write!(f, "{}", open.text)?;
+                for child in children {
+                    write!(f, "{child}")?;
+                }
+                if let Some(close) = close {
+                    write!(f, "{}", close.text)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A top level line, brackets can continue it over several source lines.
+#[derive(Clone, Debug)]
+pub struct Line<'a> {
+    pub nodes: Vec<Node<'a>>,
+    /// The line end, `None` for the last line if the source doesn't end with
+    /// one.
+    pub end: Option<Leaf<'a>>,
+}
+
+impl<'a> Line<'a> {
+    /// The nodes which aren't whitespace or comments.
+    pub fn significant(&self) -> impl Iterator<Item = &Node<'a>> {
+        self.nodes.iter().filter(|node| !node.is_trivia())
+    }
+}
+
+impl<'a> Display for Line<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for node in &self.nodes {
+            write!(f, "{node}")?;
+        }
+        if let Some(end) = self.end {
+            write!(f, "{}", end.text)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Cst<'a> {
+    pub lines: Vec<Line<'a>>,
+}
+
+impl<'a> Cst<'a> {
+    /// Builds the tree of `src`, this never fails, unlexable text becomes
+    /// [`LeafKind::Error`] leaves and unclosed brackets groups without a close.
+    pub fn parse(src: &'a str) -> Self {
+        let mut lexer = Lexer::new(src);
+        let mut position = 0;
+        let mut lines = Vec::new();
+        let mut line = Vec::new();
+        // the groups open at the current point, innermost last
+        let mut open: Vec<(Leaf<'a>, Vec<Node<'a>>)> = Vec::new();
+
+        fn push<'a>(
+            open: &mut [(Leaf<'a>, Vec<Node<'a>>)],
+            line: &mut Vec<Node<'a>>,
+            node: Node<'a>,
+        ) {
+            match open.last_mut() {
+                Some((_, children)) => children.push(node),
+                None => line.push(node),
+            }
+        }
+
+        fn close_all<'a>(open: &mut Vec<(Leaf<'a>, Vec<Node<'a>>)>, line: &mut Vec<Node<'a>>) {
+            while let Some((bracket, children)) = open.pop() {
+                push(
+                    open,
+                    line,
+                    Node::Group {
+                        open: bracket,
+                        children,
+                        close: None,
+                    },
+                );
+            }
+        }
+
+        while let Some(Spanned(token, span)) = lexer.next() {
+            if position < span.0 {
+                let whitespace = Leaf {
+                    kind: LeafKind::Whitespace,
+                    text: &src[position..span.0],
+                    span: Span(position, span.0, span.2),
+                };
+                push(&mut open, &mut line, Node::Leaf(whitespace));
+            }
+            position = span.1;
+
+            let text = &src[span.0..span.1];
+            let kind = match token {
+                Ok(Token::Comment(_)) => LeafKind::Comment,
+                Ok(token) => LeafKind::Token(token),
+                Err(err) => LeafKind::Error(err),
+            };
+            let leaf = Leaf { kind, text, span };
+
+            match token {
+                Ok(Token::LineEnd) if !open.is_empty() && line_continues(lexer) => {
+                    let leaf = Leaf {
+                        kind: LeafKind::LineEnd,
+                        ..leaf
+                    };
+                    push(&mut open, &mut line, Node::Leaf(leaf));
+                }
+                Ok(Token::LineEnd) => {
+                    close_all(&mut open, &mut line);
+                    lines.push(Line {
+                        nodes: std::mem::take(&mut line),
+                        end: Some(leaf),
+                    });
+                }
+                Ok(Token::LPar | Token::LBrace | Token::LBracket) => {
+                    open.push((leaf, Vec::new()));
+                }
+                Ok(Token::RPar | Token::RBrace | Token::RBracket) if !open.is_empty() => {
+                    let (bracket, children) = open.pop().unwrap();
+                    push(
+                        &mut open,
+                        &mut line,
+                        Node::Group {
+                            open: bracket,
+                            children,
+                            close: Some(leaf),
+                        },
+                    );
+                }
+                _ => push(&mut open, &mut line, Node::Leaf(leaf)),
+            }
+        }
+
+        let eof = lexer.eof();
+        if position < eof.0 {
+            let whitespace = Leaf {
+                kind: LeafKind::Whitespace,
+                text: &src[position..],
+                span: Span(position, eof.0, eof.2),
+            };
+            push(&mut open, &mut line, Node::Leaf(whitespace));
+        }
+        close_all(&mut open, &mut line);
+        if !line.is_empty() {
+            lines.push(Line {
+                nodes: line,
+                end: None,
+            });
+        }
+
+        Cst { lines }
+    }
+}
+
+impl<'a> Display for Cst<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            write!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn lossless() {
+    let tests = [
+        "",
+        "\n\n",
+        "type = DFA // the type\nQ = {q0,\n  q1}\n",
+        "d(q0, a) = (q1\nd(q1, a) = q0",
+        "/* unclosed",
+        "Q = {q{0..6}}\t\r\n  ] ) # r\"(a|b)*\"",
+    ];
+    for test in tests {
+        assert_eq!(Cst::parse(test).to_string(), test);
+    }
+
+    let cst = Cst::parse("d(q0, a) = (q1\nd(q1, a) = q0");
+    assert_eq!(cst.lines.len(), 2);
+}
@@ -0,0 +1,402 @@
+//! Formats machine files, working on the [`Cst`] so comments are kept.
+//!
+//! - whitespace between tokens is normalized, adjacent tokens like the parts
+//!   of `q{i}` stay adjacent
+//! - lists mixing comma and space delimited items are made to use commas
+//! - the `=` of consecutive definitions and of consecutive transitions are
+//!   aligned, as are trailing comments
+//! - runs of transitions are sorted by state, in the order states first
+//!   appear in the file
+//! - blank lines are collapsed and tables are left as written
+
+use std::collections::HashMap;
+
+use unicode_display_width::width;
+
+use crate::{
+    delta_lower,
+    loader::{
+        Span,
+        cst::{Cst, Leaf, LeafKind, Line, Node},
+        lexer::Token,
+    },
+};
+
+/// Formats the machine file `src`.
+///
+/// Formatting never fails, text which doesn't lex is kept as written.
+pub fn format(src: &str) -> String {
+    let cst = Cst::parse(src);
+
+    let mut first_use = HashMap::new();
+    for line in &cst.lines {
+        for node in &line.nodes {
+            idents(node, &mut |ident| {
+                let next = first_use.len();
+                first_use.entry(ident).or_insert(next);
+            });
+        }
+    }
+
+    let mut out = String::new();
+    let lines: Vec<_> = cst.lines.iter().map(Formatted::new).collect();
+    for (i, block) in lines
+        .split(|line| line.kind == Kind::Blank)
+        .filter(|block| !block.is_empty())
+        .enumerate()
+    {
+        if i != 0 {
+            out.push('\n');
+        }
+        let mut block = block.to_vec();
+        sort_transitions(&mut block, &first_use);
+        write_block(&block, &mut out);
+    }
+    out
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Kind {
+    Blank,
+    Comment,
+    Definition,
+    Transition,
+    /// Lines formatted token by token without any alignment.
+    Other,
+    /// Lines kept as written, like table rows whose columns are hand aligned.
+    Verbatim,
+}
+
+#[derive(Clone, Debug)]
+struct Formatted {
+    kind: Kind,
+    /// What's left of the `=` of definitions and transitions.
+    lhs: Option<String>,
+    body: String,
+    comment: Option<String>,
+    /// The state of transitions.
+    state: Option<String>,
+}
+
+impl Formatted {
+    fn new(line: &Line) -> Self {
+        let significant: Vec<_> = line.significant().collect();
+        let comments = line
+            .nodes
+            .iter()
+            .filter(|node| {
+                node.leaf()
+                    .is_some_and(|leaf| leaf.kind == LeafKind::Comment)
+            })
+            .count();
+
+        let mut formatted = Formatted {
+            kind: Kind::Other,
+            lhs: None,
+            body: String::new(),
+            comment: None,
+            state: None,
+        };
+
+        if significant.is_empty() {
+            formatted.kind = if comments == 0 {
+                Kind::Blank
+            } else {
+                Kind::Comment
+            };
+            formatted.body = line.to_string().trim().to_string();
+            return formatted;
+        }
+
+        let has = |token: Token| significant.iter().any(|node| node.token() == Some(token));
+        let eq = has(Token::Eq);
+        if has(Token::Or) && !eq && !has(Token::LSmallArrow) && !has(Token::LBigArrow) {
+            formatted.kind = Kind::Verbatim;
+            formatted.body = line.to_string().trim_end().to_string();
+            return formatted;
+        }
+
+        let mut nodes = &line.nodes[..];
+        while let [rest @ .., last] = nodes
+            && last.is_trivia()
+        {
+            if let Some(Leaf {
+                kind: LeafKind::Comment,
+                text,
+                ..
+            }) = last.leaf()
+            {
+                formatted.comment = Some(text.to_string());
+                nodes = rest;
+                break;
+            }
+            nodes = rest;
+        }
+
+        if eq {
+            let split = nodes
+                .iter()
+                .position(|node| node.token() == Some(Token::Eq))
+                .unwrap();
+            formatted.lhs = Some(write_nodes(&nodes[..split]));
+            formatted.body = write_nodes(&nodes[split + 1..]);
+
+            formatted.kind = match &significant[..] {
+                [
+                    Node::Leaf(Leaf {
+                        kind: LeafKind::Token(Token::Ident(delta_lower!(pat))),
+                        ..
+                    }),
+                    Node::Group { open, children, .. },
+                    ..,
+                ] if open.token() == Some(Token::LPar) => {
+                    formatted.state = children
+                        .iter()
+                        .find(|child| !child.is_trivia())
+                        .map(Node::to_string);
+                    Kind::Transition
+                }
+                _ => Kind::Definition,
+            };
+        } else {
+            formatted.body = write_nodes(nodes);
+        }
+        formatted
+    }
+
+    fn content(&self, lhs_width: usize) -> String {
+        match &self.lhs {
+            Some(lhs) => {
+                let padding = lhs_width.saturating_sub(last_line_width(lhs));
+                let content = format!("{lhs}{:padding$} = {}", "", self.body);
+                content.trim_end().to_string()
+            }
+            None => self.body.clone(),
+        }
+    }
+}
+
+/// Sorts each run of transitions in `block` by state, runs are broken by any
+/// other line so comments stay with the transitions they're above.
+fn sort_transitions(block: &mut [Formatted], first_use: &HashMap<&str, usize>) {
+    for run in block.chunk_by_mut(|a, b| a.kind == Kind::Transition && b.kind == Kind::Transition) {
+        run.sort_by_key(|line| {
+            let state = line.state.as_deref().unwrap_or("");
+            (
+                first_use.get(state).copied().unwrap_or(usize::MAX),
+                state.to_string(),
+            )
+        });
+    }
+}
+
+fn write_block(block: &[Formatted], out: &mut String) {
+    let lhs_width = |kind| {
+        block
+            .iter()
+            .filter(|line| line.kind == kind)
+            .filter_map(|line| line.lhs.as_deref())
+            .map(last_line_width)
+            .max()
+            .unwrap_or(0)
+    };
+    let definitions = lhs_width(Kind::Definition);
+    let transitions = lhs_width(Kind::Transition);
+
+    let contents: Vec<_> = block
+        .iter()
+        .map(|line| {
+            line.content(match line.kind {
+                Kind::Definition => definitions,
+                Kind::Transition => transitions,
+                _ => 0,
+            })
+        })
+        .collect();
+
+    let comment_column = block
+        .iter()
+        .zip(&contents)
+        .filter(|(line, _)| line.comment.is_some())
+        .map(|(_, content)| last_line_width(content) + 2)
+        .max()
+        .unwrap_or(0);
+
+    for (line, content) in block.iter().zip(contents) {
+        out.push_str(&content);
+        if let Some(comment) = &line.comment {
+            let padding = comment_column - last_line_width(&content);
+            out.push_str(&format!("{:padding$}{comment}", ""));
+        }
+        out.push('\n');
+    }
+}
+
+fn last_line_width(str: &str) -> usize {
+    width(str.rsplit('\n').next().unwrap_or("")) as usize
+}
+
+/// Calls `f` with every identifier in `node`.
+fn idents<'a>(node: &Node<'a>, f: &mut impl FnMut(&'a str)) {
+    match node {
+        Node::Leaf(leaf) => {
+            if let Some(Token::Ident(ident)) = leaf.token() {
+                f(ident)
+            }
+        }
+        Node::Group { children, .. } => children.iter().for_each(|child| idents(child, f)),
+    }
+}
+
+/// Writes `nodes` with normalized spacing.
+fn write_nodes(nodes: &[Node]) -> String {
+    let mut out = String::new();
+    let mut previous: Option<&Node> = None;
+    let mut gap: Option<&str> = None;
+
+    for node in nodes {
+        if let Some(Leaf {
+            kind: LeafKind::Whitespace,
+            text,
+            ..
+        }) = node.leaf()
+        {
+            gap = Some(text);
+            continue;
+        }
+
+        let is = |node: Option<&Node>, tokens: &[Token]| {
+            node.and_then(Node::token)
+                .is_some_and(|token| tokens.contains(&token))
+        };
+        let line_end = |node: Option<&Node>| {
+            node.and_then(Node::leaf)
+                .is_some_and(|leaf| leaf.kind == LeafKind::LineEnd)
+        };
+        const SPACED: &[Token] = &[Token::Eq, Token::LSmallArrow, Token::LBigArrow, Token::Or];
+
+        let separator =
+            if previous.is_none() || line_end(Some(node)) || is(Some(node), &[Token::Comma]) {
+                ""
+            } else if line_end(previous) {
+                gap.unwrap_or("")
+            } else if gap.is_some()
+                || is(previous, &[Token::Comma])
+                || is(previous, SPACED)
+                || is(Some(node), SPACED)
+            {
+                " "
+            } else {
+                ""
+            };
+        out.push_str(separator);
+
+        match node {
+            // the expression of a template like q{i} is kept as written
+            Node::Group { open, .. }
+                if open.token() == Some(Token::LBrace) && previous.is_some() && gap.is_none() =>
+            {
+                out.push_str(&node.to_string())
+            }
+            Node::Group {
+                open,
+                children,
+                close,
+            } => {
+                out.push_str(open.text);
+                let children = if open.token() == Some(Token::LPar) {
+                    children.clone()
+                } else {
+                    with_commas(children)
+                };
+                out.push_str(&write_nodes(&children));
+                if let Some(close) = close {
+                    out.push_str(close.text);
+                }
+            }
+            Node::Leaf(leaf) => out.push_str(leaf.text),
+        }
+
+        previous = Some(node);
+        gap = None;
+    }
+    out
+}
+
+/// Delimits the items of a list with commas if any of them are.
+fn with_commas<'a>(children: &[Node<'a>]) -> Vec<Node<'a>> {
+    if !children
+        .iter()
+        .any(|child| child.token() == Some(Token::Comma))
+    {
+        return children.to_vec();
+    }
+
+    let mut out: Vec<Node> = Vec::new();
+    // where the item before the current point ends, if it isn't delimited yet
+    let mut item_end: Option<usize> = None;
+    let mut gap = false;
+    for child in children {
+        if child.is_trivia() {
+            gap = true;
+            out.push(child.clone());
+            continue;
+        }
+        match child.token() {
+            Some(Token::Comma) => item_end = None,
+            // a range like a..z is one item
+            Some(Token::DotDot) => item_end = None,
+            _ => {
+                if let Some(end) = item_end
+                    && gap
+                {
+                    let at = out[end].span().1;
+                    let comma = Leaf {
+                        kind: LeafKind::Token(Token::Comma),
+                        text: ",",
+                        span: Span(at, at, out[end].span().2),
+                    };
+                    out.insert(end + 1, Node::Leaf(comma));
+                }
+                item_end = Some(out.len());
+            }
+        }
+        gap = false;
+        out.push(child.clone());
+    }
+    out
+}
+
+#[test]
+fn formatting() {
+    let src = "// a machine
+type=DFA
+Q = {q0, q1 q2}  // states
+accept =F // final
+E={a,b}
+
+d(q1, a) = q2
+d(q0,a) = q1 // first
+d(q{i}, {a..b}) = q{(i + 1) % 3}
+
+
+q  | a  b
+q0 | q1 -
+";
+    let expected = "// a machine
+type   = DFA
+Q      = {q0, q1, q2}  // states
+accept = F             // final
+E      = {a, b}
+
+d(q0, a)        = q1  // first
+d(q1, a)        = q2
+d(q{i}, {a..b}) = q{(i + 1) % 3}
+
+q  | a  b
+q0 | q1 -
+";
+    let formatted = format(src);
+    assert_eq!(formatted, expected);
+    assert_eq!(format(&formatted), formatted);
+}
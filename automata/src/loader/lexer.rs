@@ -1,4 +1,4 @@
-use crate::loader::{Span, Spanned};
+use crate::loader::{FileId, Span, Spanned};
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug, Default)]
 pub enum StringKind {
@@ -27,6 +27,9 @@ pub enum Token<'a> {
     Plus,
     Star,
     And,
+    Slash,
+    Percent,
+    DotDot,
 
     LSmallArrow,
     LBigArrow,
@@ -56,6 +59,9 @@ impl<'a> std::fmt::Display for Token<'a> {
             Token::Dash => write!(f, "'-'"),
             Token::Star => write!(f, "'*'"),
             Token::And => write!(f, "'&'"),
+            Token::Slash => write!(f, "'/'"),
+            Token::Percent => write!(f, "'%'"),
+            Token::DotDot => write!(f, "'..'"),
             Token::LSmallArrow => write!(f, "'->'"),
             Token::LBigArrow => write!(f, "'=>'"),
             Token::Comment(_) => write!(f, "<comment>"),
@@ -79,6 +85,7 @@ impl<'a> std::fmt::Display for Token<'a> {
 pub struct Lexer<'a> {
     input: &'a str,
     position: usize,
+    file: FileId,
 }
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
@@ -90,7 +97,23 @@ pub enum Error {
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
-        Self { input, position: 0 }
+        Self::with_file(input, FileId::default())
+    }
+
+    pub fn with_file(input: &'a str, file: FileId) -> Self {
+        Self {
+            input,
+            position: 0,
+            file,
+        }
+    }
+
+    pub fn file(&self) -> FileId {
+        self.file
+    }
+
+    pub fn eof(&self) -> Span {
+        Span(self.input.len(), self.input.len(), self.file)
     }
 
     fn consume(&mut self) -> Option<char> {
@@ -103,6 +126,27 @@ impl<'a> Lexer<'a> {
         self.input.get(self.position..)?.chars().next()
     }
 
+    fn string(&mut self, content: usize, kind: StringKind) -> Result<Token<'a>, Error> {
+        let mut escaped = false;
+        loop {
+            match self.consume() {
+                Some('"') => {
+                    break Ok(Token::String(
+                        &self.input[content..self.position - 1],
+                        kind,
+                        escaped,
+                    ));
+                }
+                None => break Err(Error::UnclosedString),
+                Some('\\') => {
+                    _ = self.consume();
+                    escaped = true;
+                }
+                _ => {}
+            }
+        }
+    }
+
     fn backtrack(&mut self) {
         if let Some(consumed) = self.input.get(..self.position)
             && let Some(previous) = consumed.chars().next_back()
@@ -133,7 +177,10 @@ impl<'a> std::iter::Iterator for Lexer<'a> {
             if c == '\n' {
                 let start = self.position;
                 self.consume();
-                let res = Some(Spanned(Ok(Token::LineEnd), Span(start, self.position)));
+                let res = Some(Spanned(
+                    Ok(Token::LineEnd),
+                    Span(start, self.position, self.file),
+                ));
                 return res;
             } else {
                 self.consume();
@@ -154,6 +201,14 @@ impl<'a> std::iter::Iterator for Lexer<'a> {
             '&' => Ok(Token::And),
             ',' => Ok(Token::Comma),
             '|' => Ok(Token::Or),
+            '%' => Ok(Token::Percent),
+            '.' => match self.peek() {
+                Some('.') => {
+                    self.consume();
+                    Ok(Token::DotDot)
+                }
+                _ => Err(Error::InvalidChar('.')),
+            },
             '=' => match self.peek() {
                 Some('>') => {
                     self.consume();
@@ -168,26 +223,7 @@ impl<'a> std::iter::Iterator for Lexer<'a> {
                 }
                 _ => Ok(Token::Dash),
             },
-            '"' => {
-                let mut escaped = false;
-                loop {
-                    match self.consume() {
-                        Some('"') => {
-                            break Ok(Token::String(
-                                &self.input[start + 1..self.position],
-                                StringKind::Regular,
-                                escaped,
-                            ));
-                        }
-                        None => break Err(Error::UnclosedString),
-                        Some('\\') => {
-                            _ = self.consume();
-                            escaped = true;
-                        }
-                        _ => {}
-                    }
-                }
-            }
+            '"' => self.string(start + 1, StringKind::Regular),
 
             '/' => match self.consume() {
                 Some('/') => loop {
@@ -197,7 +233,7 @@ impl<'a> std::iter::Iterator for Lexer<'a> {
                             break Ok(Token::Comment(&self.input[start + 2..=self.position]));
                         }
                         None => {
-                            break Ok(Token::Comment(&self.input[start + 2..=self.position]));
+                            break Ok(Token::Comment(&self.input[start + 2..self.position]));
                         }
                         _ => {}
                     }
@@ -214,11 +250,16 @@ impl<'a> std::iter::Iterator for Lexer<'a> {
                 },
                 Some(_) => {
                     self.backtrack();
-                    Err(Error::InvalidChar('/'))
+                    Ok(Token::Slash)
                 }
-                None => Err(Error::InvalidChar('/')),
+                None => Ok(Token::Slash),
             },
 
+            'r' if self.peek() == Some('"') => {
+                self.consume();
+                self.string(start + 2, StringKind::Regex)
+            }
+
             c if begin_ident(c) => loop {
                 match self.consume() {
                     Some(c) if continue_ident(c) => {}
@@ -232,7 +273,7 @@ impl<'a> std::iter::Iterator for Lexer<'a> {
 
             c => Err(Error::InvalidChar(c)),
         };
-        let span = Span(start, self.position);
+        let span = Span(start, self.position, self.file);
         Some(Spanned(res, span))
     }
 }
@@ -248,6 +289,8 @@ fn tokenizer() {
         "/",
         "//",
         "()[]{}~=>==>->-+*&|, hello _th012is__ a wondweful",
+        "q{(i+1)%7} q{0..6} / . ..",
+        r#""string" r"(a|b)*" r rr"#,
     ];
 
     for test in tests {
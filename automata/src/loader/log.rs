@@ -3,7 +3,7 @@ use std::fmt::Display;
 use unicode_display_width::width;
 use unicode_segmentation::UnicodeSegmentation;
 
-use crate::loader::Span;
+use crate::loader::{FileId, Span, codes::Code, source::SourceFile, suggest};
 
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Logs {
@@ -15,75 +15,35 @@ pub trait LogSink {
     fn emit(&mut self, entry: LogEntry) -> &mut LogEntry;
 
     fn emit_error_locless(&mut self, msg: impl Into<String>) -> &mut LogEntry {
-        self.emit(LogEntry {
-            message: msg.into(),
-            span: None,
-            level: LogLevel::Error,
-            child: None,
-        })
+        self.emit(LogEntry::new(msg, None, LogLevel::Error))
     }
 
     fn emit_error(&mut self, msg: impl Into<String>, span: Span) -> &mut LogEntry {
-        self.emit(LogEntry {
-            message: msg.into(),
-            span: Some(span),
-            level: LogLevel::Error,
-            child: None,
-        })
+        self.emit(LogEntry::new(msg, Some(span), LogLevel::Error))
     }
 
     fn emit_warning(&mut self, msg: impl Into<String>, span: Span) -> &mut LogEntry {
-        self.emit(LogEntry {
-            message: msg.into(),
-            span: Some(span),
-            level: LogLevel::Warning,
-            child: None,
-        })
+        self.emit(LogEntry::new(msg, Some(span), LogLevel::Warning))
     }
 
     fn emit_warning_locless(&mut self, msg: impl Into<String>) -> &mut LogEntry {
-        self.emit(LogEntry {
-            message: msg.into(),
-            span: None,
-            level: LogLevel::Warning,
-            child: None,
-        })
+        self.emit(LogEntry::new(msg, None, LogLevel::Warning))
     }
 
     fn emit_info(&mut self, msg: impl Into<String>, span: Span) -> &mut LogEntry {
-        self.emit(LogEntry {
-            message: msg.into(),
-            span: Some(span),
-            level: LogLevel::Info,
-            child: None,
-        })
+        self.emit(LogEntry::new(msg, Some(span), LogLevel::Info))
     }
 
     fn emit_info_logless(&mut self, msg: impl Into<String>) -> &mut LogEntry {
-        self.emit(LogEntry {
-            message: msg.into(),
-            span: None,
-            level: LogLevel::Info,
-            child: None,
-        })
+        self.emit(LogEntry::new(msg, None, LogLevel::Info))
     }
 
     fn emit_help(&mut self, msg: impl Into<String>, span: Span) -> &mut LogEntry {
-        self.emit(LogEntry {
-            message: msg.into(),
-            span: Some(span),
-            level: LogLevel::Help,
-            child: None,
-        })
+        self.emit(LogEntry::new(msg, Some(span), LogLevel::Help))
     }
 
     fn emit_help_logless(&mut self, msg: impl Into<String>) -> &mut LogEntry {
-        self.emit(LogEntry {
-            message: msg.into(),
-            span: None,
-            level: LogLevel::Help,
-            child: None,
-        })
+        self.emit(LogEntry::new(msg, None, LogLevel::Help))
     }
 }
 
@@ -109,9 +69,47 @@ impl Logs {
 
     pub fn displayable_with<'a>(
         &'a self,
-        src: &'a str,
+        files: &'a [SourceFile<'a>],
     ) -> impl Iterator<Item = LogEntryDisplay<'a>> {
-        self.logs.iter().map(|entry| LogEntryDisplay { src, entry })
+        self.logs.iter().map(|entry| LogEntryDisplay {
+            files,
+            entry,
+            options: RenderOptions::new(),
+        })
+    }
+
+    /// Changes the level of every entry to the one `level` returns for it,
+    /// removing the entries it returns `None` for.
+    pub fn relevel(&mut self, mut level: impl FnMut(&mut LogEntry) -> Option<LogLevel>) {
+        self.logs.retain_mut(|entry| match level(entry) {
+            Some(level) => {
+                entry.level = level;
+                true
+            }
+            None => false,
+        });
+        self.has_error = self
+            .logs
+            .iter()
+            .any(|entry| matches!(entry.level, LogLevel::Error));
+    }
+
+    /// Removes the entries from index `start` on which repeat an earlier one
+    /// of them with the same span, level and message.
+    pub fn dedup_from(&mut self, start: usize) {
+        let mut index = start;
+        while index < self.logs.len() {
+            let entry = &self.logs[index];
+            if self.logs[start..index].iter().any(|kept| {
+                kept.span == entry.span
+                    && kept.level == entry.level
+                    && kept.message == entry.message
+            }) {
+                self.logs.remove(index);
+            } else {
+                index += 1;
+            }
+        }
     }
 
     pub fn entries(&self) -> &[LogEntry] {
@@ -129,6 +127,7 @@ impl Default for Logs {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum LogLevel {
     Info,
@@ -137,118 +136,1038 @@ pub enum LogLevel {
     Help,
 }
 
+impl LogLevel {
+    /// The name of the level as shown in logs, like `error`.
+    pub fn name(self) -> &'static str {
+        match self {
+            LogLevel::Help => "help",
+            LogLevel::Info => "info",
+            LogLevel::Warning => "warning",
+            LogLevel::Error => "error",
+        }
+    }
+}
+
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct LogEntry {
     pub message: String,
+    /// The primary span, the place the entry is about.
     pub span: Option<Span>,
+    /// What to write next to the primary span.
+    pub label: Option<String>,
     pub level: LogLevel,
-    pub child: Option<Box<LogEntry>>,
+    /// Other places related to the entry.
+    pub labels: Vec<Label>,
+    /// Notes and helps shown after the source.
+    pub notes: Vec<Note>,
+    /// The code explaining the entry.
+    pub code: Option<Code>,
+    /// Edits to the source which address the entry.
+    pub suggestions: Vec<Suggestion>,
 }
 
-impl LogSink for LogEntry {
-    fn emit(&mut self, entry: LogEntry) -> &mut LogEntry {
-        self.child = Some(Box::new(entry));
-        self.child.as_mut().unwrap()
+/// A secondary span of a [`LogEntry`] with a message about it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// A message without a span attached to a [`LogEntry`], its level is either
+/// [`LogLevel::Info`] or [`LogLevel::Help`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Note {
+    pub level: LogLevel,
+    pub message: String,
+}
+
+impl LogEntry {
+    pub fn new(message: impl Into<String>, span: Option<Span>, level: LogLevel) -> Self {
+        Self {
+            message: message.into(),
+            span,
+            label: None,
+            level,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            code: None,
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn code(&mut self, code: Code) -> &mut Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Sets the message written next to the primary span.
+    pub fn label(&mut self, message: impl Into<String>) -> &mut Self {
+        self.label = Some(message.into());
+        self
+    }
+
+    pub fn secondary(&mut self, span: Span, message: impl Into<String>) -> &mut Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn note(&mut self, message: impl Into<String>) -> &mut Self {
+        self.notes.push(Note {
+            level: LogLevel::Info,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn help(&mut self, message: impl Into<String>) -> &mut Self {
+        self.notes.push(Note {
+            level: LogLevel::Help,
+            message: message.into(),
+        });
+        self
+    }
+
+    /// Adds a help suggesting the candidate closest to the misspelled `name`
+    /// at `span`, if there is one.
+    pub fn did_you_mean<'b>(
+        &mut self,
+        span: Span,
+        name: &str,
+        candidates: impl IntoIterator<Item = &'b str>,
+    ) -> &mut Self {
+        if let Some(candidate) = suggest::closest(name, candidates) {
+            self.help(format!("did you mean {candidate:?}?")).suggest(
+                span,
+                candidate,
+                Applicability::MaybeIncorrect,
+            );
+        }
+        self
+    }
+
+    pub fn suggest(
+        &mut self,
+        span: Span,
+        replacement: impl Into<String>,
+        applicability: Applicability,
+    ) -> &mut Self {
+        self.suggestions.push(Suggestion {
+            span,
+            replacement: replacement.into(),
+            applicability,
+        });
+        self
+    }
+}
+
+/// How sure a [`Suggestion`] is to be what the user wants.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Applicability {
+    /// The edit is definitely right and can be applied without asking.
+    MachineApplicable,
+    /// The edit fixes the entry but may not be what was meant.
+    MaybeIncorrect,
+    /// The edit contains placeholders like `...` which need filling in.
+    HasPlaceholders,
+}
+
+/// An edit replacing the text at `span` with `replacement`, insertions have an
+/// empty span.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Suggestion {
+    pub span: Span,
+    pub replacement: String,
+    pub applicability: Applicability,
+}
+
+/// Applies the machine applicable suggestions of `entries` which edit `file`
+/// to its source `src`.
+///
+/// Suggestions overlapping an earlier one are skipped, as are repeated
+/// insertions of the same text at the same place.
+pub fn apply_suggestions<'a>(
+    src: &str,
+    file: FileId,
+    entries: impl IntoIterator<Item = &'a LogEntry>,
+) -> String {
+    let mut suggestions: Vec<_> = entries
+        .into_iter()
+        .flat_map(|entry| &entry.suggestions)
+        .filter(|suggestion| {
+            suggestion.applicability == Applicability::MachineApplicable
+                && suggestion.span.2 == file
+        })
+        .collect();
+    suggestions.sort_by_key(|suggestion| (suggestion.span.0, suggestion.span.1));
+
+    let mut out = String::new();
+    let mut position = 0;
+    let mut previous: Option<&Suggestion> = None;
+    for suggestion in suggestions {
+        let Span(start, end, _) = suggestion.span;
+        if start < position || previous == Some(suggestion) || src.get(start..end).is_none() {
+            continue;
+        }
+        out.push_str(&src[position..start]);
+        out.push_str(&suggestion.replacement);
+        position = end;
+        previous = Some(suggestion);
     }
+    out.push_str(&src[position..]);
+    out
+}
+
+/// How [`LogEntryDisplay`] marks up what it renders.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Markup {
+    /// Plain text, colored with ANSI escape codes.
+    Ansi,
+    /// HTML to embed in a page, a `<pre class="log">` styled through the
+    /// classes of [`HTML_STYLE`].
+    Html,
+}
+
+/// Options for rendering log entries for people.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RenderOptions {
+    pub markup: Markup,
+    pub color: bool,
+    /// The columns between tab stops, tabs in the source are expanded to them.
+    pub tab_width: usize,
+    /// How many lines around the annotated ones are shown.
+    pub context_lines: usize,
+    /// Underline with box drawing characters rather than `~` and `-`.
+    pub unicode: bool,
+}
+
+impl RenderOptions {
+    pub fn new() -> Self {
+        Self {
+            markup: Markup::Ansi,
+            color: true,
+            tab_width: 4,
+            context_lines: 0,
+            unicode: false,
+        }
+    }
+
+    pub fn html(mut self) -> Self {
+        self.markup = Markup::Html;
+        self
+    }
+
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width.max(1);
+        self
+    }
+
+    pub fn context_lines(mut self, context_lines: usize) -> Self {
+        self.context_lines = context_lines;
+        self
+    }
+
+    pub fn unicode(mut self, unicode: bool) -> Self {
+        self.unicode = unicode;
+        self
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Styles for the classes of HTML rendered log entries, matching the colors
+/// of the ANSI ones.
+pub const HTML_STYLE: &str = ".log .bold { font-weight: bold; }
+.log .error { color: #cd3131; font-weight: bold; }
+.log .warning { color: #b58900; font-weight: bold; }
+.log .info, .log .secondary, .log .gutter { color: #0598bc; font-weight: bold; }
+.log .help { color: #0dbc79; font-weight: bold; }
+";
+
+/// What a piece of rendered text is, decides its color or class.
+#[derive(Clone, Copy)]
+enum Style {
+    Level(LogLevel),
+    Secondary,
+    Gutter,
+    Bold,
 }
 
 pub struct LogEntryDisplay<'a> {
-    src: &'a str,
+    files: &'a [SourceFile<'a>],
     entry: &'a LogEntry,
+    options: RenderOptions,
+}
+
+/// A span to underline in a snippet and what to write next to it.
+struct Annotation<'a> {
+    span: Span,
+    label: Option<&'a str>,
+    primary: bool,
+}
+
+impl<'a> LogEntryDisplay<'a> {
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Writes source text, escaped if need be.
+    fn text(&self, f: &mut std::fmt::Formatter<'_>, text: &str) -> std::fmt::Result {
+        if self.options.markup == Markup::Ansi {
+            return write!(f, "{text}");
+        }
+        for char in text.chars() {
+            match char {
+                '<' => write!(f, "&lt;")?,
+                '>' => write!(f, "&gt;")?,
+                '&' => write!(f, "&amp;")?,
+                '"' => write!(f, "&quot;")?,
+                char => write!(f, "{char}")?,
+            }
+        }
+        Ok(())
+    }
+
+    fn styled(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        style: Style,
+        text: &str,
+    ) -> std::fmt::Result {
+        const RESET: &str = "\x1b[0;22m";
+        const BOLD: &str = "\x1b[1m";
+        const RED: &str = "\x1b[31m";
+        const GREEN: &str = "\x1b[32m";
+        const YELLOW: &str = "\x1b[33m";
+        const CYAN: &str = "\x1b[36m";
+
+        if !self.options.color {
+            return self.text(f, text);
+        }
+        match self.options.markup {
+            Markup::Ansi => {
+                let color = match style {
+                    Style::Level(LogLevel::Help) => GREEN,
+                    Style::Level(LogLevel::Info) | Style::Secondary | Style::Gutter => CYAN,
+                    Style::Level(LogLevel::Warning) => YELLOW,
+                    Style::Level(LogLevel::Error) => RED,
+                    Style::Bold => "",
+                };
+                write!(f, "{BOLD}{color}{text}{RESET}")
+            }
+            Markup::Html => {
+                let class = match style {
+                    Style::Level(level) => level.name(),
+                    Style::Secondary => "secondary",
+                    Style::Gutter => "gutter",
+                    Style::Bold => "bold",
+                };
+                write!(f, "<span class=\"{class}\">")?;
+                self.text(f, text)?;
+                write!(f, "</span>")
+            }
+        }
+    }
 }
 
 impl<'a> Display for LogEntryDisplay<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        pub const RESET: &str = "\x1b[0;22m";
-        pub const BOLD: &str = "\x1b[1m";
-        // pub const UNDERLINE: &str = "\x1b[4m";
-        pub const RED: &str = "\x1b[31m";
-        pub const GREEN: &str = "\x1b[32m";
-        pub const YELLOW: &str = "\x1b[33m";
-        // pub const BLUE: &str = "\x1b[34m";
-        pub const CYAN: &str = "\x1b[36m";
-
-        let mut next_entry = Some(self.entry);
-
-        while let Some(entry) = next_entry {
-            match entry.level {
-                LogLevel::Help => write!(f, "{BOLD}{GREEN}help{RESET}{BOLD}: ")?,
-                LogLevel::Info => write!(f, "{BOLD}{CYAN}info{RESET}{BOLD}: ")?,
-                LogLevel::Warning => write!(f, "{BOLD}{YELLOW}warning{RESET}{BOLD}: ")?,
-                LogLevel::Error => write!(f, "{BOLD}{RED}error{RESET}{BOLD}: ")?,
-            }
-            writeln!(f, "{}{RESET}", entry.message)?;
-
-            if let Some(span) = entry.span {
-                let line_start = self.src.get(..=span.0).unwrap_or("").lines().count();
-                let line_end = self.src.get(..span.1).unwrap_or("").lines().count();
-
-                let padding = if line_end == 0 {
-                    1
-                } else {
-                    line_end.ilog10() as usize
-                };
+        let entry = self.entry;
+        let html = self.options.markup == Markup::Html;
+        let tab_width = self.options.tab_width.max(1);
+        if html {
+            write!(f, "<pre class=\"log\">")?;
+        }
 
-                let start = self
-                    .src
-                    .get(..span.0)
-                    .and_then(|s| s.rfind('\n'))
-                    .map(|v| v + 1)
-                    .unwrap_or(0);
-
-                let end = if self.src.get(..span.1).unwrap_or("").ends_with("\n") {
-                    span.1
-                } else {
-                    self.src
-                        .get(span.1..)
-                        .and_then(|s| s.find('\n'))
-                        .map(|v| v + span.1)
-                        .unwrap_or(self.src.len())
-                };
+        self.styled(f, Style::Level(entry.level), entry.level.name())?;
+        let header = match entry.code {
+            Some(code) => format!("[{code}]: {}", entry.message),
+            None => format!(": {}", entry.message),
+        };
+        self.styled(f, Style::Bold, &header)?;
+        writeln!(f)?;
+
+        let annotations: Vec<_> = entry
+            .span
+            .map(|span| Annotation {
+                span,
+                label: entry.label.as_deref(),
+                primary: true,
+            })
+            .into_iter()
+            .chain(entry.labels.iter().map(|label| Annotation {
+                span: label.span,
+                label: Some(&label.message),
+                primary: false,
+            }))
+            .collect();
+
+        // the file of the primary span goes first, then the others in the
+        // order they were labeled in
+        let mut files = Vec::new();
+        for annotation in &annotations {
+            if !files.contains(&annotation.span.2) {
+                files.push(annotation.span.2);
+            }
+        }
+
+        for file in files {
+            let src = self.files[file.0 as usize].src;
+            let annotations: Vec<_> = annotations
+                .iter()
+                .filter(|annotation| annotation.span.2 == file)
+                .collect();
+
+            let line_starts: Vec<_> = std::iter::once(0)
+                .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+                .collect();
+            let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset) - 1;
+            let line_range = |span: Span| {
+                let start = line_of(span.0);
+                (start, line_of(span.1.saturating_sub(1)).max(start))
+            };
+            let line_bounds = |line: usize| {
+                let start = line_starts[line];
+                let end = line_starts
+                    .get(line + 1)
+                    .map(|next| next - 1)
+                    .unwrap_or(src.len());
+                (start, end)
+            };
 
-                let mut index = start;
-                for (i, line) in self
-                    .src
-                    .get(start..end)
+            let context = self.options.context_lines;
+            let mut lines = std::collections::BTreeSet::new();
+            for annotation in &annotations {
+                let (start, end) = line_range(annotation.span);
+                let end = (end + context).min(line_starts.len() - 1);
+                lines.extend(start.saturating_sub(context)..=end);
+            }
+
+            // spans from several files need to say which one they are in
+            if self.files.len() > 1 {
+                let span = annotations[0].span;
+                let line = line_of(span.0);
+                let name = self.files[file.0 as usize].name;
+                let column = src
+                    .get(line_starts[line]..span.0)
                     .unwrap_or("")
-                    .split_inclusive("\n")
-                    .enumerate()
-                {
-                    write!(f, "{BOLD}{CYAN}{:>padding$}: {RESET}", i + line_start)?;
-                    for char in line.chars() {
-                        if char == '\t' {
-                            write!(f, " ")?
+                    .chars()
+                    .count()
+                    + 1;
+                self.styled(f, Style::Gutter, "--> ")?;
+                self.text(f, &format!("{name}:{}:{column}", line + 1))?;
+                writeln!(f)?;
+            }
+
+            let last_line = lines.last().copied().unwrap_or(0) + 1;
+            let padding = last_line.ilog10() as usize + 1;
+
+            // a single skipped line takes as much room as the dots would
+            let mut shown = Vec::new();
+            for line in lines {
+                match shown.last() {
+                    Some(&Some(previous)) if line == previous + 2 => shown.push(Some(previous + 1)),
+                    Some(&Some(previous)) if line > previous + 2 => shown.push(None),
+                    _ => {}
+                }
+                shown.push(Some(line));
+            }
+
+            for line in shown {
+                let Some(line) = line else {
+                    self.styled(f, Style::Gutter, "...")?;
+                    writeln!(f)?;
+                    continue;
+                };
+                let (start, end) = line_bounds(line);
+                let text = &src[start..end];
+
+                // the columns each grapheme takes, tabs up to the next stop
+                let mut column = 0;
+                let graphemes: Vec<_> = text
+                    .grapheme_indices(true)
+                    .map(|(index, grapheme)| {
+                        let size = if grapheme == "\t" {
+                            tab_width - column % tab_width
                         } else {
-                            write!(f, "{char}")?
-                        }
-                    }
-                    if !line.ends_with("\n") {
-                        writeln!(f)?;
+                            width(grapheme) as usize
+                        };
+                        column += size;
+                        (start + index, grapheme, size)
+                    })
+                    .collect();
+
+                self.styled(f, Style::Gutter, &format!("{:>padding$}: ", line + 1))?;
+                for &(_, grapheme, size) in &graphemes {
+                    if grapheme == "\t" {
+                        write!(f, "{:size$}", "")?;
+                    } else {
+                        self.text(f, grapheme)?;
                     }
-                    write!(f, "{BOLD}{CYAN}")?;
-                    for _ in 0..padding + 3 {
-                        write!(f, " ")?;
+                }
+                writeln!(f)?;
+
+                for annotation in &annotations {
+                    let (first, last) = line_range(annotation.span);
+                    if !(first..=last).contains(&line) {
+                        continue;
                     }
-                    for grapheme in line.graphemes(true) {
-                        if (span.0..span.1).contains(&index) {
-                            for _ in 0..width(grapheme){
-                                write!(f, "~")?;
-                            }
-                        } else {
-                            for _ in 0..width(grapheme){
-                                write!(f, " ")?;
-                            }
+                    let (marker, style) = match (annotation.primary, self.options.unicode) {
+                        (true, false) => ('~', Style::Level(entry.level)),
+                        (true, true) => ('━', Style::Level(entry.level)),
+                        (false, false) => ('-', Style::Secondary),
+                        (false, true) => ('─', Style::Secondary),
+                    };
+
+                    let mut column = 0;
+                    let mut markers = 0;
+                    for &(index, _, size) in &graphemes {
+                        if (annotation.span.0..annotation.span.1).contains(&index) {
+                            markers += size;
+                        } else if index < annotation.span.0 {
+                            column += size;
                         }
-                        index += grapheme.len();
                     }
-                    write!(f, "{RESET}")?;
-                    index += '\n'.len_utf8();
+
+                    let mut underline = " ".repeat(padding + 2 + column);
+                    underline.extend(std::iter::repeat_n(marker, markers.max(1)));
+                    if let Some(label) = annotation.label.filter(|_| line == last) {
+                        underline.push(' ');
+                        underline.push_str(label);
+                    }
+                    self.styled(f, style, &underline)?;
                     writeln!(f)?;
                 }
             }
-            next_entry = entry.child.as_deref()
         }
 
+        for note in &entry.notes {
+            self.styled(f, Style::Level(note.level), note.level.name())?;
+            self.styled(f, Style::Bold, &format!(": {}", note.message))?;
+            writeln!(f)?;
+        }
+
+        if html {
+            write!(f, "</pre>")?;
+        }
         Ok(())
     }
 }
+
+/// A place in a source, lines and columns start at 1.
+///
+/// Editors disagree on how to count columns so both UTF-8 bytes and UTF-16
+/// code units are given, the latter are what browsers and LSP clients use.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Position {
+    pub offset: usize,
+    pub utf16_offset: usize,
+    pub line: usize,
+    pub column: usize,
+    pub utf16_column: usize,
+}
+
+impl Position {
+    pub fn new(src: &str, offset: usize) -> Self {
+        let mut offset = offset.min(src.len());
+        while !src.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let utf16 = |str: &str| str.chars().map(char::len_utf16).sum::<usize>();
+        let before = &src[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Self {
+            offset,
+            utf16_offset: utf16(before),
+            line: before.matches('\n').count() + 1,
+            column: offset - line_start + 1,
+            utf16_column: utf16(&before[line_start..]) + 1,
+        }
+    }
+}
+
+impl Applicability {
+    /// The name of the applicability in machine readable logs, like
+    /// `machine_applicable`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Applicability::MachineApplicable => "machine_applicable",
+            Applicability::MaybeIncorrect => "maybe_incorrect",
+            Applicability::HasPlaceholders => "has_placeholders",
+        }
+    }
+}
+
+/// Just enough of JSON to write the machine readable logs.
+enum Json {
+    Null,
+    Number(usize),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::Number(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl<const N: usize> From<[(&'static str, Json); N]> for Json {
+    fn from(value: [(&'static str, Json); N]) -> Self {
+        Json::Object(value.into())
+    }
+}
+
+impl FromIterator<Json> for Json {
+    fn from_iter<I: IntoIterator<Item = Json>>(iter: I) -> Self {
+        Json::Array(iter.into_iter().collect())
+    }
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Number(number) => write!(f, "{number}"),
+            Json::String(string) => {
+                write!(f, "\"")?;
+                for char in string.chars() {
+                    match char {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\r' => write!(f, "\\r")?,
+                        '\t' => write!(f, "\\t")?,
+                        char if char.is_control() => write!(f, "\\u{:04x}", char as u32)?,
+                        char => write!(f, "{char}")?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{value}", Json::from(*key))?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl Logs {
+    /// The entries as JSON lines, one object per entry.
+    ///
+    /// Spans are objects with the `file` name and the `start` and `end`
+    /// [`Position`], `null` for entries without one.
+    pub fn to_json_lines(&self, files: &[SourceFile]) -> String {
+        let location = |span: Span| {
+            let file = &files[span.2.0 as usize];
+            let position = |offset| {
+                let position = Position::new(file.src, offset);
+                Json::from([
+                    ("offset", position.offset.into()),
+                    ("line", position.line.into()),
+                    ("column", position.column.into()),
+                    ("utf16_offset", position.utf16_offset.into()),
+                    ("utf16_column", position.utf16_column.into()),
+                ])
+            };
+            Json::from([
+                ("file", file.name.into()),
+                ("start", position(span.0)),
+                ("end", position(span.1)),
+            ])
+        };
+
+        let mut out = String::new();
+        for entry in &self.logs {
+            let json = Json::from([
+                ("level", entry.level.name().into()),
+                ("code", entry.code.map(|code| code.to_string()).into()),
+                ("message", entry.message.as_str().into()),
+                ("span", entry.span.map(location).into()),
+                ("label", entry.label.as_deref().into()),
+                (
+                    "labels",
+                    entry
+                        .labels
+                        .iter()
+                        .map(|label| {
+                            Json::from([
+                                ("span", location(label.span)),
+                                ("message", label.message.as_str().into()),
+                            ])
+                        })
+                        .collect(),
+                ),
+                (
+                    "notes",
+                    entry
+                        .notes
+                        .iter()
+                        .map(|note| {
+                            Json::from([
+                                ("level", note.level.name().into()),
+                                ("message", note.message.as_str().into()),
+                            ])
+                        })
+                        .collect(),
+                ),
+                (
+                    "suggestions",
+                    entry
+                        .suggestions
+                        .iter()
+                        .map(|suggestion| {
+                            Json::from([
+                                ("span", location(suggestion.span)),
+                                ("replacement", suggestion.replacement.as_str().into()),
+                                ("applicability", suggestion.applicability.name().into()),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ]);
+            out.push_str(&json.to_string());
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// A SARIF 2.1.0 log for code scanning tools, collecting the entries of any
+/// number of checked files into one run.
+///
+/// Columns count UTF-16 code units, which is the SARIF default.
+#[derive(Default)]
+pub struct Sarif {
+    rules: Vec<Code>,
+    results: Vec<Json>,
+}
+
+impl Sarif {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, logs: &Logs, files: &[SourceFile]) -> &mut Self {
+        let artifact = |span: Span| Json::from([("uri", files[span.2.0 as usize].name.into())]);
+        let region = |span: Span| {
+            let src = files[span.2.0 as usize].src;
+            let (start, end) = (Position::new(src, span.0), Position::new(src, span.1));
+            Json::from([
+                ("startLine", start.line.into()),
+                ("startColumn", start.utf16_column.into()),
+                ("endLine", end.line.into()),
+                ("endColumn", end.utf16_column.into()),
+                ("byteOffset", start.offset.into()),
+                ("byteLength", (end.offset - start.offset).into()),
+            ])
+        };
+        let location = |span: Span| {
+            Json::from([(
+                "physicalLocation",
+                Json::from([
+                    ("artifactLocation", artifact(span)),
+                    ("region", region(span)),
+                ]),
+            )])
+        };
+
+        for entry in &logs.logs {
+            let mut message = entry.message.clone();
+            for note in &entry.notes {
+                message.push_str(&format!("\n{}: {}", note.level.name(), note.message));
+            }
+            let mut result = vec![
+                (
+                    "level",
+                    match entry.level {
+                        LogLevel::Error => "error",
+                        LogLevel::Warning => "warning",
+                        LogLevel::Info | LogLevel::Help => "note",
+                    }
+                    .into(),
+                ),
+                ("message", Json::from([("text", message.into())])),
+                ("locations", entry.span.map(location).into_iter().collect()),
+                (
+                    "relatedLocations",
+                    entry
+                        .labels
+                        .iter()
+                        .enumerate()
+                        .map(|(id, label)| {
+                            let Json::Object(mut related) = location(label.span) else {
+                                unreachable!()
+                            };
+                            related.push(("id", id.into()));
+                            related.push((
+                                "message",
+                                Json::from([("text", label.message.as_str().into())]),
+                            ));
+                            Json::Object(related)
+                        })
+                        .collect(),
+                ),
+                (
+                    "fixes",
+                    entry
+                        .suggestions
+                        .iter()
+                        .map(|suggestion| {
+                            let replacement = Json::from([
+                                ("deletedRegion", region(suggestion.span)),
+                                (
+                                    "insertedContent",
+                                    Json::from([("text", suggestion.replacement.as_str().into())]),
+                                ),
+                            ]);
+                            Json::from([
+                                (
+                                    "description",
+                                    Json::from([("text", suggestion.applicability.name().into())]),
+                                ),
+                                (
+                                    "artifactChanges",
+                                    Json::Array(vec![Json::from([
+                                        ("artifactLocation", artifact(suggestion.span)),
+                                        ("replacements", Json::Array(vec![replacement])),
+                                    ])]),
+                                ),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ];
+            if let Some(code) = entry.code {
+                let index = match self.rules.iter().position(|rule| *rule == code) {
+                    Some(index) => index,
+                    None => {
+                        self.rules.push(code);
+                        self.rules.len() - 1
+                    }
+                };
+                result.insert(0, ("ruleId", code.to_string().into()));
+                result.insert(1, ("ruleIndex", index.into()));
+            }
+            self.results.push(Json::Object(result));
+        }
+        self
+    }
+}
+
+impl Display for Sarif {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rules = self
+            .rules
+            .iter()
+            .map(|code| {
+                Json::from([
+                    ("id", code.to_string().into()),
+                    (
+                        "shortDescription",
+                        Json::from([("text", code.summary().into())]),
+                    ),
+                    (
+                        "fullDescription",
+                        Json::from([("text", code.explanation().into())]),
+                    ),
+                ])
+            })
+            .collect();
+        let driver = Json::from([
+            ("name", "automata".into()),
+            ("version", env!("CARGO_PKG_VERSION").into()),
+            ("rules", rules),
+        ]);
+        write!(
+            f,
+            r#"{{"$schema":"https://json.schemastore.org/sarif-2.1.0.json","version":"2.1.0","runs":[{{"tool":{{"driver":{driver}}},"columnKind":"utf16CodeUnits","results":["#
+        )?;
+        for (i, result) in self.results.iter().enumerate() {
+            if i != 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{result}")?;
+        }
+        write!(f, "]}}]}}")
+    }
+}
+
+#[test]
+fn render_labels() {
+    let src = "Q = {q0, q1}\nE = {a}\nd(q0, a) = q1\nd(q0, a) = q0";
+    let files = [SourceFile {
+        name: "main",
+        src,
+        included_at: None,
+    }];
+    let span = |text: &str, nth: usize| {
+        let start = src.match_indices(text).nth(nth).unwrap().0;
+        Span(start, start + text.len(), FileId(0))
+    };
+    let mut entry = LogEntry::new("conflict", Some(span("q0", 3)), LogLevel::Error);
+    entry
+        .label("conflicts here")
+        .secondary(span("q1", 1), "first defined here")
+        .secondary(span("d(q0, a)", 0), "for this transition")
+        .secondary(span("q0", 0), "declared here");
+    let display = LogEntryDisplay {
+        files: &files,
+        entry: &entry,
+        options: RenderOptions::new().color(false),
+    };
+
+    // every label is drawn under its own line, in the order they were added
+    assert_eq!(
+        display.to_string(),
+        "error: conflict
+1: Q = {q0, q1}
+        -- declared here
+2: E = {a}
+3: d(q0, a) = q1
+              -- first defined here
+   -------- for this transition
+4: d(q0, a) = q0
+              ~~ conflicts here
+"
+    );
+}
+
+#[test]
+fn render_labels_in_other_files() {
+    let files = [
+        SourceFile {
+            name: "main",
+            src: "include \"lib\"\nd(q0, a) = q1",
+            included_at: None,
+        },
+        SourceFile {
+            name: "lib",
+            src: "Q = {q0}\nE = {a}",
+            included_at: Some(Span(8, 13, FileId(0))),
+        },
+    ];
+    let mut entry = LogEntry::new(
+        "undefined state",
+        Some(Span(25, 27, FileId(0))),
+        LogLevel::Error,
+    );
+    entry
+        .label("not in Q")
+        .secondary(Span(4, 8, FileId(1)), "Q defined here");
+    let display = LogEntryDisplay {
+        files: &files,
+        entry: &entry,
+        options: RenderOptions::new().color(false),
+    };
+
+    // the primary file goes first and each file says where it starts
+    assert_eq!(
+        display.to_string(),
+        "error: undefined state
+--> main:2:12
+2: d(q0, a) = q1
+              ~~ not in Q
+--> lib:1:5
+1: Q = {q0}
+       ---- Q defined here
+"
+    );
+}
+
+#[test]
+fn render_notes_and_helps() {
+    let files = [SourceFile {
+        name: "main",
+        src: "E = {ab}",
+        included_at: None,
+    }];
+    let mut entry = LogEntry::new(
+        "invalid letter",
+        Some(Span(5, 7, FileId(0))),
+        LogLevel::Error,
+    );
+    entry
+        .code(Code::INVALID_LETTER)
+        .help("use a single character")
+        .note("letters are compared by character")
+        .help("or quote it");
+    assert_eq!(entry.notes[0].level, LogLevel::Help);
+    assert_eq!(entry.notes[1].level, LogLevel::Info);
+    let display = LogEntryDisplay {
+        files: &files,
+        entry: &entry,
+        options: RenderOptions::new().color(false),
+    };
+
+    // notes and helps follow the source in the order they were added
+    assert_eq!(
+        display.to_string(),
+        "error[A0028]: invalid letter
+1: E = {ab}
+        ~~
+help: use a single character
+info: letters are compared by character
+help: or quote it
+"
+    );
+
+    let display = LogEntryDisplay {
+        options: RenderOptions::new().html(),
+        ..display
+    };
+    let html = display.to_string();
+    assert!(html.contains("<span class=\"help\">help</span>"));
+    assert!(html.contains("<span class=\"info\">info</span>"));
+}
@@ -1,16 +1,24 @@
+use std::collections::HashMap;
+
 use crate::{
-    automatan::*,
+    automatan::{lint::Lint, *},
     dual_enum_serde,
     loader::{
         ast::TopLevel,
-        log::{LogEntry, LogSink},
+        codes::Code,
+        log::{Applicability, LogEntry, LogLevel, LogSink},
     },
 };
 
 pub mod ast;
+pub mod codes;
+pub mod cst;
+pub mod fmt;
 pub mod lexer;
 pub mod log;
 pub mod parser;
+pub mod source;
+pub mod suggest;
 
 #[macro_export]
 macro_rules! maker {
@@ -28,9 +36,36 @@ macro_rules! maker {
     };
 }
 
+/// Owns the names machines use which don't appear verbatim in the source
+/// (generated states, decoded escapes), for as long as the machines borrow them.
+///
+/// Names are compared by value so the same name may be stored more than once.
+#[derive(Default)]
+pub struct Interner(typed_arena::Arena<u8>);
+
+impl Interner {
+    pub fn intern(&self, str: &str) -> &str {
+        self.0.alloc_str(str)
+    }
+}
+
+impl Clone for Interner {
+    /// A clone starts out empty, names interned before stay with the original.
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl std::fmt::Debug for Interner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Interner").finish_non_exhaustive()
+    }
+}
+
 pub const INITIAL_STATE: &str = "q0";
 pub const INITIAL_STACK: &str = "z0";
 pub const BLANK_SYMBOL: &str = "B";
+pub const START_VARIABLE: &str = "S";
 
 #[macro_export]
 macro_rules! epsilon {
@@ -39,6 +74,13 @@ macro_rules! epsilon {
     };
 }
 
+#[macro_export]
+macro_rules! otherwise {
+    ($ident: ident) => {
+      $crate::maker!($ident: "otherwise", "*")
+    };
+}
+
 #[macro_export]
 macro_rules! delta_lower {
     ($ident: ident) => {
@@ -60,12 +102,22 @@ macro_rules! gamma_upper {
     };
 }
 
+/// Identifies a file of a [`source::SourceMap`], the root file is `FileId(0)`.
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct FileId(pub u32);
+
+/// A byte range of the file it belongs to.
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-pub struct Span(pub usize, pub usize);
+pub struct Span(pub usize, pub usize, pub FileId);
 impl Span {
     pub fn join(&self, end: Span) -> Span {
-        Span(self.0, end.1)
+        Span(self.0, end.1, self.2)
     }
 }
 
@@ -83,7 +135,10 @@ impl<T> Spanned<T> {
 
 pub struct Context<'a> {
     logs: log::Logs,
-    src: &'a str,
+    files: Vec<source::SourceFile<'a>>,
+    includes: HashMap<Span, Result<FileId, &'a str>>,
+    interner: &'a Interner,
+    resilient: bool,
 }
 
 impl<'a> LogSink for Context<'a> {
@@ -93,29 +148,129 @@ impl<'a> LogSink for Context<'a> {
 }
 
 impl<'a> Context<'a> {
-    pub fn new(src: &'a str) -> Self {
+    /// A context for `src` on its own, names which aren't in it go in `interner`.
+    pub fn new(src: &'a str, interner: &'a Interner) -> Self {
+        Self {
+            logs: log::Logs::new(),
+            files: vec![source::SourceFile {
+                name: "<input>",
+                src,
+                included_at: None,
+            }],
+            includes: HashMap::new(),
+            interner,
+            resilient: false,
+        }
+    }
+
+    /// A context for the root file of `sources`, which can include the others.
+    pub fn with_sources(sources: &'a source::SourceMap) -> Self {
         Self {
             logs: log::Logs::new(),
-            src,
+            files: sources.files().collect(),
+            includes: sources.includes().collect(),
+            interner: sources.interner(),
+            resilient: false,
         }
     }
 
+    /// Makes compilation best-effort, lines with errors are skipped and the
+    /// machine built from the rest is still returned alongside the logs.
+    pub fn resilient(mut self) -> Self {
+        self.resilient = true;
+        self
+    }
+
+    pub fn is_resilient(&self) -> bool {
+        self.resilient
+    }
+
+    /// Whether errors so far mean no machine should be returned.
+    pub fn should_abort(&self) -> bool {
+        self.contains_errors() && !self.resilient
+    }
+
     pub fn src(&self) -> &'a str {
-        self.src
+        self.files[0].src
+    }
+
+    /// Stores `str` for as long as the sources, for names which aren't in them.
+    pub fn intern(&self, str: &str) -> &'a str {
+        self.interner.intern(str)
+    }
+
+    pub fn file(&self, file: FileId) -> &source::SourceFile<'a> {
+        &self.files[file.0 as usize]
+    }
+
+    pub fn files(&self) -> &[source::SourceFile<'a>] {
+        &self.files
+    }
+
+    /// The file the include directive with the path at `path` refers to, `None`
+    /// if the path was never loaded.
+    pub fn include(&self, path: Span) -> Option<Result<FileId, &'a str>> {
+        self.includes.get(&path).copied()
+    }
+
+    /// Maps a span of an included file to the include directive in the root
+    /// file it was pulled in through.
+    pub fn root_span(&self, mut span: Span) -> Span {
+        while let Some(included_at) = self.file(span.2).included_at {
+            span = included_at;
+        }
+        span
     }
 
     pub fn logs_display(&self) -> impl Iterator<Item = log::LogEntryDisplay<'_>> {
-        self.logs.displayable_with(self.src)
+        self.logs.displayable_with(&self.files)
     }
 
     pub fn eof(&self) -> Span {
-        Span(self.src.len(), self.src.len())
+        self.files[0].eof(FileId(0))
+    }
+
+    /// The insertion which adds `line` as a new last line of the root file,
+    /// for suggestions of missing definitions.
+    pub fn append_line(&self, line: &str) -> (Span, String) {
+        let at = self.eof();
+        if self.src().is_empty() || self.src().ends_with('\n') {
+            (at, format!("{line}\n"))
+        } else {
+            (at, format!("\n{line}"))
+        }
+    }
+
+    /// Widens `span` to the whole lines it is on, including the line end, for
+    /// suggestions removing them.
+    pub fn whole_lines(&self, span: Span) -> Span {
+        let src = self.file(span.2).src;
+        let start = src[..span.0].rfind('\n').map_or(0, |i| i + 1);
+        let end = src[span.1..]
+            .find('\n')
+            .map_or(src.len(), |i| span.1 + i + 1);
+        Span(start, end, span.2)
+    }
+
+    /// Marks the current end of the logs, for [`Context::dedup_logs_since`].
+    pub fn logs_mark(&self) -> usize {
+        self.logs.entries().len()
+    }
+
+    /// Drops the entries logged since `mark` which repeat one logged since
+    /// then, so the expansions of one line report each problem once.
+    pub fn dedup_logs_since(&mut self, mark: usize) {
+        self.logs.dedup_from(mark);
     }
 
     pub fn contains_errors(&self) -> bool {
         self.logs.contains_errors()
     }
 
+    pub fn logs(&self) -> &log::Logs {
+        &self.logs
+    }
+
     pub fn into_logs(self) -> log::Logs {
         self.logs
     }
@@ -128,18 +283,89 @@ dual_enum_serde! {
         Fa(#[serde(borrow)] fa::Fa<'a>),
         Pda(#[serde(borrow)] pda::Pda<'a>),
         Tm(#[serde(borrow)] tm::Tm<'a>),
+        Cfg(#[serde(borrow)] cfg::Cfg<'a>),
     }
 }
 
 pub fn parse_universal<'a>(ctx: &mut Context<'a>) -> Option<Machine<'a>> {
-    let mut items = parser::Parser::new(ctx).collect::<Vec<_>>().into_iter();
-    if ctx.logs.contains_errors() {
+    let (directives, items): (Vec<_>, Vec<_>) = parser::Parser::new(ctx)
+        .partition(|item| matches!(item.0, TopLevel::Item(Spanned("allow" | "deny", _), _)));
+    let machine = compile(items, ctx);
+    let levels = lint_levels(directives, ctx);
+
+    apply_lint_levels(&mut ctx.logs, &levels);
+    if ctx.should_abort() {
+        return None;
+    }
+    machine
+}
+
+/// Sets the level of the warnings of every lint in `levels`, removing the
+/// allowed ones. Errors are left alone even if their code is a lint's.
+fn apply_lint_levels(logs: &mut log::Logs, levels: &HashMap<Lint, Spanned<Option<LogLevel>>>) {
+    logs.relevel(|entry| {
+        let level = entry
+            .code
+            .filter(|_| entry.level == LogLevel::Warning)
+            .and_then(Lint::from_code)
+            .and_then(|lint| levels.get(&lint));
+        match level {
+            Some(Spanned(Some(level), span)) => {
+                entry.secondary(*span, "denied here");
+                Some(*level)
+            }
+            Some(Spanned(None, _)) => None,
+            None => Some(entry.level),
+        }
+    });
+}
+
+/// The levels the `allow = {...}` and `deny = {...}` items set for lints,
+/// `None` for allowed lints, with the span of the name setting it.
+fn lint_levels<'a>(
+    directives: Vec<Spanned<TopLevel<'a>>>,
+    ctx: &mut Context<'a>,
+) -> HashMap<Lint, Spanned<Option<LogLevel>>> {
+    let mut levels = HashMap::new();
+    for directive in directives {
+        let TopLevel::Item(Spanned(directive, _), list) = directive.0 else {
+            continue;
+        };
+        let level = match directive {
+            "deny" => Some(LogLevel::Error),
+            _ => None,
+        };
+        let Some(list) = list.expect_set(ctx) else {
+            continue;
+        };
+        for item in list {
+            let Some(name) = item.expect_ident(ctx) else {
+                continue;
+            };
+            match name.parse::<Lint>() {
+                Ok(lint) => {
+                    levels.insert(lint, Spanned(level, item.1));
+                }
+                Err(_) => {
+                    ctx.emit_error(format!("unknown lint {name:?}"), item.1)
+                        .code(Code::UNKNOWN_LINT)
+                        .did_you_mean(item.1, name, Lint::ALL.map(Lint::name));
+                }
+            }
+        }
+    }
+    levels
+}
+
+fn compile<'a>(items: Vec<Spanned<TopLevel<'a>>>, ctx: &mut Context<'a>) -> Option<Machine<'a>> {
+    let mut items = items.into_iter();
+    if ctx.should_abort() {
         return None;
     }
 
     use Spanned as S;
 
-    #[derive(Debug)]
+    #[derive(Clone, Copy, Debug)]
     enum Type {
         Dfa,
         Nfa,
@@ -147,6 +373,8 @@ pub fn parse_universal<'a>(ctx: &mut Context<'a>) -> Option<Machine<'a>> {
         Npda,
         Tm,
         Ntm,
+        Regex,
+        Cfg,
     }
 
     fn parse_type<'a>(item: Option<S<TopLevel<'a>>>, ctx: &mut Context<'a>) -> Option<Type> {
@@ -156,12 +384,24 @@ pub fn parse_universal<'a>(ctx: &mut Context<'a>) -> Option<Machine<'a>> {
             }
             Some(S(_, span)) => {
                 ctx.emit_error("expected type=<type> as first item", span)
-                    .emit_help_logless("add: type = ...");
+                    .code(Code::MISSING_TYPE)
+                    .help("add: type = ...")
+                    .suggest(
+                        Span(0, 0, FileId(0)),
+                        "type = ...\n",
+                        Applicability::HasPlaceholders,
+                    );
                 return None;
             }
             None => {
                 ctx.emit_error("expected type=<type> as first item", ctx.eof())
-                    .emit_help_logless("add: type = ...");
+                    .code(Code::MISSING_TYPE)
+                    .help("add: type = ...")
+                    .suggest(
+                        Span(0, 0, FileId(0)),
+                        "type = ...\n",
+                        Applicability::HasPlaceholders,
+                    );
                 return None;
             }
         };
@@ -173,10 +413,18 @@ pub fn parse_universal<'a>(ctx: &mut Context<'a>) -> Option<Machine<'a>> {
             "npdaA" | "NPDA" => Type::Npda,
             "tm" | "TM" => Type::Tm,
             "ntm" | "NTM" => Type::Ntm,
+            "regex" | "REGEX" => Type::Regex,
+            "cfg" | "CFG" => Type::Cfg,
             _ => {
                 ctx.emit_error(
-                    "unknown type, expected 'DFA' | 'NFA' | 'DPDA' | 'NPDA' | 'TM' | 'NTM'",
+                    "unknown type, expected 'DFA' | 'NFA' | 'DPDA' | 'NPDA' | 'TM' | 'NTM' | 'REGEX' | 'CFG'",
+                    span,
+                )
+                .code(Code::UNKNOWN_TYPE)
+                .did_you_mean(
                     span,
+                    str,
+                    ["DFA", "NFA", "DPDA", "NPDA", "TM", "NTM", "REGEX", "CFG"],
                 );
                 return None;
             }
@@ -193,12 +441,448 @@ pub fn parse_universal<'a>(ctx: &mut Context<'a>) -> Option<Machine<'a>> {
         epsilon_moves: true,
     };
 
-    Some(match parse_type(items.next(), ctx)? {
+    let ty = parse_type(items.next(), ctx)?;
+    let machine = match ty {
         Type::Dfa => Machine::Fa(fa::Fa::compile(items, ctx, D)?),
         Type::Nfa => Machine::Fa(fa::Fa::compile(items, ctx, N)?),
         Type::Dpda => Machine::Pda(pda::Pda::compile(items, ctx, D)?),
         Type::Npda => Machine::Pda(pda::Pda::compile(items, ctx, N)?),
         Type::Tm => Machine::Tm(tm::Tm::compile(items, ctx, D)?),
         Type::Ntm => Machine::Tm(tm::Tm::compile(items, ctx, N)?),
-    })
+        Type::Regex => Machine::Fa(fa::Fa::compile_regex(items, ctx)?),
+        Type::Cfg => Machine::Cfg(cfg::Cfg::compile(items, ctx)?),
+    };
+
+    if !ctx.contains_errors() {
+        match (&machine, ty) {
+            (Machine::Fa(fa), Type::Regex) => lint::regex(fa, ctx),
+            (Machine::Fa(fa), ty) => lint::fa(fa, matches!(ty, Type::Dfa), ctx),
+            (Machine::Pda(pda), _) => lint::pda(pda, ctx),
+            (Machine::Tm(tm), _) => lint::tm(tm, ctx),
+            (Machine::Cfg(_), _) => {}
+        }
+    }
+    Some(machine)
+}
+
+#[test]
+fn resilient() {
+    let src = "type = DFA
+Q = {q0, q1}
+E = {a, b}
+F = {q1}
+d(q0, a) = q1
+d(q0, c) = q1
+d(q1, a = q0
+d(q1, b) = q0";
+    let interner = Interner::default();
+    assert!(parse_universal(&mut Context::new(src, &interner)).is_none());
+
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner).resilient();
+    let Some(Machine::Fa(fa)) = parse_universal(&mut ctx) else {
+        panic!("expected a partial machine");
+    };
+    assert!(ctx.contains_errors());
+    let codes: Vec<_> = ctx.logs().entries().iter().map(|e| e.code).collect();
+    assert!(codes.contains(&Some(Code::UNDEFINED_LETTER)));
+    assert!(codes.iter().all(Option::is_some));
+    assert_eq!(fa.states.len(), 2);
+    assert_eq!(fa.transitions.len(), 2);
+    assert!(
+        fa.transitions
+            .keys()
+            .all(|from| from.letter.is_some_and(|letter| letter.0 != "c"))
+    );
+
+    // conflicting transitions, epsilon moves and invalid letters are left out
+    let src = "type = DFA
+Q = {q0, q1}
+E = {a, bc}
+F = {q1}
+d(q0, a) = q1
+d(q0, a) = q0
+d(q0, ~) = q1";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner).resilient();
+    let Some(Machine::Fa(fa)) = parse_universal(&mut ctx) else {
+        panic!("expected a partial machine");
+    };
+    assert_eq!(fa.alphabet.len(), 1);
+    let transitions: Vec<_> = fa.transitions.values().flatten().collect();
+    assert_eq!(transitions.len(), 1);
+    assert_eq!(transitions[0].state.0, "q1");
+
+    // without a valid initial state there is no machine to return
+    let src = "type = DFA\nQ = {q1}\nE = {a}\nF = {q1}\nd(q1, a) = q1";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner).resilient();
+    assert!(parse_universal(&mut ctx).is_none());
+    assert!(ctx.contains_errors());
+}
+
+#[test]
+fn escapes() {
+    let src = r#"type = DFA
+Q = {q0}
+E = {"\'", "\\", "\u{3b1}", "~"}
+F = {q0}
+d(q0, {"'", "\\", "α", "~"}) = q0"#;
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    let Some(Machine::Fa(fa)) = parse_universal(&mut ctx) else {
+        panic!("failed to compile");
+    };
+    let mut letters: Vec<_> = fa.alphabet.keys().map(|letter| letter.0).collect();
+    letters.sort();
+    assert_eq!(letters, ["'", "\\", "~", "α"]);
+    assert_eq!(fa.transitions.len(), 4);
+
+    let errors = |src: &str| {
+        let interner = Interner::default();
+        let mut ctx = Context::new(src, &interner);
+        assert!(parse_universal(&mut ctx).is_none());
+        ctx.logs()
+            .entries()
+            .iter()
+            .filter(|entry| entry.level == LogLevel::Error)
+            .map(|entry| {
+                let span = entry.span.unwrap();
+                (entry.code.unwrap(), src[span.0..span.1].to_string())
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        errors("type = DFA\nQ = {q0}\nE = {\"ab\", \"\\n\"}\nF = {q0}"),
+        [(Code::INVALID_LETTER, "\"ab\"".to_string())]
+    );
+    assert_eq!(
+        errors("type = DFA\nQ = {q0}\nE = {\"a\\q\", \"b\\u{110000}\"}\nF = {q0}"),
+        [
+            (Code::INVALID_ESCAPE, "\\q".to_string()),
+            (Code::INVALID_ESCAPE, "\\u{110000}".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn multiline_brackets() {
+    let src = "type = NFA
+Q = {
+    q0,
+    q1, q2
+}
+E = {a, b}
+F = {q2}
+d(
+    q0,
+    a
+) = {q1,
+     q2}
+d(q1, b) = q2";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    let Some(Machine::Fa(fa)) = parse_universal(&mut ctx) else {
+        panic!("failed to compile");
+    };
+    assert!(!ctx.contains_errors());
+    assert_eq!(fa.states.len(), 3);
+    let transitions: usize = fa.transitions.values().map(Vec::len).sum();
+    assert_eq!(transitions, 3);
+
+    // an unclosed bracket doesn't swallow the item on the next line
+    let src = "type = DFA
+Q = {q0, q1
+E = {a}
+F = {q1}
+d(q0, a) = q1";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner).resilient();
+    let machine = parse_universal(&mut ctx);
+    let errors: Vec<_> = ctx
+        .logs()
+        .entries()
+        .iter()
+        .filter(|entry| entry.level == LogLevel::Error)
+        .map(|entry| (entry.code, entry.span.unwrap().0))
+        .collect();
+    assert_eq!(
+        errors[0],
+        (Some(Code::UNCLOSED), src.find("q1\n").unwrap() + 2)
+    );
+    assert!(
+        errors
+            .iter()
+            .all(|(code, _)| *code != Some(Code::UNEXPECTED_TOKEN))
+    );
+    let Some(Machine::Fa(fa)) = machine else {
+        panic!("expected a partial machine");
+    };
+    assert_eq!(fa.alphabet.len(), 1);
+}
+
+#[test]
+fn tables() {
+    // comments and blank lines between rows don't end the table
+    let src = "type = NFA
+Q = {q0, q1}
+E = {a, b}
+F = {q1}
+d  | a   b
+q0 | q1  q0
+
+// back to the start
+q1 | q0  -
+
+d  | a
+q1 | q1";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    let Some(Machine::Fa(fa)) = parse_universal(&mut ctx) else {
+        panic!("failed to compile");
+    };
+    let mut transitions: Vec<_> = fa
+        .transitions
+        .iter()
+        .flat_map(|(from, to)| {
+            to.iter()
+                .map(|to| (from.state.0, from.letter.unwrap().0, to.state.0))
+        })
+        .collect();
+    transitions.sort();
+    assert_eq!(
+        transitions,
+        [
+            ("q0", "a", "q1"),
+            ("q0", "b", "q0"),
+            ("q1", "a", "q0"),
+            ("q1", "a", "q1"),
+        ]
+    );
+
+    let src = "type = DFA\nQ = {q0}\nE = {a}\ndelta | a\nq0 | q0 q0\nF = {q0}\nf | a\nq0 | q0";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner).resilient();
+    parse_universal(&mut ctx);
+    let codes: Vec<_> = ctx.logs().entries().iter().map(|e| e.code).collect();
+    assert!(codes.contains(&Some(Code::MALFORMED_TABLE)));
+    assert!(codes.contains(&Some(Code::UNEXPECTED_FUNCTION)));
+}
+
+#[test]
+fn fixes() {
+    let src = "type = DFA
+Q = {q0, q1 q2}
+E = {a b, c}
+F = {q1}
+d(q0, {a..c}) = q1";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    parse_universal(&mut ctx);
+    let fixed = log::apply_suggestions(src, FileId(0), ctx.logs().entries());
+    assert_eq!(
+        fixed,
+        "type = DFA
+Q = {q0, q1, q2}
+E = {a, b, c}
+F = {q1}
+d(q0, {a..c}) = q1
+q0 = q0"
+    );
+
+    // only the lints about the machine itself are left
+    let interner = Interner::default();
+    let mut ctx = Context::new(&fixed, &interner);
+    assert!(parse_universal(&mut ctx).is_some());
+    assert!(ctx.logs().entries().iter().all(|entry| {
+        lint::Lint::ALL
+            .iter()
+            .any(|lint| entry.code == Some(lint.code()))
+    }));
+
+    // either line can go, so neither removal is applied automatically
+    let src = "type = DPDA
+Q = {q0}
+E = {a}
+G = {Z0}
+accept = N
+F = {q0}
+d(q0, a, Z0) = (q0, Z0)";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    parse_universal(&mut ctx);
+    let entry = ctx
+        .logs()
+        .entries()
+        .iter()
+        .find(|entry| entry.code == Some(Code::INVALID_ACCEPT_BY))
+        .unwrap();
+    let removed: Vec<_> = entry
+        .suggestions
+        .iter()
+        .map(|suggestion| {
+            assert_eq!(suggestion.applicability, log::Applicability::MaybeIncorrect);
+            assert_eq!(suggestion.replacement, "");
+            &src[suggestion.span.0..suggestion.span.1]
+        })
+        .collect();
+    assert_eq!(removed, ["F = {q0}\n", "accept = N\n"]);
+    assert_eq!(
+        log::apply_suggestions(src, FileId(0), ctx.logs().entries()),
+        format!("{src}\nq0 = q0\nz0 = Z0")
+    );
+}
+
+#[test]
+fn did_you_mean() {
+    let suggested = |src: &str| {
+        let interner = Interner::default();
+        let mut ctx = Context::new(src, &interner);
+        parse_universal(&mut ctx);
+        ctx.logs()
+            .entries()
+            .iter()
+            .flat_map(|entry| &entry.suggestions)
+            .filter(|suggestion| suggestion.applicability == Applicability::MaybeIncorrect)
+            .map(|suggestion| {
+                let Span(start, end, _) = suggestion.span;
+                format!("{} -> {}", &src[start..end], suggestion.replacement)
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        suggested("type = DFA\nQ = {q_a, q_b}\nE = {a}\nF = {q_b}\nq0 = qa\nd(q_a, a) = qb"),
+        ["qa -> q_a", "qb -> q_b"]
+    );
+    assert_eq!(suggested("type = Nfa\nQ = {q0}"), ["Nfa -> NFA"]);
+    assert_eq!(
+        suggested("type = DFA\nQ = {q0}\nSigma = {a}\nq0 = q0\ndelt(q0, a) = q0"),
+        ["Sigma -> sigma", "delt -> delta"]
+    );
+}
+
+#[test]
+fn allow_and_deny() {
+    let src = "type = NFA
+allow = {defaults, unused_letter}
+deny = {duplicate_transitions}
+Q = {q0, q1}
+E = {a, b}
+F = {q1}
+d(q0, a) = q1
+d(q0, a) = q1";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    assert!(parse_universal(&mut ctx).is_none());
+    let entries: Vec<_> = ctx
+        .logs()
+        .entries()
+        .iter()
+        .map(|entry| (entry.code, entry.level))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            (Some(Code::DUPLICATE_TRANSITION), LogLevel::Error),
+            (Some(Code::UNUSED_LETTER), LogLevel::Warning),
+            (Some(Code::UNKNOWN_LINT), LogLevel::Error),
+        ]
+    );
+
+    // directives can come before the type and silence parser warnings
+    let src = "allow = {duplicate_transitions, inconsistent_commas, unused_letters}
+type = NFA
+Q = {q0 q1, q2}
+E = {a, b}
+F = {q1}
+q0 = q0
+d(q0, a) = q1
+d(q0, a) = q1";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    assert!(parse_universal(&mut ctx).is_some());
+    let codes: Vec<_> = ctx
+        .logs()
+        .entries()
+        .iter()
+        .map(|entry| entry.code)
+        .collect();
+    assert_eq!(codes, [Some(Code::UNREACHABLE_STATE)]);
+
+    // allowing a lint never drops an error
+    let src = "type = CFG\nallow = {no_transitions}";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    assert!(parse_universal(&mut ctx).is_none());
+    assert!(ctx.contains_errors());
+
+    let mut logs = log::Logs::new();
+    logs.emit(LogEntry::new("no transitions", None, LogLevel::Error))
+        .code(Code::NO_TRANSITIONS);
+    logs.emit(LogEntry::new("no transitions", None, LogLevel::Warning))
+        .code(Code::NO_TRANSITIONS);
+    let span = Span(0, 0, FileId(0));
+    let levels = HashMap::from([(Lint::NoTransitions, Spanned(None, span))]);
+    apply_lint_levels(&mut logs, &levels);
+    let levels: Vec<_> = logs.entries().iter().map(|entry| entry.level).collect();
+    assert_eq!(levels, [LogLevel::Error]);
+    assert!(logs.contains_errors());
+}
+
+#[test]
+fn machine_readable_logs() {
+    let src = "type = DFA\nQ = {q0}\nE = {\"é\", b}\nq0 = q0\nd(q0, \"é\") = q1";
+    let position = log::Position::new(src, src.rfind("q1").unwrap());
+    assert_eq!(
+        (position.line, position.column, position.utf16_column),
+        (5, 15, 14)
+    );
+
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    parse_universal(&mut ctx);
+    let json = ctx.logs().to_json_lines(ctx.files());
+    assert_eq!(json.lines().count(), ctx.logs().entries().len());
+    let undefined = json
+        .lines()
+        .find(|line| line.contains("\"code\":\"A"))
+        .unwrap();
+    assert!(undefined.starts_with("{\"level\":\"error\""));
+    assert!(undefined.contains(
+        "\"start\":{\"offset\":56,\"line\":5,\"column\":15,\"utf16_offset\":54,\"utf16_column\":14}"
+    ));
+
+    let sarif = log::Sarif::new().add(ctx.logs(), ctx.files()).to_string();
+    assert!(sarif.contains("\"version\":\"2.1.0\""));
+    assert!(sarif.contains("\"startLine\":5,\"startColumn\":14"));
+}
+
+#[test]
+fn render_options() {
+    let src = "type = DFA\nQ = {q0, q1}\nE = {a}\nF = {q1}\nq0 = q0\n\n\n\nd(q0, a) = q1\n\td(q0, a) = q0";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    parse_universal(&mut ctx);
+    let rendered = |options| {
+        ctx.logs_display()
+            .map(|entry| entry.options(options).to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // line 9 lines up with line 10, and the tab goes to the next stop
+    let plain = rendered(log::RenderOptions::new().color(false).tab_width(2));
+    assert_eq!(
+        plain[0],
+        "error[A0015]: transition already defined for (q0, a) (non determinism not permitted)
+ 9: d(q0, a) = q1
+               -- first defined here
+10:   d(q0, a) = q0
+                 ~~ conflicts here
+            - starting from this letter
+"
+    );
+
+    let html = rendered(log::RenderOptions::new().html().context_lines(1));
+    assert!(html[0].contains("<span class=\"gutter\"> 8: </span>\n"));
+    assert!(html[0].starts_with("<pre class=\"log\"><span class=\"error\">error</span>"));
 }
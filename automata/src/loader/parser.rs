@@ -1,9 +1,13 @@
 use std::borrow::Cow;
 
-use crate::epsilon;
-use crate::loader::log::LogSink;
-use crate::loader::{Context, Span};
+use crate::loader::codes::Code;
+use crate::loader::log::{Applicability, LogSink};
+use crate::{epsilon, otherwise};
+use std::collections::HashSet;
 
+use crate::loader::{Context, FileId, Span, source};
+
+use super::lexer::StringKind;
 use super::lexer::Token as T;
 use crate::loader::Spanned as S;
 
@@ -13,6 +17,15 @@ use super::lexer::Lexer;
 pub struct Parser<'a, 'b> {
     lexer: Lexer<'a>,
     peek: Option<S<T<'a>>>,
+    /// How many brackets are open, line ends inside them are skipped.
+    depth: usize,
+    /// The lexers of the files currently including another file, along with
+    /// the span of their include directive.
+    included: Vec<(Lexer<'a>, Span)>,
+    /// Files already included, each file is only included once.
+    seen: HashSet<FileId>,
+    /// Whether the included file being parsed has ended.
+    ended: bool,
     ctx: &'b mut Context<'a>,
 }
 
@@ -24,12 +37,33 @@ impl<'a, 'b> Iterator for Parser<'a, 'b> {
     }
 }
 
+/// Whether the line `lexer` is at can continue a bracket left open on the
+/// line before.
+pub(crate) fn line_continues(lexer: Lexer) -> bool {
+    let mut depth = 0usize;
+    for S(token, _) in lexer {
+        match token {
+            Ok(T::LineEnd) => break,
+            Ok(T::LPar | T::LBrace | T::LBracket) => depth += 1,
+            Ok(T::RPar | T::RBrace | T::RBracket) if depth == 0 => return true,
+            Ok(T::RPar | T::RBrace | T::RBracket) => depth -= 1,
+            Ok(T::Eq | T::LSmallArrow | T::Or) => return false,
+            _ => {}
+        }
+    }
+    true
+}
+
 impl<'a, 'b> Parser<'a, 'b> {
     pub fn new(ctx: &'b mut Context<'a>) -> Self {
         Parser {
             lexer: Lexer::new(ctx.src()),
             ctx,
             peek: None,
+            depth: 0,
+            included: Vec::new(),
+            seen: HashSet::from([FileId::default()]),
+            ended: false,
         }
     }
 
@@ -48,17 +82,39 @@ impl<'a, 'b> Parser<'a, 'b> {
         loop {
             match self.lexer.next() {
                 Some(S(Ok(T::Comment(_)), _)) => {}
+                Some(S(Ok(T::LineEnd), _)) if self.depth > 0 && self.next_line_continues() => {}
                 Some(S(Ok(T::LineEnd), span)) => {
                     self.peek = Some(S(T::LineEnd, span));
                     return self.peek;
                 }
                 Some(S(Ok(ok), r)) => return Some(S(ok, r)),
-                Some(S(Err(err), span)) => _ = self.ctx.emit_error(format!("lexer: {err:?}"), span),
-                None => return None,
+                Some(S(Err(err), span)) => {
+                    _ = self
+                        .ctx
+                        .emit_error(format!("lexer: {err:?}"), span)
+                        .code(Code::LEXER)
+                }
+                // an included file ends its last line before resuming the includer
+                None if !self.included.is_empty() && !self.ended => {
+                    self.ended = true;
+                    self.peek = Some(S(T::LineEnd, self.lexer.eof()));
+                    return self.peek;
+                }
+                None => {
+                    (self.lexer, _) = self.included.pop()?;
+                    self.ended = false;
+                }
             }
         }
     }
 
+    /// Whether the line after the current line end can continue an open
+    /// bracket, that is it closes the bracket or doesn't look like the start
+    /// of a new top level item.
+    fn next_line_continues(&self) -> bool {
+        line_continues(self.lexer)
+    }
+
     fn peek_token_optional(&mut self) -> Option<S<T<'a>>> {
         if self.peek.is_none() {
             self.peek = self.next_token_optional();
@@ -68,12 +124,12 @@ impl<'a, 'b> Parser<'a, 'b> {
 
     fn next_token(&mut self) -> S<T<'a>> {
         self.next_token_optional()
-            .unwrap_or(S(T::LineEnd, self.ctx.eof()))
+            .unwrap_or(S(T::LineEnd, self.lexer.eof()))
     }
 
     fn peek_token(&mut self) -> S<T<'a>> {
         self.peek_token_optional()
-            .unwrap_or(S(T::LineEnd, self.ctx.eof()))
+            .unwrap_or(S(T::LineEnd, self.lexer.eof()))
     }
 
     fn expect_token(&mut self, expected: T<'a>) -> (bool, Span) {
@@ -83,10 +139,12 @@ impl<'a, 'b> Parser<'a, 'b> {
                 (true, span)
             }
             S(token, span) => {
-                self.ctx.emit_error(
-                    format!("unexpected {:#} expected {:}", token, expected),
-                    span,
-                );
+                self.ctx
+                    .emit_error(
+                        format!("unexpected {:#} expected {:}", token, expected),
+                        span,
+                    )
+                    .code(Code::UNEXPECTED_TOKEN);
                 (false, span)
             }
         }
@@ -96,17 +154,22 @@ impl<'a, 'b> Parser<'a, 'b> {
         match tok {
             S(T::Tilde, r) => S(Symbol::Epsilon("~"), r),
             S(T::Ident(repr @ epsilon!(pat)), r) => S(Symbol::Epsilon(repr), r),
+            S(T::Star, r) => S(Symbol::Otherwise("*"), r),
+            S(T::Ident(repr @ otherwise!(pat)), r) => S(Symbol::Otherwise(repr), r),
             S(T::Ident(ident), r) => S(Symbol::Ident(ident), r),
             S(got, span) => {
-                self.ctx.emit_error(
-                    format!(
-                        "unexpected {:#} expected symbol ( {:} | {:} )",
-                        got,
-                        T::Tilde,
-                        T::Ident("")
-                    ),
-                    span,
-                );
+                self.ctx
+                    .emit_error(
+                        format!(
+                            "unexpected {:#} expected symbol ( {:} | {:} | {:} )",
+                            got,
+                            T::Tilde,
+                            T::Star,
+                            T::Ident("")
+                        ),
+                        span,
+                    )
+                    .code(Code::UNEXPECTED_TOKEN);
                 S(Symbol::Ident("<INVALID>"), span)
             }
         }
@@ -123,6 +186,7 @@ impl<'a, 'b> Parser<'a, 'b> {
         if !matched {
             return S(Tuple(Vec::new()), start);
         }
+        self.depth += 1;
 
         while !matches!(self.peek_token().0, T::RPar) {
             items.push(self.parse_item());
@@ -131,11 +195,14 @@ impl<'a, 'b> Parser<'a, 'b> {
             }
             if let S(T::LineEnd, span) = self.peek_token() {
                 self.ctx
-                    .emit_error(format!("unexpected eol expected {:}", T::RPar), span);
+                    .emit_error(format!("unexpected eol expected {:}", T::RPar), span)
+                    .code(Code::UNCLOSED);
+                self.depth -= 1;
                 return S(Tuple(items), start.join(span));
             }
         }
 
+        self.depth -= 1;
         let (_, end) = self.expect_token(T::RPar);
 
         S(Tuple(items), start.join(end))
@@ -145,19 +212,75 @@ impl<'a, 'b> Parser<'a, 'b> {
         let (r, k, e, s) = match tok {
             S(T::String(r, k, e), s) => (r, k, e, s),
             S(t, s) => {
-                self.ctx.emit_error(
-                    format!(
-                        "unexpected {:#} expected {:}",
-                        t,
-                        T::String("", Default::default(), false)
-                    ),
-                    s,
-                );
+                self.ctx
+                    .emit_error(
+                        format!(
+                            "unexpected {:#} expected {:}",
+                            t,
+                            T::String("", Default::default(), false)
+                        ),
+                        s,
+                    )
+                    .code(Code::UNEXPECTED_TOKEN);
                 return S("<INVALID>".into(), s);
             }
         };
 
-        S(r.into(), s)
+        if !e || k == StringKind::Regex {
+            return S(r.into(), s);
+        }
+        S(self.unescape(r, s.0 + 1, s.2).into(), s)
+    }
+
+    /// Decodes the escapes in the contents of a string starting at `base`.
+    fn unescape(&mut self, raw: &str, base: usize, file: FileId) -> String {
+        let mut string = String::with_capacity(raw.len());
+        let mut chars = raw.char_indices();
+        while let Some((start, c)) = chars.next() {
+            if c != '\\' {
+                string.push(c);
+                continue;
+            }
+            let decoded = match chars.next() {
+                Some((_, 'n')) => Ok('\n'),
+                Some((_, 't')) => Ok('\t'),
+                Some((_, 'r')) => Ok('\r'),
+                Some((_, '0')) => Ok('\0'),
+                Some((_, c @ ('\\' | '"' | '\''))) => Ok(c),
+                Some((_, 'u')) => {
+                    let rest = chars.as_str();
+                    match rest
+                        .strip_prefix('{')
+                        .and_then(|r| r.split_once('}'))
+                        .map(|(hex, _)| hex)
+                    {
+                        Some(hex) => {
+                            for _ in 0..hex.chars().count() + 2 {
+                                chars.next();
+                            }
+                            u32::from_str_radix(hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or("invalid unicode scalar in escape")
+                        }
+                        None => Err("invalid unicode escape, expected \\u{...}"),
+                    }
+                }
+                Some(_) => Err("unknown escape"),
+                None => Err("unterminated escape"),
+            };
+            let end = chars.offset();
+            match decoded {
+                Ok(c) => string.push(c),
+                Err(msg) => {
+                    self.ctx
+                        .emit_error(msg, Span(base + start, base + end, file))
+                        .code(Code::INVALID_ESCAPE)
+                        .help("valid escapes are \\n \\t \\r \\0 \\\\ \\\" \\' \\u{...}");
+                }
+            }
+        }
+        string
     }
 
     fn parse_string(&mut self) -> S<Cow<'a, str>> {
@@ -167,7 +290,17 @@ impl<'a, 'b> Parser<'a, 'b> {
 
     fn parse_item(&mut self) -> S<Item<'a>> {
         match self.peek_token().0 {
-            T::Ident(_) | T::Tilde => self.parse_symbol().map(Item::Symbol),
+            T::Ident(ident) => {
+                let symbol = self.parse_symbol();
+                match self.peek_token() {
+                    S(T::LBrace, span) if span.0 == symbol.1.1 => {
+                        self.parse_template(S(ident, symbol.1)).map(Item::Template)
+                    }
+                    _ => symbol.map(Item::Symbol),
+                }
+            }
+            T::Tilde | T::Star => self.parse_symbol().map(Item::Symbol),
+            T::String(_, StringKind::Regex, _) => self.parse_regex().map(Item::Regex),
             T::String(_, _, _) => self.parse_string().map(Item::String),
             T::LPar => self.parse_tupple().map(Item::Tuple),
             T::LBrace | T::LBracket => self.parse_list().map(Item::List),
@@ -175,9 +308,10 @@ impl<'a, 'b> Parser<'a, 'b> {
                 let S(got, span) = self.next_token();
                 self.ctx.emit_error(
                     format!(
-                        "unexpected {:#} expected item ( {:} | {:} | {:} | {:} | {:} | {:} )",
+                        "unexpected {:#} expected item ( {:} | {:} | {:} | {:} | {:} | {:} | {:} )",
                         got,
                         T::Tilde,
+                        T::Star,
                         T::Ident(""),
                         T::String("", Default::default(), false),
                         T::LPar,
@@ -185,59 +319,208 @@ impl<'a, 'b> Parser<'a, 'b> {
                         T::LBracket,
                     ),
                     span,
-                );
+                )
+                .code(Code::UNEXPECTED_TOKEN);
                 S(Item::Symbol(Symbol::Ident("<INVALID>")), span)
             }
         }
     }
 
-    fn parse_list(&mut self) -> S<List<'a>> {
-        let mut list = Vec::new();
+    /// Parses the remainder of a template after its leading ident. Parts of a
+    /// template can't be separated by whitespace, so `q{i}_{j}` is one
+    /// template while `q {i}` is an ident followed by a list.
+    fn parse_template(&mut self, first: S<&'a str>) -> S<Template<'a>> {
+        let mut segments = vec![S(Segment::Literal(first.0), first.1)];
+        let mut end = first.1;
+        loop {
+            match self.peek_token() {
+                S(T::LBrace, start) if start.0 == end.1 => {
+                    self.next_token();
+                    let expr = self.parse_range_expr();
+                    let (closed, close) = self.expect_token(T::RBrace);
+                    end = start.join(if closed { close } else { expr.1 });
+                    segments.push(S(Segment::Expr(expr), end));
+                    if !closed {
+                        break;
+                    }
+                }
+                S(T::Ident(ident), span) if span.0 == end.1 => {
+                    self.next_token();
+                    segments.push(S(Segment::Literal(ident), span));
+                    end = span;
+                }
+                _ => break,
+            }
+        }
+        S(Template(segments), first.1.join(end))
+    }
 
-        let (start, match_end) = match self.next_token() {
-            S(T::LBrace, span) => (span, T::RBrace),
-            S(T::LBracket, span) => (span, T::RBracket),
+    fn parse_range_expr(&mut self) -> S<Expr<'a>> {
+        let start = self.parse_sum_expr();
+        if !matches!(self.peek_token().0, T::DotDot) {
+            return start;
+        }
+        self.next_token();
+        let end = self.parse_sum_expr();
+        let span = start.1.join(end.1);
+        S(Expr::Range(Box::new(start), Box::new(end)), span)
+    }
+
+    fn parse_sum_expr(&mut self) -> S<Expr<'a>> {
+        let mut lhs = self.parse_product_expr();
+        loop {
+            let op = match self.peek_token().0 {
+                T::Plus => BinOp::Add,
+                T::Dash => BinOp::Sub,
+                _ => return lhs,
+            };
+            self.next_token();
+            let rhs = self.parse_product_expr();
+            let span = lhs.1.join(rhs.1);
+            lhs = S(Expr::Binary(op, Box::new(lhs), Box::new(rhs)), span);
+        }
+    }
+
+    fn parse_product_expr(&mut self) -> S<Expr<'a>> {
+        let mut lhs = self.parse_unary_expr();
+        loop {
+            let op = match self.peek_token().0 {
+                T::Star => BinOp::Mul,
+                T::Slash => BinOp::Div,
+                T::Percent => BinOp::Rem,
+                _ => return lhs,
+            };
+            self.next_token();
+            let rhs = self.parse_unary_expr();
+            let span = lhs.1.join(rhs.1);
+            lhs = S(Expr::Binary(op, Box::new(lhs), Box::new(rhs)), span);
+        }
+    }
+
+    fn parse_unary_expr(&mut self) -> S<Expr<'a>> {
+        match self.peek_token() {
+            S(T::Dash, start) => {
+                self.next_token();
+                let expr = self.parse_unary_expr();
+                let span = start.join(expr.1);
+                S(Expr::Neg(Box::new(expr)), span)
+            }
+            S(T::LPar, start) => {
+                self.next_token();
+                let expr = self.parse_sum_expr();
+                let (_, end) = self.expect_token(T::RPar);
+                S(expr.0, start.join(end))
+            }
+            S(T::Ident(ident), span) => {
+                self.next_token();
+                if ident.starts_with(|c: char| c.is_ascii_digit()) {
+                    match ident.parse() {
+                        Ok(int) => S(Expr::Int(int), span),
+                        Err(_) => {
+                            self.ctx
+                                .emit_error("invalid integer", span)
+                                .code(Code::INVALID_INTEGER);
+                            S(Expr::Int(0), span)
+                        }
+                    }
+                } else {
+                    S(Expr::Var(ident), span)
+                }
+            }
             S(got, span) => {
                 self.ctx.emit_error(
                     format!(
-                        "unexpected {:#} expected list start ( {:} | {:} )",
+                        "unexpected {:#} expected expression ( integer | variable | {:} | {:} )",
                         got,
-                        T::RBrace,
-                        T::RBracket
+                        T::LPar,
+                        T::Dash
                     ),
                     span,
-                );
+                )
+                .code(Code::UNEXPECTED_TOKEN);
+                S(Expr::Int(0), span)
+            }
+        }
+    }
+
+    fn parse_list(&mut self) -> S<List<'a>> {
+        let mut list = Vec::new();
+
+        let (start, match_end) = match self.next_token() {
+            S(T::LBrace, span) => (span, T::RBrace),
+            S(T::LBracket, span) => (span, T::RBracket),
+            S(got, span) => {
+                self.ctx
+                    .emit_error(
+                        format!(
+                            "unexpected {:#} expected list start ( {:} | {:} )",
+                            got,
+                            T::RBrace,
+                            T::RBracket
+                        ),
+                        span,
+                    )
+                    .code(Code::UNEXPECTED_TOKEN);
                 return S(List(Vec::new(), ListKind::BracketComma), span);
             }
         };
+        self.depth += 1;
 
         let mut comma = false;
+        let mut first = true;
+        // where the first inconsistent delimiter is and the ends of the items
+        // not followed by a comma, to suggest adding the missing ones
+        let mut inconsistent = None;
+        let mut missing = Vec::new();
         while self.peek_token().0 != match_end {
-            list.push(self.parse_item());
+            let item = self.parse_item();
+            let item_end = if self.peek_token().0 == T::DotDot {
+                self.parse_letter_range(item, &mut list)
+            } else {
+                let span = item.1;
+                list.push(item);
+                span
+            };
 
-            if list.len() != 1
-                && self.peek_token().0 != match_end
-                && !matches!(self.peek_token().0, T::LineEnd)
-                && matches!(self.peek_token().0, T::Comma) != comma
+            let S(next, next_span) = self.peek_token();
+            if next != match_end && !matches!(next, T::LineEnd | T::Comma) {
+                missing.push(Span(item_end.1, item_end.1, item_end.2));
+            }
+            if !std::mem::replace(&mut first, false)
+                && next != match_end
+                && !matches!(next, T::LineEnd)
+                && matches!(next, T::Comma) != comma
             {
-                let span = self.peek_token().1;
-                self.ctx.emit_warning(
-                    "inconsistent comma delimiting. use commas to delimit all or no items",
-                    span,
-                );
+                inconsistent.get_or_insert(next_span);
             }
             if matches!(self.peek_token().0, T::Comma) {
                 comma = true;
                 self.next_token();
             }
             if let S(T::LineEnd, span) = self.peek_token() {
-                self.ctx.emit_error(
-                    format!("unexpected eol expected list close ( {:} )", match_end),
-                    span,
-                );
+                self.ctx
+                    .emit_error(
+                        format!("unexpected eol expected list close ( {:} )", match_end),
+                        span,
+                    )
+                    .code(Code::UNCLOSED);
+                self.depth -= 1;
                 return S(List(list, ListKind::BraceComma), start.join(span));
             }
         }
+        self.depth -= 1;
+        if let Some(span) = inconsistent {
+            let entry = self
+                .ctx
+                .emit_warning(
+                    "inconsistent comma delimiting. use commas to delimit all or no items",
+                    span,
+                )
+                .code(Code::INCONSISTENT_COMMAS);
+            for at in missing {
+                entry.suggest(at, ",", Applicability::MachineApplicable);
+            }
+        }
         let (_, end) = self.expect_token(match_end);
         let kind = match (comma, match_end) {
             (true, T::RBrace) => ListKind::BraceComma,
@@ -249,8 +532,88 @@ impl<'a, 'b> Parser<'a, 'b> {
         S(List(list, kind), start.join(end))
     }
 
+    /// Expands `start..end` inside a list into one item per character in the
+    /// range, each spanning the whole range, and returns that span.
+    fn parse_letter_range(&mut self, start: S<Item<'a>>, list: &mut Vec<S<Item<'a>>>) -> Span {
+        const MAX_RANGE: u32 = 4096;
+
+        self.next_token();
+        let end = self.parse_item();
+        let span = start.1.join(end.1);
+
+        fn letter(item: &Item) -> Option<char> {
+            let str: &str = match item {
+                Item::Symbol(Symbol::Ident(str)) => str,
+                Item::String(str) => str,
+                _ => return None,
+            };
+            let mut chars = str.chars();
+            chars.next().filter(|_| chars.next().is_none())
+        }
+
+        let (Some(first), Some(last)) = (letter(&start.0), letter(&end.0)) else {
+            self.ctx
+                .emit_error("range bounds must be single letters", span)
+                .code(Code::INVALID_RANGE)
+                .help("write the range like {a..z}");
+            return span;
+        };
+        if first > last {
+            self.ctx
+                .emit_error(format!("empty range, {first:?} comes after {last:?}"), span)
+                .code(Code::INVALID_RANGE);
+            return span;
+        }
+        if last as u32 - first as u32 >= MAX_RANGE {
+            self.ctx
+                .emit_error(
+                    format!("range is too large, it can have at most {MAX_RANGE} letters"),
+                    span,
+                )
+                .code(Code::INVALID_RANGE);
+            return span;
+        }
+
+        let string = matches!(start.0, Item::String(_));
+        for c in first..=last {
+            let str = self.ctx.intern(c.encode_utf8(&mut [0; 4]));
+            let item = if string {
+                Item::String(Cow::Borrowed(str))
+            } else {
+                Item::Symbol(Symbol::Ident(str))
+            };
+            list.push(S(item, span));
+        }
+        span
+    }
+
     fn parse_regex(&mut self) -> S<Regex<'a>> {
-        todo!()
+        match self.next_token() {
+            S(T::String(src, StringKind::Regex, _), span) => {
+                let regex = RegexParser {
+                    src,
+                    base: span.0 + 2,
+                    file: span.2,
+                    position: 0,
+                    ctx: self.ctx,
+                }
+                .parse();
+                S(regex.0, span)
+            }
+            S(got, span) => {
+                self.ctx
+                    .emit_error(
+                        format!(
+                            "unexpected {:#} expected {:#}",
+                            got,
+                            T::String("", StringKind::Regex, false)
+                        ),
+                        span,
+                    )
+                    .code(Code::UNEXPECTED_TOKEN);
+                S(Regex::Concat(Vec::new()), span)
+            }
+        }
     }
 
     fn parse_as_production_unit(&mut self, tok: S<T<'a>>) -> S<ProductionUnit<'a>> {
@@ -260,16 +623,18 @@ impl<'a, 'b> Parser<'a, 'b> {
             S(T::Ident(ident), r) => S(ProductionUnit::Ident(ident), r),
             S(T::String(_, _, _), _) => self.parse_as_string(tok).map(ProductionUnit::String),
             S(got, span) => {
-                self.ctx.emit_error(
-                    format!(
-                        "unexpected {:#} expected production unit ( {:} | {:} | {:} )",
-                        got,
-                        T::Tilde,
-                        T::Ident(""),
-                        T::String("", Default::default(), false)
-                    ),
-                    span,
-                );
+                self.ctx
+                    .emit_error(
+                        format!(
+                            "unexpected {:#} expected production unit ( {:} | {:} | {:} )",
+                            got,
+                            T::Tilde,
+                            T::Ident(""),
+                            T::String("", Default::default(), false)
+                        ),
+                        span,
+                    )
+                    .code(Code::UNEXPECTED_TOKEN);
                 S(ProductionUnit::Ident("<INVALID>"), span)
             }
         }
@@ -312,7 +677,8 @@ impl<'a, 'b> Parser<'a, 'b> {
             if group.0.is_empty() {
                 let span = self.peek_token().1;
                 self.ctx
-                    .emit_error("cannot have empty production group", span);
+                    .emit_error("cannot have empty production group", span)
+                    .code(Code::EMPTY_PRODUCTION);
             }
 
             let group_start = group.0.first().map(|g| g.1).unwrap_or(start);
@@ -327,10 +693,12 @@ impl<'a, 'b> Parser<'a, 'b> {
         }
 
         if groups.is_empty() {
-            self.ctx.emit_error(
-                "cannot have empty production rule",
-                start.join(lhs_group_end),
-            );
+            self.ctx
+                .emit_error(
+                    "cannot have empty production rule",
+                    start.join(lhs_group_end),
+                )
+                .code(Code::EMPTY_PRODUCTION);
         }
 
         let rules_start = groups.first().map(|f| f.1).unwrap_or(start);
@@ -361,12 +729,165 @@ impl<'a, 'b> Parser<'a, 'b> {
         Some(S(TopLevel::TransitionFunc(dest, item), span))
     }
 
+    /// Parses a transition table, the header having been read up to `name`.
+    ///
+    /// ```text
+    /// d  | a   b
+    /// q0 | q1  q0
+    /// q1 | q1  -
+    /// ```
+    ///
+    /// Rows continue for as long as lines start with `label |`, `-` marks an
+    /// empty cell and further `|` between cells are optional.
+    fn parse_table(&mut self, name: &'a str, start: Span) -> S<TopLevel<'a>> {
+        self.expect_token(T::Or);
+
+        let mut columns = Vec::new();
+        let mut end = start;
+        while !matches!(self.peek_token().0, T::LineEnd) {
+            if matches!(self.peek_token().0, T::Or) {
+                self.next_token();
+                continue;
+            }
+            let column = self.parse_item();
+            end = column.1;
+            columns.push(column);
+        }
+        if columns.is_empty() {
+            self.ctx
+                .emit_error("table must have at least one column", start)
+                .code(Code::MALFORMED_TABLE);
+        }
+
+        let mut rows = Vec::new();
+        loop {
+            if !self.next_line_is_table_row(name) {
+                break;
+            }
+            self.advance_line();
+            while matches!(self.peek_token().0, T::LineEnd) {
+                self.advance_line();
+            }
+
+            let label = self.parse_item();
+            self.expect_token(T::Or);
+
+            let mut cells = Vec::new();
+            while !matches!(self.peek_token().0, T::LineEnd) {
+                match self.peek_token() {
+                    S(T::Or, _) => _ = self.next_token(),
+                    S(T::Dash, span) => {
+                        self.next_token();
+                        cells.push(S(None, span));
+                    }
+                    _ => cells.push(self.parse_item().map(Some)),
+                }
+            }
+
+            let row_end = cells.last().map(|cell| cell.1).unwrap_or(label.1);
+            if cells.len() != columns.len() {
+                self.ctx
+                    .emit_error(
+                        format!(
+                            "table row has {} cells but the table has {} columns",
+                            cells.len(),
+                            columns.len()
+                        ),
+                        label.1.join(row_end),
+                    )
+                    .code(Code::MALFORMED_TABLE)
+                    .help("use '-' for cells without a transition");
+            }
+            end = row_end;
+            rows.push(TableRow { label, cells });
+        }
+
+        S(
+            TopLevel::Table(Table {
+                name: S(name, start),
+                columns,
+                rows,
+            }),
+            start.join(end),
+        )
+    }
+
+    /// Looks past the current line end, and any blank or comment only lines
+    /// after it, for `label |` without consuming anything. A label naming the
+    /// table starts a new table instead.
+    fn next_line_is_table_row(&self, name: &str) -> bool {
+        let mut tokens = self.lexer.filter_map(|token| match token {
+            S(Ok(T::Comment(_) | T::LineEnd), _) => None,
+            S(token, _) => Some(token),
+        });
+        match (tokens.next(), tokens.next()) {
+            (Some(Ok(T::Ident(label))), Some(Ok(T::Or))) => label != name,
+            (Some(Ok(T::Tilde | T::String(_, _, _))), Some(Ok(T::Or))) => true,
+            _ => false,
+        }
+    }
+
+    /// Continues parsing in the file included by `directive`, returning to the
+    /// current file once it ends.
+    fn include(&mut self, path: &str, path_span: Span, directive: Span) {
+        let file = match self.ctx.include(path_span) {
+            Some(Ok(file)) => file,
+            Some(Err(err)) => {
+                self.ctx
+                    .emit_error(format!("cannot include {path:?}: {err}"), path_span)
+                    .code(Code::INCLUDE_FAILED);
+                return;
+            }
+            None => {
+                self.ctx
+                    .emit_error(format!("cannot include {path:?}"), path_span)
+                    .code(Code::INCLUDE_FAILED)
+                    .note("no files are available to include from");
+                return;
+            }
+        };
+
+        if let Some(start) = std::iter::once(self.lexer.file())
+            .chain(self.included.iter().rev().map(|(lexer, _)| lexer.file()))
+            .position(|includer| includer == file)
+        {
+            let entry = self
+                .ctx
+                .emit_error(
+                    format!("include cycle, {path:?} is already being included"),
+                    directive,
+                )
+                .code(Code::INCLUDE_CYCLE);
+            for (_, includer) in self.included.iter().rev().take(start) {
+                entry.secondary(*includer, "which is included here");
+            }
+            return;
+        }
+        if !self.seen.insert(file) {
+            return;
+        }
+
+        let lexer = Lexer::with_file(self.ctx.file(file).src, file);
+        let includer = std::mem::replace(&mut self.lexer, lexer);
+        self.included.push((includer, directive));
+        self.ended = false;
+    }
+
     pub fn next_element(&mut self) -> Option<S<TopLevel<'a>>> {
         let result = loop {
             let next = self.next_token_optional()?;
             match (next, self.peek_token()) {
                 // empty
                 (S(T::LineEnd, _), _) => self.advance_line(),
+                // include
+                (
+                    S(T::Ident(ident), start),
+                    S(T::String(path, StringKind::Regular, _), path_span),
+                ) if source::is_include(ident) => {
+                    self.next_token();
+                    self.advance_line();
+                    self.include(path, path_span, start.join(path_span));
+                }
                 // transition function
                 (S(T::Ident(ident), start), S(T::LPar, _)) => {
                     if let Some(tf) = self.parse_transition_function(ident, start) {
@@ -383,6 +904,10 @@ impl<'a, 'b> Parser<'a, 'b> {
                     let span = start.join(item.1);
                     break Some(S(TopLevel::Item(name, item), span));
                 }
+                // table
+                (S(T::Ident(ident), start), S(T::Or, _)) => {
+                    break Some(self.parse_table(ident, start));
+                }
                 // production rule
                 (
                     sym @ S(T::Ident(_) | T::Tilde | T::String(_, _, _), _),
@@ -395,22 +920,27 @@ impl<'a, 'b> Parser<'a, 'b> {
                 }
 
                 (S(T::Ident(_), _), S(tok, span)) => {
-                    self.ctx.emit_error(
-                        format!(
-                            "unexpected {:#} expected {:} | {:}",
-                            tok,
-                            T::Eq,
-                            T::LSmallArrow
-                        ),
-                        span,
-                    );
+                    self.ctx
+                        .emit_error(
+                            format!(
+                                "unexpected {:#} expected {:} | {:} | {:}",
+                                tok,
+                                T::Eq,
+                                T::LSmallArrow,
+                                T::Or
+                            ),
+                            span,
+                        )
+                        .code(Code::UNEXPECTED_TOKEN);
                     while !matches!(self.next_token().0, T::LineEnd) {}
                 }
                 _ => {
-                    self.ctx.emit_error(
-                        format!("unexpected {:#} expected {:}", next.0, T::Ident("")),
-                        next.1,
-                    );
+                    self.ctx
+                        .emit_error(
+                            format!("unexpected {:#} expected {:}", next.0, T::Ident("")),
+                            next.1,
+                        )
+                        .code(Code::UNEXPECTED_TOKEN);
                     while !matches!(self.next_token().0, T::LineEnd) {}
                 }
             }
@@ -419,3 +949,284 @@ impl<'a, 'b> Parser<'a, 'b> {
         result
     }
 }
+
+/// Parses the contents of a regex string `r"..."`.
+///
+/// Operators from lowest to highest precedence: union `|`, intersection `&`,
+/// concatenation, complement `!` and the postfix `*`, `+` and `?`. `.` matches
+/// any letter, `[a-z]` / `[^a-z]` match letter classes and `~` (or any epsilon)
+/// matches the empty string.
+struct RegexParser<'a, 'b> {
+    src: &'a str,
+    base: usize,
+    file: FileId,
+    position: usize,
+    ctx: &'b mut Context<'a>,
+}
+
+impl<'a, 'b> RegexParser<'a, 'b> {
+    fn parse(mut self) -> S<Regex<'a>> {
+        let mut parts = vec![self.parse_union()];
+        while let Some(c) = self.peek() {
+            let start = self.position;
+            self.consume();
+            self.ctx
+                .emit_error(format!("unmatched '{c}' in regex"), self.span(start))
+                .code(Code::REGEX_SYNTAX);
+            parts.push(self.parse_union());
+        }
+        self.collect(parts, 0, Regex::Concat)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src.get(self.position..)?.chars().next()
+    }
+
+    fn consume(&mut self) -> Option<char> {
+        let next = self.peek()?;
+        self.position += next.len_utf8();
+        Some(next)
+    }
+
+    fn span(&self, start: usize) -> Span {
+        Span(self.base + start, self.base + self.position, self.file)
+    }
+
+    fn collect(
+        &self,
+        mut parts: Vec<S<Regex<'a>>>,
+        start: usize,
+        group: fn(Vec<S<Regex<'a>>>) -> Regex<'a>,
+    ) -> S<Regex<'a>> {
+        if parts.len() == 1 {
+            parts.pop().unwrap()
+        } else {
+            S(group(parts), self.span(start))
+        }
+    }
+
+    fn parse_union(&mut self) -> S<Regex<'a>> {
+        let start = self.position;
+        let mut parts = vec![self.parse_intersection()];
+        while self.peek() == Some('|') {
+            self.consume();
+            parts.push(self.parse_intersection());
+        }
+        self.collect(parts, start, Regex::Union)
+    }
+
+    fn parse_intersection(&mut self) -> S<Regex<'a>> {
+        let start = self.position;
+        let mut parts = vec![self.parse_concat()];
+        while self.peek() == Some('&') {
+            self.consume();
+            parts.push(self.parse_concat());
+        }
+        self.collect(parts, start, Regex::Intersection)
+    }
+
+    fn parse_concat(&mut self) -> S<Regex<'a>> {
+        let start = self.position;
+        let mut parts = Vec::new();
+        while let Some(c) = self.peek()
+            && !matches!(c, ')' | '|' | '&')
+        {
+            parts.push(self.parse_complement());
+        }
+        if parts.is_empty() {
+            return S(Regex::Concat(parts), self.span(start));
+        }
+        self.collect(parts, start, Regex::Concat)
+    }
+
+    fn parse_complement(&mut self) -> S<Regex<'a>> {
+        let start = self.position;
+        if self.peek() == Some('!') {
+            self.consume();
+            let inner = self.parse_complement();
+            return S(Regex::Complement(Box::new(inner)), self.span(start));
+        }
+        self.parse_postfix()
+    }
+
+    fn parse_postfix(&mut self) -> S<Regex<'a>> {
+        let start = self.position;
+        let mut atom = self.parse_atom();
+        loop {
+            atom = match self.peek() {
+                Some('*') => {
+                    self.consume();
+                    S(Regex::Star(Box::new(atom)), self.span(start))
+                }
+                Some('+') => {
+                    self.consume();
+                    S(Regex::Plus(Box::new(atom)), self.span(start))
+                }
+                Some('?') => {
+                    self.consume();
+                    let empty = S(Regex::Concat(Vec::new()), self.span(self.position));
+                    S(Regex::Union(vec![atom, empty]), self.span(start))
+                }
+                _ => break atom,
+            }
+        }
+    }
+
+    fn parse_atom(&mut self) -> S<Regex<'a>> {
+        let start = self.position;
+        let Some(c) = self.consume() else {
+            return S(Regex::Concat(Vec::new()), self.span(start));
+        };
+        let regex = match c {
+            '(' => {
+                let inner = self.parse_union();
+                if self.peek() == Some(')') {
+                    self.consume();
+                } else {
+                    let open = Span(self.base + start, self.base + start + 1, self.file);
+                    let close = self.span(self.position);
+                    self.ctx
+                        .emit_error("unclosed group in regex", open)
+                        .code(Code::REGEX_SYNTAX)
+                        .secondary(close, "add a matching ')'")
+                        .suggest(close, ")", Applicability::MaybeIncorrect);
+                }
+                inner.0
+            }
+            '[' => self.parse_class(start),
+            '.' => Regex::Match {
+                complement: true,
+                patterns: Vec::new(),
+            },
+            '*' | '+' | '?' => {
+                let span = self.span(start);
+                self.ctx
+                    .emit_error(format!("nothing to repeat before '{c}'"), span)
+                    .code(Code::REGEX_SYNTAX)
+                    .help(format!("escape it to match a literal: \\{c}"))
+                    .suggest(
+                        Span(span.0, span.0, span.2),
+                        "\\",
+                        Applicability::MaybeIncorrect,
+                    );
+                Regex::Concat(Vec::new())
+            }
+            '\\' => match self.parse_escape(start) {
+                Some(terminal) => Regex::Terminal(terminal),
+                None => Regex::Concat(Vec::new()),
+            },
+            c if matches!(&*c.to_string(), epsilon!(pat)) => Regex::Concat(Vec::new()),
+            _ => Regex::Terminal(&self.src[start..self.position]),
+        };
+        S(regex, self.span(start))
+    }
+
+    fn parse_class(&mut self, start: usize) -> Regex<'a> {
+        let complement = self.peek() == Some('^');
+        if complement {
+            self.consume();
+        }
+
+        let mut patterns = Vec::new();
+        loop {
+            let from_start = self.position;
+            let from = match self.consume() {
+                Some(']') => break,
+                Some('\\') => self.parse_escape(from_start).and_then(|t| t.chars().next()),
+                Some(c) => Some(c),
+                None => {
+                    let open = Span(self.base + start, self.base + start + 1, self.file);
+                    let close = self.span(self.position);
+                    self.ctx
+                        .emit_error("unclosed class in regex", open)
+                        .code(Code::REGEX_SYNTAX)
+                        .secondary(close, "add a matching ']'")
+                        .suggest(close, "]", Applicability::MaybeIncorrect);
+                    break;
+                }
+            };
+
+            let mut to = from;
+            if self.peek() == Some('-') && !self.src[self.position + 1..].starts_with(']') {
+                self.consume();
+                let to_start = self.position;
+                to = match self.consume() {
+                    Some('\\') => self.parse_escape(to_start).and_then(|t| t.chars().next()),
+                    Some(c) => Some(c),
+                    None => None,
+                };
+            }
+
+            match (from, to) {
+                (Some(from), Some(to)) if from <= to => patterns.push(from..=to),
+                (Some(from), Some(to)) => {
+                    let span = self.span(from_start);
+                    let entry = self
+                        .ctx
+                        .emit_error("regex class range out of order", span)
+                        .code(Code::REGEX_SYNTAX)
+                        .help(format!("did you mean '{to}-{from}'"));
+                    // ranges written with escapes would need them rewritten
+                    if span.1 - span.0 == from.len_utf8() + 1 + to.len_utf8() {
+                        entry.suggest(span, format!("{to}-{from}"), Applicability::MaybeIncorrect);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Regex::Match {
+            complement,
+            patterns,
+        }
+    }
+
+    /// Parses the remainder of an escape sequence, `start` being the position of the `\\`.
+    fn parse_escape(&mut self, start: usize) -> Option<&'a str> {
+        let escaped = self.position;
+        match self.consume() {
+            Some('n') => Some("\n"),
+            Some('t') => Some("\t"),
+            Some('r') => Some("\r"),
+            Some('0') => Some("\0"),
+            Some('u') => {
+                let value = self
+                    .src
+                    .get(self.position..)
+                    .and_then(|s| s.strip_prefix('{'))
+                    .and_then(|s| Some(&s[..s.find('}')?]));
+                let Some(value) = value else {
+                    self.ctx
+                        .emit_error(
+                            "invalid unicode escape in regex, expected \\u{...}",
+                            self.span(start),
+                        )
+                        .code(Code::REGEX_SYNTAX);
+                    return None;
+                };
+                self.position += value.len() + 2;
+                match u32::from_str_radix(value, 16).ok().and_then(char::from_u32) {
+                    Some(c) => Some(self.ctx.intern(c.encode_utf8(&mut [0; 4]))),
+                    None => {
+                        self.ctx
+                            .emit_error("invalid unicode scalar in regex escape", self.span(start))
+                            .code(Code::REGEX_SYNTAX);
+                        None
+                    }
+                }
+            }
+            Some(c) if c.is_alphanumeric() => {
+                self.ctx
+                    .emit_error(format!("unknown escape '\\{c}' in regex"), self.span(start))
+                    .code(Code::REGEX_SYNTAX);
+                None
+            }
+            Some(_) => Some(&self.src[escaped..self.position]),
+            None => {
+                self.ctx
+                    .emit_error("unterminated escape in regex", self.span(start))
+                    .code(Code::REGEX_SYNTAX);
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Source files and the `include "path"` directives between them.
+//!
+//! Includes are resolved up front by [`SourceMap::load`] so every file lives
+//! as long as the [`Context`](super::Context) compiling them, the parser then
+//! only has to switch between already loaded files.
+
+use std::collections::HashMap;
+
+use crate::loader::{
+    FileId, Interner, Span, Spanned,
+    lexer::{Lexer, StringKind, Token},
+};
+
+/// Reads the files named by `include` directives.
+pub trait FileProvider {
+    fn read(&mut self, path: &str) -> Result<String, String>;
+
+    /// The path an `include` of `path` refers to from within the file at `from`.
+    fn resolve(&self, from: &str, path: &str) -> String {
+        resolve_relative(from, path)
+    }
+}
+
+/// Reads includes from the file system.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FsProvider;
+
+impl FileProvider for FsProvider {
+    fn read(&mut self, path: &str) -> Result<String, String> {
+        std::fs::read_to_string(path).map_err(|err| err.to_string())
+    }
+}
+
+/// Serves includes from memory, for when there is no file system (the web).
+#[derive(Clone, Debug, Default)]
+pub struct MemoryProvider(pub HashMap<String, String>);
+
+impl FileProvider for MemoryProvider {
+    fn read(&mut self, path: &str) -> Result<String, String> {
+        self.0
+            .get(path)
+            .cloned()
+            .ok_or_else(|| "no such file".into())
+    }
+}
+
+/// Joins `path` onto the directory of `from`, resolving `.` and `..`.
+pub fn resolve_relative(from: &str, path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    if !path.starts_with('/') {
+        parts.extend(from.split('/'));
+        parts.pop();
+    }
+    for part in path.split('/') {
+        match part {
+            "." => {}
+            ".." if parts
+                .last()
+                .is_some_and(|last| !last.is_empty() && *last != "..") =>
+            {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+#[derive(Clone, Debug)]
+pub struct SourceFile<'a> {
+    pub name: &'a str,
+    pub src: &'a str,
+    /// The path of the `include` this file was first loaded through, `None`
+    /// for the root file.
+    pub included_at: Option<Span>,
+}
+
+impl<'a> SourceFile<'a> {
+    pub fn eof(&self, file: FileId) -> Span {
+        Span(self.src.len(), self.src.len(), file)
+    }
+}
+
+/// Owns a root file and everything it transitively includes.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    files: Vec<(String, String, Option<Span>)>,
+    includes: HashMap<Span, Result<FileId, String>>,
+    interner: Interner,
+}
+
+impl SourceMap {
+    /// Loads the root file `name` with contents `src` and every file it
+    /// includes. A file is loaded once no matter how often it's included,
+    /// failures are kept and reported where the file is included.
+    pub fn load(
+        name: impl Into<String>,
+        src: impl Into<String>,
+        provider: &mut impl FileProvider,
+    ) -> Self {
+        let mut map = SourceMap {
+            files: vec![(name.into(), src.into(), None)],
+            includes: HashMap::new(),
+            interner: Interner::default(),
+        };
+
+        let mut next = 0;
+        while next < map.files.len() {
+            let file = FileId(next as u32);
+            let (from, src, _) = &map.files[next];
+            let found = includes(src, file)
+                .map(|Spanned(path, span)| (provider.resolve(from, path), span))
+                .collect::<Vec<_>>();
+
+            for (path, span) in found {
+                let loaded = map.files.iter().position(|(name, _, _)| *name == path);
+                let result = match loaded {
+                    Some(index) => Ok(FileId(index as u32)),
+                    None => provider.read(&path).map(|src| {
+                        map.files.push((path, src, Some(span)));
+                        FileId(map.files.len() as u32 - 1)
+                    }),
+                };
+                map.includes.insert(span, result);
+            }
+            next += 1;
+        }
+        map
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = SourceFile<'_>> {
+        self.files
+            .iter()
+            .map(|(name, src, included_at)| SourceFile {
+                name,
+                src,
+                included_at: *included_at,
+            })
+    }
+
+    pub fn includes(&self) -> impl Iterator<Item = (Span, Result<FileId, &str>)> {
+        self.includes
+            .iter()
+            .map(|(span, result)| (*span, result.as_ref().copied().map_err(String::as_str)))
+    }
+
+    /// Where the names compiled from these sources which aren't in them live.
+    pub fn interner(&self) -> &Interner {
+        &self.interner
+    }
+}
+
+/// Whether `ident` starts an include directive.
+pub fn is_include(ident: &str) -> bool {
+    matches!(ident, "include" | "import")
+}
+
+/// The paths of the `include "path"` lines of `src`, spanning the string.
+fn includes(src: &str, file: FileId) -> impl Iterator<Item = Spanned<&str>> {
+    let mut tokens = Lexer::with_file(src, file)
+        .filter(|token| !matches!(token.0, Ok(Token::Comment(_))))
+        .peekable();
+    let mut line_start = true;
+    std::iter::from_fn(move || {
+        loop {
+            let Spanned(token, _) = tokens.next()?;
+            let was_line_start = std::mem::replace(&mut line_start, false);
+            match token {
+                Ok(Token::LineEnd) => line_start = true,
+                Ok(Token::Ident(ident)) if was_line_start && is_include(ident) => {
+                    if let Some(Spanned(Ok(Token::String(path, StringKind::Regular, _)), span)) =
+                        tokens.peek()
+                    {
+                        return Some(Spanned(*path, *span));
+                    }
+                }
+                _ => {}
+            }
+        }
+    })
+}
+
+#[test]
+fn include() {
+    use crate::loader::{Context, Machine, parse_universal};
+
+    let files = [
+        ("dir/alphabet", "E = {a, b}\ninclude \"../states\""),
+        ("states", "Q = {q0, q1}\nF = {q1}"),
+        ("dir/cycle", "include \"cycle\""),
+    ];
+    let mut provider = MemoryProvider(
+        files
+            .into_iter()
+            .map(|(path, src)| (path.to_string(), src.to_string()))
+            .collect(),
+    );
+
+    let src = "type = DFA\ninclude \"dir/alphabet\"\ninclude \"states\"\nd(q0, a) = q1";
+    let sources = SourceMap::load("main", src, &mut provider);
+    let mut ctx = Context::with_sources(&sources);
+    let Some(Machine::Fa(fa)) = parse_universal(&mut ctx) else {
+        panic!("failed to compile");
+    };
+    assert_eq!(fa.states.len(), 2);
+    let definition = fa.alphabet.values().next().unwrap().definition;
+    assert_eq!(ctx.file(definition.2).name, "dir/alphabet");
+    assert_eq!(ctx.root_span(definition).2, FileId(0));
+
+    for src in ["include \"dir/cycle\"", "include \"nothing\""] {
+        let sources = SourceMap::load("main", src, &mut provider);
+        let mut ctx = Context::with_sources(&sources);
+        assert!(parse_universal(&mut ctx).is_none());
+        assert!(
+            ctx.logs().entries()[0]
+                .message
+                .starts_with(if src.contains("cycle") {
+                    "include cycle"
+                } else {
+                    "cannot include"
+                })
+        );
+    }
+}
@@ -0,0 +1,55 @@
+//! Finding the name a misspelled one was most likely meant to be.
+
+/// The number of single character insertions, deletions and substitutions
+/// needed to turn `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a != b);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The candidate closest to `name` if one is close enough to be a likely typo.
+///
+/// Candidates differing from `name` only in case always match, others may
+/// differ in at most a third of their characters. Ties go to the
+/// alphabetically first candidate so the result doesn't depend on the order of
+/// a `HashMap`.
+pub fn closest<'b>(name: &str, candidates: impl IntoIterator<Item = &'b str>) -> Option<&'b str> {
+    let length = name.chars().count();
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .filter_map(|candidate| {
+            if candidate.to_lowercase() == name.to_lowercase() {
+                return Some((0, candidate));
+            }
+            let distance = edit_distance(name, candidate);
+            let limit = length.max(candidate.chars().count()) / 3;
+            (distance <= limit).then_some((distance, candidate))
+        })
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+#[test]
+fn closest_names() {
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("", "abc"), 3);
+    assert_eq!(edit_distance("q_a", "qa"), 1);
+
+    assert_eq!(closest("qa", ["q_a", "qb", "q0"]), Some("q_a"));
+    assert_eq!(closest("Sigma", ["E", "S", "sigma"]), Some("sigma"));
+    assert_eq!(closest("q", ["Q", "F"]), Some("Q"));
+    assert_eq!(closest("x", ["Q", "F"]), None);
+    assert_eq!(closest("q0", ["q1"]), None);
+    assert_eq!(closest("state", ["stata", "stats"]), Some("stata"));
+}
@@ -1,11 +1,13 @@
-use std::collections::HashMap;
+//! Machines lowered into dense tables indexed by interned states and symbols,
+//! for simulating without hashing names on every step.
+
+use std::{collections::HashMap, hash::Hash};
+
+use crate::loader::Span;
 
-pub mod dfa;
-pub mod dpda;
 pub mod nfa;
 pub mod npda;
 pub mod ntm;
-pub mod tm;
 
 pub trait Get<Idx> {
     type Output;
@@ -61,29 +63,77 @@ macro_rules! index {
     };
 }
 
-#[derive(Clone, Debug, Copy, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct State(u16);
 
-#[derive(Clone, Debug, Copy, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Symbol(u16);
 
+/// A machine has more states or symbols than fit in an index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TooLarge;
+
+impl std::fmt::Display for TooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "more than {} states or symbols", u16::MAX)
+    }
+}
+
+/// Numbers the names of `map` in the order they were defined in, giving the
+/// name table and the index of every name.
+fn intern<'a, K: Copy + Eq + Hash, V>(
+    map: &HashMap<K, V>,
+    name: impl Fn(K) -> &'a str,
+    definition: impl Fn(&V) -> Span,
+) -> Result<(Vec<String>, HashMap<K, u16>), TooLarge> {
+    let mut sorted: Vec<_> = map.iter().collect();
+    sorted.sort_by_key(|&(&key, value)| {
+        let span = definition(value);
+        (span.2.0, span.0, name(key))
+    });
+    if sorted.len() > u16::MAX as usize {
+        return Err(TooLarge);
+    }
+    let names = sorted
+        .iter()
+        .map(|&(&key, _)| name(key).to_string())
+        .collect();
+    let indices = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, &(&key, _))| (key, i as u16))
+        .collect();
+    Ok((names, indices))
+}
+
 #[derive(Clone, Debug)]
 pub struct StateMap<T>(Vec<T>);
 
 index!(StateMap, self, self.0, index.0 as usize, index = State);
 
-impl<T> StateMap<T>{
-    pub fn entries(&self) -> impl Iterator<Item = (State, &T)>{
-        self.0.iter().enumerate().map(|(i, v)|(State(i as u16), v))
+impl<T> StateMap<T> {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (State, &T)> {
+        self.0.iter().enumerate().map(|(i, v)| (State(i as u16), v))
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct SymbolMap<T>(Vec<T>);
 
-impl<T> SymbolMap<T>{
-    pub fn entries(&self) -> impl Iterator<Item = (Symbol, &T)>{
-        self.0.iter().enumerate().map(|(i, v)|(Symbol(i as u16), v))
+impl<T> SymbolMap<T> {
+    pub fn entries(&self) -> impl Iterator<Item = (Symbol, &T)> {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (Symbol(i as u16), v))
     }
 }
 
@@ -95,9 +145,18 @@ pub struct StateSymbolMap<T> {
     max_state: u16,
 }
 
-impl<T> StateSymbolMap<T>{
-    pub fn entries(&self) -> impl Iterator<Item = ((State, Symbol), &T)>{
-        self.map.iter().enumerate().map(|(i, v)|{
+impl<T: Clone> StateSymbolMap<T> {
+    fn new(states: usize, symbols: usize, value: T) -> Self {
+        Self {
+            map: vec![value; states * symbols],
+            max_state: states as u16,
+        }
+    }
+}
+
+impl<T> StateSymbolMap<T> {
+    pub fn entries(&self) -> impl Iterator<Item = ((State, Symbol), &T)> {
+        self.map.iter().enumerate().map(|(i, v)| {
             let state = State((i % self.max_state as usize) as u16);
             let symbol = Symbol((i / self.max_state as usize) as u16);
             ((state, symbol), v)
@@ -123,9 +182,9 @@ index!(
 #[derive(Clone, Debug, Default)]
 pub struct CharMap<T>(HashMap<char, T>);
 
-impl<T> CharMap<T>{
-    pub fn entries(&self) -> impl Iterator<Item = (char, &T)>{
-        self.0.iter().map(|(k, v)|(*k, v))
+impl<T> CharMap<T> {
+    pub fn entries(&self) -> impl Iterator<Item = (char, &T)> {
+        self.0.iter().map(|(k, v)| (*k, v))
     }
 }
 
@@ -141,9 +200,9 @@ index!(
 #[derive(Clone, Debug, Default)]
 pub struct CharEpsilonMap<T>(HashMap<Option<char>, T>);
 
-impl<T> CharEpsilonMap<T>{
-    pub fn entries(&self) -> impl Iterator<Item = (Option<char>, &T)>{
-        self.0.iter().map(|(k, v)|(*k, v))
+impl<T> CharEpsilonMap<T> {
+    pub fn entries(&self) -> impl Iterator<Item = (Option<char>, &T)> {
+        self.0.iter().map(|(k, v)| (*k, v))
     }
 }
 
@@ -157,3 +216,41 @@ index!(
 );
 index!(CharEpsilonMap, self, self.0, &char, char = Option<char>, self.0.entry(char).or_default());
 
+#[test]
+fn lowering() {
+    use crate::loader::{Context, Interner, Machine, parse_universal};
+
+    let src = "type = NPDA
+Q = {q0, q1, q2}
+E = {a, b}
+T = {z0, A}
+F = {q2}
+accept = F
+q0 = q0
+z0 = z0
+d(q0, a, z0) = (q0, [A z0])
+d(q0, a, A) = (q0, [A A])
+d(q0, ~, z0) = (q2, z0)
+d(q0, b, A) = (q1, ~)
+d(q1, b, A) = (q1, ~)
+d(q1, ~, z0) = (q2, z0)";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    let Some(Machine::Pda(pda)) = parse_universal(&mut ctx) else {
+        panic!("expected a push down automaton");
+    };
+    let npda = npda::Npda::lower(&pda).unwrap();
+
+    // indices follow the definitions and map back to the names
+    let states: Vec<_> = npda.states().map(|(_, name)| name).collect();
+    assert_eq!(states, ["q0", "q1", "q2"]);
+    assert_eq!(npda.get_state("q1"), Some(State(1)));
+    assert_eq!(npda.get_symbol_name(npda.initial_stack()), Some("z0"));
+    assert_eq!(npda.final_states().unwrap().collect::<Vec<_>>(), [State(2)]);
+
+    // the top of the stack goes last
+    let pushes = &npda.transitions()[(State(0), npda.initial_stack())];
+    let to = &pushes.get(Some('a')).unwrap()[0];
+    assert_eq!(to.stack(), [Symbol(0), Symbol(1)]);
+    assert_eq!(&src[to.transition().0..to.transition().1], "(q0, [A z0])");
+}
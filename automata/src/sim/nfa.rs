@@ -1 +1,334 @@
+use std::collections::HashSet;
 
+use super::*;
+use crate::automatan::fa;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct To {
+    state: State,
+    transition: Span,
+    function: Span,
+}
+
+impl To {
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Where the transition is written, the state it goes to.
+    pub fn transition(&self) -> Span {
+        self.transition
+    }
+
+    /// Where the transition function it belongs to is written.
+    pub fn function(&self) -> Span {
+        self.function
+    }
+}
+
+/// A finite automaton, deterministic or not, lowered from [`fa::Fa`].
+///
+/// Deterministic automata are simulated by [`Simulator`] too, their frontier
+/// never holds more than one state.
+#[derive(Clone, Debug)]
+pub struct Nfa {
+    initial_state: State,
+    state_names: StateMap<String>,
+    alphabet: HashSet<char>,
+
+    final_states: StateMap<bool>,
+    transitions: StateMap<CharEpsilonMap<Vec<To>>>,
+}
+
+impl Nfa {
+    /// Lowers a compiled machine, numbering its states in the order they were
+    /// defined in.
+    pub fn lower(fa: &fa::Fa<'_>) -> Result<Self, TooLarge> {
+        let (state_names, states) = intern(&fa.states, |state| state.0, |info| info.definition)?;
+        let state = |state| State(states[&state]);
+
+        let mut final_states = StateMap(vec![false; states.len()]);
+        for &final_state in fa.final_states.keys() {
+            final_states[state(final_state)] = true;
+        }
+
+        let mut transitions = StateMap(vec![CharEpsilonMap::<Vec<To>>::default(); states.len()]);
+        for (from, to) in &fa.transitions {
+            let to = to.iter().map(|to| To {
+                state: state(to.state),
+                transition: to.transition,
+                function: to.function,
+            });
+            let letter = from
+                .letter
+                .map(|letter| letter.0.chars().next().unwrap_or_default());
+            transitions[state(from.state)]
+                .get_mut_or_insert_default(letter)
+                .extend(to);
+        }
+
+        Ok(Nfa {
+            initial_state: state(fa.initial_state),
+            state_names: StateMap(state_names),
+            alphabet: fa
+                .alphabet
+                .keys()
+                .map(|letter| letter.0.chars().next().unwrap_or_default())
+                .collect(),
+            final_states,
+            transitions,
+        })
+    }
+
+    pub fn get_state_name(&self, state: State) -> Option<&str> {
+        self.state_names.get(state).map(String::as_str)
+    }
+
+    pub fn get_state(&self, name: &str) -> Option<State> {
+        self.states().find(|&(_, n)| n == name).map(|(s, _)| s)
+    }
+
+    pub fn initial_state(&self) -> State {
+        self.initial_state
+    }
+
+    pub fn is_final(&self, state: State) -> bool {
+        self.final_states.get(state).copied().unwrap_or_default()
+    }
+
+    pub fn final_states(&self) -> impl Iterator<Item = State> {
+        self.final_states
+            .entries()
+            .filter(|&(_, f)| *f)
+            .map(|(s, _)| s)
+    }
+
+    pub fn states(&self) -> impl Iterator<Item = (State, &str)> {
+        self.state_names.entries().map(|s| (s.0, s.1.as_str()))
+    }
+
+    pub fn alphabet(&self) -> &HashSet<char> {
+        &self.alphabet
+    }
+
+    /// The transitions from `state` on `letter`, or on epsilon for `None`.
+    pub fn transitions(&self, state: State, letter: Option<char>) -> &[To] {
+        self.transitions
+            .get(state)
+            .and_then(|t| t.get(letter))
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+/// A transition taken on the way to a state.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Step {
+    pub from: State,
+    /// The letter read, `None` for epsilon moves.
+    pub letter: Option<char>,
+    pub to: State,
+    /// Where the transition is written, to highlight it.
+    pub transition: Span,
+    pub function: Span,
+}
+
+/// A state the automaton may be in, with how it got there.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NfaState {
+    pub state: State,
+    /// The byte offset into the input of the next letter.
+    pub position: usize,
+    pub path: Vec<Step>,
+}
+
+pub struct Simulator {
+    input: String,
+    machine: Nfa,
+    /// The states the automaton is in, each once and closed over epsilon moves.
+    running: Vec<NfaState>,
+    position: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SimulatorResult {
+    Pending,
+    Reject,
+    Accept(NfaState),
+}
+
+impl Simulator {
+    pub fn begin(input: impl Into<String>, machine: Nfa) -> Self {
+        let mut simulator = Self {
+            input: input.into(),
+            running: vec![NfaState {
+                state: machine.initial_state,
+                position: 0,
+                path: Vec::new(),
+            }],
+            machine,
+            position: 0,
+        };
+        simulator.close();
+        simulator
+    }
+
+    pub fn machine(&self) -> &Nfa {
+        &self.machine
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    /// The byte offset into the input of the next letter.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The states the automaton is in after reading up to [`Self::position`].
+    pub fn frontier(&self) -> &[NfaState] {
+        &self.running
+    }
+
+    /// Adds the states reachable through epsilon moves to the frontier.
+    fn close(&mut self) {
+        let mut seen: HashSet<_> = self.running.iter().map(|s| s.state).collect();
+        let mut i = 0;
+        while let Some(from) = self.running.get(i) {
+            let mut reached = Vec::new();
+            for to in self.machine.transitions(from.state, None) {
+                if seen.insert(to.state) {
+                    reached.push(from.take(None, to));
+                }
+            }
+            self.running.extend(reached);
+            i += 1;
+        }
+    }
+
+    /// Reads the next letter, or decides whether the input is accepted once
+    /// all of it is read.
+    pub fn step(&mut self) -> SimulatorResult {
+        let Some(letter) = self.input[self.position..].chars().next() else {
+            return match self.running.iter().find(|s| self.machine.is_final(s.state)) {
+                Some(accepted) => SimulatorResult::Accept(accepted.clone()),
+                None => SimulatorResult::Reject,
+            };
+        };
+
+        self.position += letter.len_utf8();
+        let mut seen = HashSet::new();
+        let mut new = Vec::new();
+        for from in &self.running {
+            for to in self.machine.transitions(from.state, Some(letter)) {
+                if seen.insert(to.state) {
+                    let mut next = from.take(Some(letter), to);
+                    next.position = self.position;
+                    new.push(next);
+                }
+            }
+        }
+        self.running = new;
+        self.close();
+
+        if self.running.is_empty() {
+            SimulatorResult::Reject
+        } else {
+            SimulatorResult::Pending
+        }
+    }
+
+    /// Steps until the input is accepted or rejected.
+    pub fn run(&mut self) -> SimulatorResult {
+        loop {
+            match self.step() {
+                SimulatorResult::Pending => {}
+                result => return result,
+            }
+        }
+    }
+}
+
+impl NfaState {
+    fn take(&self, letter: Option<char>, to: &To) -> NfaState {
+        let mut path = self.path.clone();
+        path.push(Step {
+            from: self.state,
+            letter,
+            to: to.state,
+            transition: to.transition,
+            function: to.function,
+        });
+        NfaState {
+            state: to.state,
+            position: self.position,
+            path,
+        }
+    }
+}
+
+#[test]
+fn simulate() {
+    use crate::loader::{Context, Interner, Machine, parse_universal};
+
+    // strings over {a, b} ending in ab
+    let src = "type = NFA
+Q = {s, q0, q1, q2}
+E = {a, b}
+F = {q2}
+q0 = s
+d(s, ~) = q0
+d(q0, a) = {q0, q1}
+d(q0, b) = q0
+d(q1, b) = q2";
+    let interner = Interner::default();
+    let mut ctx = Context::new(src, &interner);
+    let Some(Machine::Fa(fa)) = parse_universal(&mut ctx) else {
+        panic!("expected a finite automaton");
+    };
+    let nfa = Nfa::lower(&fa).unwrap();
+    let run = |input: &str| Simulator::begin(input, nfa.clone()).run();
+
+    assert_eq!(run(""), SimulatorResult::Reject);
+    assert_eq!(run("aba"), SimulatorResult::Reject);
+    assert_eq!(run("ac"), SimulatorResult::Reject);
+    let SimulatorResult::Accept(accepted) = run("bab") else {
+        panic!("expected bab to be accepted");
+    };
+    let path: Vec<_> = accepted
+        .path
+        .iter()
+        .map(|step| {
+            let from = nfa.get_state_name(step.from).unwrap();
+            (
+                from,
+                step.letter,
+                &src[step.transition.0..step.transition.1],
+            )
+        })
+        .collect();
+    assert_eq!(
+        path,
+        [
+            ("s", None, "q0"),
+            ("q0", Some('b'), "q0"),
+            ("q0", Some('a'), "q1"),
+            ("q1", Some('b'), "q2"),
+        ]
+    );
+
+    // the frontier is closed over epsilon moves
+    let mut simulator = Simulator::begin("ab", nfa.clone());
+    let frontier = |simulator: &Simulator| {
+        let mut states: Vec<_> = simulator.frontier().iter().map(|s| s.state).collect();
+        states.sort();
+        states
+    };
+    assert_eq!(frontier(&simulator), [State(0), State(1)]);
+    assert_eq!(simulator.step(), SimulatorResult::Pending);
+    assert_eq!(simulator.position(), 1);
+    assert_eq!(frontier(&simulator), [State(1), State(2)]);
+    assert_eq!(simulator.step(), SimulatorResult::Pending);
+    assert_eq!(frontier(&simulator), [State(1), State(3)]);
+    assert!(matches!(simulator.step(), SimulatorResult::Accept(_)));
+}
@@ -1,22 +1,41 @@
 use std::collections::HashSet;
 
 use super::*;
+use crate::automatan::{Letter, pda};
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub struct To(State, Vec<Symbol>);
+pub struct To {
+    state: State,
+    stack: Vec<Symbol>,
+    transition: Span,
+    function: Span,
+}
+
+impl To {
+    pub fn state(&self) -> State {
+        self.state
+    }
 
-impl To{
-    pub fn state(&self) -> State{
-        self.0
+    pub fn stack(&self) -> &[Symbol] {
+        &self.stack
     }
 
-    pub fn stack(&self) -> &[Symbol]{
-        &self.1
+    /// Where the transition is written, the tuple it goes to.
+    pub fn transition(&self) -> Span {
+        self.transition
+    }
+
+    /// Where the transition function it belongs to is written.
+    pub fn function(&self) -> Span {
+        self.function
     }
 }
 
+/// A push down automaton, deterministic or not, lowered from [`pda::Pda`].
+///
+/// Deterministic push down automata are simulated by [`Simulator`] too, they
+/// only ever have one configuration running.
 #[derive(Clone, Debug)]
-#[allow(unused)]
 pub struct Npda {
     initial_state: State,
     initial_stack: Symbol,
@@ -28,59 +47,95 @@ pub struct Npda {
     transitions: StateSymbolMap<CharEpsilonMap<Vec<To>>>,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
-pub struct StateTransition<T> {
-    pub from: T,
-    pub to: T,
-}
-
 impl Npda {
-    pub fn get_state_name(&self, state: State) -> Option<&str>{
+    pub fn get_state_name(&self, state: State) -> Option<&str> {
         self.state_names.get(state).map(String::as_str)
     }
 
-    pub fn get_symbol_name(&self, symbol: Symbol) -> Option<&str>{
+    pub fn get_symbol_name(&self, symbol: Symbol) -> Option<&str> {
         self.symbol_names.get(symbol).map(String::as_str)
     }
-    
-    pub fn initial_state(&self) -> State{
+
+    pub fn get_state(&self, name: &str) -> Option<State> {
+        self.states().find(|&(_, n)| n == name).map(|(s, _)| s)
+    }
+
+    pub fn get_symbol(&self, name: &str) -> Option<Symbol> {
+        self.symbols().find(|&(_, n)| n == name).map(|(s, _)| s)
+    }
+
+    pub fn initial_state(&self) -> State {
         self.initial_state
     }
 
-    pub fn initial_stack(&self) -> Symbol{
+    pub fn initial_stack(&self) -> Symbol {
         self.initial_stack
     }
 
-    pub fn final_states(&self) -> Option<impl Iterator<Item = State>>{
-        Some(self.final_states.as_ref()?.entries().filter(|&(_, f)| *f).map(|(s, _)| s))
+    pub fn final_states(&self) -> Option<impl Iterator<Item = State>> {
+        Some(
+            self.final_states
+                .as_ref()?
+                .entries()
+                .filter(|&(_, f)| *f)
+                .map(|(s, _)| s),
+        )
     }
-    
-    pub fn states(&self) -> impl Iterator<Item = (State, &str)>{
-        self.state_names.entries().map(|s|(s.0, s.1.as_str()))
+
+    pub fn states(&self) -> impl Iterator<Item = (State, &str)> {
+        self.state_names.entries().map(|s| (s.0, s.1.as_str()))
     }
 
-    pub fn symbols(&self) -> impl Iterator<Item = (Symbol, &str)>{
-        self.symbol_names.entries().map(|s|(s.0, s.1.as_str()))
+    pub fn symbols(&self) -> impl Iterator<Item = (Symbol, &str)> {
+        self.symbol_names.entries().map(|s| (s.0, s.1.as_str()))
     }
 
-    pub fn transitions(&self) -> &StateSymbolMap<CharEpsilonMap<Vec<To>>>{
+    pub fn alphabet(&self) -> &HashSet<char> {
+        &self.alphabet
+    }
+
+    pub fn transitions(&self) -> &StateSymbolMap<CharEpsilonMap<Vec<To>>> {
         &self.transitions
     }
 }
 
+/// A transition taken on the way to a configuration.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Step {
+    pub from: State,
+    /// The letter read, `None` for epsilon moves.
+    pub letter: Option<char>,
+    /// The symbol popped off the top of the stack.
+    pub symbol: Symbol,
+    pub to: State,
+    /// The symbols pushed, the new top last.
+    pub pushed: Vec<Symbol>,
+    /// Where the transition is written, to highlight it.
+    pub transition: Span,
+    pub function: Span,
+}
+
+/// A configuration of the automaton, with how it got there.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct NpdaState {
     pub state: State,
+    /// The stack, its top last.
     pub stack: Vec<Symbol>,
+    /// The byte offset into the input of the next letter.
     pub position: usize,
+    pub path: Vec<Step>,
 }
 
 pub struct Simulator {
     input: String,
     machine: Npda,
     running: Vec<NpdaState>,
+    rejected: Vec<NpdaState>,
+    /// Every configuration reached, so cycles of epsilon moves end.
+    seen: HashSet<(State, Vec<Symbol>, usize)>,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SimulatorResult {
     Pending,
     Reject,
@@ -89,428 +144,232 @@ pub enum SimulatorResult {
 
 impl Simulator {
     pub fn begin(input: impl Into<String>, machine: Npda) -> Self {
+        let initial = NpdaState {
+            state: machine.initial_state,
+            stack: vec![machine.initial_stack],
+            position: 0,
+            path: Vec::new(),
+        };
         Self {
             input: input.into(),
-            running: vec![NpdaState {
-                state: machine.initial_state,
-                stack: vec![machine.initial_stack],
-                position: 0,
-            }],
+            seen: HashSet::from([(initial.state, initial.stack.clone(), 0)]),
+            running: vec![initial],
+            rejected: Vec::new(),
             machine,
         }
     }
 
-    pub fn step(&mut self) -> SimulatorResult {
-        println!("step, ({}) paths", self.running.len());
-        let mut new = Vec::new();
-        for mut npda in self.running.drain(..) {
-            let Some(top) = npda.stack.pop() else {
-                continue;
-            };
-
-            for to in self
-                .machine
-                .transitions
-                .get((npda.state, top))
-                .and_then(|t| t.get(None))
-                .iter()
-                .flat_map(|t| t.iter())
-            {
-                let mut stack = npda.stack.clone();
-                stack.extend_from_slice(&to.1);
-                new.push(NpdaState {
-                    state: to.0,
-                    stack,
-                    position: npda.position,
-                });
-            }
-
-            let Some(next) = self
-                .input
-                .get(npda.position..)
-                .and_then(|c| c.chars().next())
-            else {
-                if let Some(final_states) = &self.machine.final_states
-                    && final_states.get(npda.state).copied().unwrap_or_default()
-                {
-                    return SimulatorResult::Accept(npda.clone());
-                } else if npda.stack == [self.machine.initial_stack] {
-                    return SimulatorResult::Accept(npda.clone());
-                } else {
-                    continue;
-                }
-            };
-
-            for to in self
-                .machine
-                .transitions
-                .get((npda.state, top))
-                .and_then(|t| t.get(Some(next)))
-                .iter()
-                .flat_map(|t| t.iter())
-            {
-                let mut stack = npda.stack.clone();
-                stack.extend_from_slice(&to.1);
-                new.push(NpdaState {
-                    state: to.0,
-                    stack,
-                    position: npda.position + next.len_utf8(),
-                });
-            }
-        }
-        self.running = new;
-        if self.running.is_empty() {
-            SimulatorResult::Reject
-        } else {
-            SimulatorResult::Pending
-        }
+    pub fn machine(&self) -> &Npda {
+        &self.machine
     }
-}
 
-// ------ parser/semantics
+    pub fn input(&self) -> &str {
+        &self.input
+    }
 
-use crate::loader::{
-    Context, DELTA_LOWER, GAMMA_UPPER, SIGMA_UPPER, Spanned,
-    ast::{self, Symbol as Sym},
-};
+    /// The configurations still being explored.
+    pub fn running(&self) -> &[NpdaState] {
+        &self.running
+    }
 
-impl Npda {
-    pub fn load_from_ast<'a>(
-        items: impl Iterator<Item = Spanned<ast::TopLevel<'a>>>,
-        ctx: &mut Context<'a>,
-    ) -> Option<Npda> {
-        let mut initial_state = None;
-        let mut initial_stack = None;
-
-        let mut states = HashMap::new();
-        let mut stack_symbols = HashMap::new();
-        let mut alphabet = HashSet::new();
-        let mut final_states = None;
-
-        let mut transitions_map = HashMap::new();
-
-        for Spanned(element, span) in items {
-            use Spanned as S;
-            use ast::TopLevel as TL;
-            match element {
-                TL::Item(S("Q", _), list) => {
-                    if !states.is_empty() {
-                        ctx.emit_error("states already set", span);
-                    }
-                    let Some(list) = list.expect_set(ctx) else {
-                        continue;
-                    };
-                    for item in list {
-                        let Some(ident) = item.expect_ident(ctx) else {
-                            continue;
-                        };
-                        let state = match states.len().try_into() {
-                            Ok(ok) => State(ok),
-                            Err(_) => {
-                                ctx.emit_error("too many states defined", item.1);
-                                State(0)
-                            }
-                        };
-                        if let Some(old) = states.insert(ident, state) {
-                            ctx.emit_error("state redefined", item.1);
-                            states.insert(ident, old);
-                        }
-                    }
+    /// The configurations which had no transition to take and didn't accept.
+    pub fn rejected(&self) -> &[NpdaState] {
+        &self.rejected
+    }
 
-                    if list.is_empty() {
-                        ctx.emit_error("states cannot be empty", span);
-                    }
-                }
-                TL::Item(S("E" | SIGMA_UPPER | "sigma", _), list) => {
-                    if !alphabet.is_empty() {
-                        ctx.emit_error("alphabet already set", span);
-                    }
-                    let Some(list) = list.expect_set(ctx) else {
-                        continue;
-                    };
-                    for item in list {
-                        let Some(ident) = item.expect_ident(ctx) else {
-                            continue;
-                        };
-
-                        if ident.chars().count() != 1 {
-                            ctx.emit_error("letter cannot be longer than one char", item.1);
-                        }
-
-                        if !alphabet.insert(ident.chars().next().unwrap_or_default()) {
-                            ctx.emit_error("letter redefined", item.1);
-                        }
-                    }
-                    if list.is_empty() {
-                        ctx.emit_error("alphabet cannot be empty", span);
-                    }
-                }
-                TL::Item(S("F", _), list) => {
-                    if final_states.is_some() {
-                        ctx.emit_error("final states already set", span);
-                    }
-                    let mut map = HashSet::new();
-                    let Some(list) = list.expect_set(ctx) else {
-                        continue;
-                    };
-                    for item in list {
-                        let Some(ident) = item.expect_ident(ctx) else {
-                            continue;
-                        };
-                        if let Some(state) = states.get(ident) {
-                            if !map.insert(*state) {
-                                ctx.emit_error("final state redefined", item.1);
-                            }
-                        } else {
-                            ctx.emit_error("final state not defined in set of states", item.1);
-                        }
-                    }
-                    final_states = Some(map);
-                }
-                TL::Item(S("T" | GAMMA_UPPER | "gamma", _), list) => {
-                    if !stack_symbols.is_empty() {
-                        ctx.emit_error("stack symbols already set", span);
-                    }
-                    let Some(list) = list.expect_set(ctx) else {
-                        continue;
-                    };
-                    for item in list {
-                        let Some(ident) = item.expect_ident(ctx) else {
-                            continue;
-                        };
-                        let symbol = match stack_symbols.len().try_into() {
-                            Ok(ok) => Symbol(ok),
-                            Err(_) => {
-                                ctx.emit_error("too many stack symbols defined", item.1);
-                                Symbol(0)
-                            }
-                        };
-                        if let Some(old) = stack_symbols.insert(ident, symbol) {
-                            ctx.emit_error("stack symbol redefined", item.1);
-                            stack_symbols.insert(ident, old);
-                        }
-                    }
+    /// Whether `npda` accepts, by final state when the machine has final
+    /// states and by empty stack otherwise, once all the input is read.
+    fn accepts(&self, npda: &NpdaState) -> bool {
+        if npda.position < self.input.len() {
+            return false;
+        }
+        match &self.machine.final_states {
+            Some(final_states) => final_states.get(npda.state).copied().unwrap_or_default(),
+            None => npda.stack.is_empty(),
+        }
+    }
 
-                    if list.is_empty() {
-                        ctx.emit_error("stack symbols cannot be empty", span);
-                    }
-                }
-                TL::Item(S("I" | "q0", _), S(src, src_d)) => match src {
-                    ast::Item::Symbol(Sym::Ident(ident)) => {
-                        if initial_state.is_some() {
-                            ctx.emit_error("initial state already set", span);
-                        }
-                        if let Some(initial) = states.get(ident) {
-                            initial_state = Some(*initial)
-                        } else {
-                            ctx.emit_error("initial state symbol not defined as a state", src_d);
-                        }
-                    }
-                    _ => ctx.emit_error("expected ident", src_d),
-                },
-                TL::Item(S("S" | "z0", _), S(src, src_d)) => match src {
-                    ast::Item::Symbol(Sym::Ident(ident)) => {
-                        if initial_stack.is_some() {
-                            ctx.emit_error("initial stack already set", span);
-                        }
-                        if let Some(initial) = stack_symbols.get(ident) {
-                            initial_stack = Some(*initial)
-                        } else {
-                            ctx.emit_error(
-                                "initial stack symbol not defined as a stack symbol",
-                                src_d,
-                            );
-                        }
-                    }
-                    _ => ctx.emit_error("expected ident", src_d),
-                },
-                TL::Item(S(name, dest_s), _) => {
-                    ctx.emit_error(format!("unknown item {name:?}, expected 'Q' | 'E' | '{SIGMA_UPPER}' | 'sigma' | 'F' | 'T' | '{GAMMA_UPPER}' | 'gamma' | 'I' | 'q0' | 'S' | 'z0'"), dest_s);
-                }
+    /// Takes every transition of every running configuration once, breadth
+    /// first so the shortest accepting path is found.
+    pub fn step(&mut self) -> SimulatorResult {
+        if let Some(accepted) = self.running.iter().find(|npda| self.accepts(npda)) {
+            return SimulatorResult::Accept(accepted.clone());
+        }
 
-                TL::TransitionFunc(S((S("d" | DELTA_LOWER | "delta", _), tuple), _), list) => {
-                    let list = list.set_weak();
-                    let Some((state, letter, stack_symbol)) =
-                        tuple.as_ref().expect_npda_transition_function(ctx)
-                    else {
-                        continue;
-                    };
-                    let Some(state) = states.get(state.0).copied() else {
-                        ctx.emit_error("transition state not defined as state", state.1);
-                        continue;
-                    };
-                    let Some(stack_symbol) = stack_symbols.get(stack_symbol.0).copied() else {
-                        ctx.emit_error(
-                            "transition stack symbol not defined as stack symbol",
-                            stack_symbol.1,
-                        );
+        let mut new = Vec::new();
+        for npda in std::mem::take(&mut self.running) {
+            let next = self.input[npda.position..].chars().next();
+            let mut moved = false;
+            if let Some(&top) = npda.stack.last() {
+                let transitions = self.machine.transitions.get((npda.state, top));
+                let epsilon = transitions.and_then(|t| t.get(None));
+                let letter = next.and_then(|next| Some((next, transitions?.get(Some(next))?)));
+                let moves = epsilon.into_iter().flatten().map(|to| (None, to));
+                let moves = moves.chain(
+                    letter
+                        .into_iter()
+                        .flat_map(|(next, to)| to.iter().map(move |to| (Some(next), to))),
+                );
+
+                for (letter, to) in moves {
+                    let mut stack = npda.stack[..npda.stack.len() - 1].to_vec();
+                    stack.extend_from_slice(&to.stack);
+                    let position = npda.position + letter.map_or(0, char::len_utf8);
+                    if !self.seen.insert((to.state, stack.clone(), position)) {
                         continue;
-                    };
-
-                    let char = match letter.0 {
-                        Sym::Epsilon => None,
-                        Sym::Ident(val) => {
-                            if let Some(char) = val.chars().next()
-                                && val.chars().count() == 1
-                            {
-                                if !alphabet.contains(&char) {
-                                    ctx.emit_error(
-                                        "transition letter not defined in alphabet",
-                                        letter.1,
-                                    );
-                                }
-                                Some(char)
-                            } else {
-                                ctx.emit_error(
-                                    "transition letter can only be single character",
-                                    letter.1,
-                                );
-                                None
-                            }
-                        }
-                    };
-
-                    for item in list {
-                        let Some((next_state, stack)) = item
-                            .expect_tuple(ctx)
-                            .and_then(|item| item.expect_npda_transition(ctx))
-                        else {
-                            continue;
-                        };
-
-                        let Some(next_state) = states.get(next_state.0).copied() else {
-                            ctx.emit_error("transition state not defined as state", next_state.1);
-                            continue;
-                        };
-
-                        let stack: Vec<_> = stack
-                            .iter()
-                            .rev()
-                            .filter_map(|symbol| {
-                                if matches!(symbol.0, ast::Item::Symbol(Sym::Epsilon)) {
-                                    return None;
-                                }
-                                let ident = symbol.expect_ident(ctx)?;
-
-                                let Some(symbol) = stack_symbols.get(ident).copied() else {
-                                    ctx.emit_error("transition stack symbol not defined", symbol.1);
-                                    return None;
-                                };
-                                Some(symbol)
-                            })
-                            .collect();
-
-                        if !transitions_map
-                            .entry((state, char, stack_symbol))
-                            .or_insert(HashSet::new())
-                            .insert((next_state, stack))
-                        {
-                            ctx.emit_warning("duplicate transition", item.1);
-                        }
                     }
+                    moved = true;
+                    let mut path = npda.path.clone();
+                    path.push(Step {
+                        from: npda.state,
+                        letter,
+                        symbol: top,
+                        to: to.state,
+                        pushed: to.stack.clone(),
+                        transition: to.transition,
+                        function: to.function,
+                    });
+                    new.push(NpdaState {
+                        state: to.state,
+                        stack,
+                        position,
+                        path,
+                    });
                 }
-                TL::TransitionFunc(S((S(name, _), _), dest_s), _) => {
-                    ctx.emit_error(
-                        format!(
-                            "unknown function {name:?}, expected 'd' | 'delta' | '{DELTA_LOWER}'"
-                        ),
-                        dest_s,
-                    );
-                }
-
-                TL::ProductionRule(_, _) => {
-                    ctx.emit_error("unexpected production rule", span);
-                }
-                TL::Table() => ctx.emit_error("unexpected table", span),
+            }
+            if !moved {
+                self.rejected.push(npda);
             }
         }
+        self.running = new;
 
-        if stack_symbols.is_empty() {
-            ctx.emit_error_locless("stack symbols never defined");
-        }
-
-        if alphabet.is_empty() {
-            ctx.emit_error_locless("alphabet never defined");
-        }
-
-        if states.is_empty() {
-            ctx.emit_error_locless("states never defined");
+        if let Some(accepted) = self.running.iter().find(|npda| self.accepts(npda)) {
+            SimulatorResult::Accept(accepted.clone())
+        } else if self.running.is_empty() {
+            SimulatorResult::Reject
+        } else {
+            SimulatorResult::Pending
         }
+    }
 
-        let initial_stack = match initial_stack {
-            Some(some) => some,
-            None => {
-                if let Some(initial) = stack_symbols.get("z0") {
-                    ctx.emit_warning_locless(
-                        "initial stack symbol not defined, defaulting to 'z0'",
-                    );
-                    *initial
-                } else {
-                    ctx.emit_error_locless("initial stack symbol not defined");
-                    Symbol(0)
-                }
+    /// Steps until the input is accepted or rejected, or `max_steps` steps
+    /// were taken as epsilon moves pushing symbols can run forever.
+    pub fn run(&mut self, max_steps: usize) -> SimulatorResult {
+        for _ in 0..max_steps {
+            match self.step() {
+                SimulatorResult::Pending => {}
+                result => return result,
             }
-        };
+        }
+        SimulatorResult::Pending
+    }
+}
 
-        let initial_state = match initial_state {
-            Some(some) => some,
-            None => {
-                if let Some(initial) = states.get("q0") {
-                    ctx.emit_warning_locless("initial state not defined, defaulting to 'q0'");
-                    *initial
-                } else {
-                    ctx.emit_error_locless("initial state not defined");
-                    State(0)
-                }
+impl Npda {
+    /// Lowers a compiled machine, numbering its states and symbols in the
+    /// order they were defined in.
+    pub fn lower(pda: &pda::Pda<'_>) -> Result<Self, TooLarge> {
+        let (state_names, states) = intern(&pda.states, |state| state.0, |info| info.definition)?;
+        let (symbol_names, symbols) =
+            intern(&pda.symbols, |symbol| symbol.0, |info| info.definition)?;
+        let state = |state| State(states[&state]);
+        let symbol = |symbol| Symbol(symbols[&symbol]);
+        let letter = |letter: Letter<'_>| letter.0.chars().next().unwrap_or_default();
+
+        let final_states = pda.final_states.as_ref().map(|final_states| {
+            let mut map = StateMap(vec![false; states.len()]);
+            for &final_state in final_states.keys() {
+                map[state(final_state)] = true;
             }
-        };
-
-        let state_names = StateMap(states.iter().fold(
-            vec![String::new(); states.len()],
-            |mut a, (k, v)| {
-                a[v.0 as usize] = k.to_string();
-                a
-            },
-        ));
-        let symbol_names = SymbolMap(stack_symbols.iter().fold(
-            vec![String::new(); stack_symbols.len()],
-            |mut a, (k, v)| {
-                a[v.0 as usize] = k.to_string();
-                a
-            },
-        ));
-
-        let final_states = final_states.map(|f| {
-            StateMap(f.iter().fold(vec![false; states.len()], |mut a, k| {
-                a[k.0 as usize] = true;
-                a
-            }))
+            map
         });
 
-        let mut transitions: StateSymbolMap<CharEpsilonMap<Vec<To>>> = StateSymbolMap {
-            map: vec![CharEpsilonMap::default(); stack_symbols.len() * states.len()],
-            max_state: states.len() as u16,
-        };
-
-        for ((q, c, s), to) in transitions_map {
-            let from = &mut transitions[(q, s)];
-            for (n, ss) in to {
-                from.get_mut_or_insert_default(c).push(To(n, ss));
-            }
+        let mut transitions: StateSymbolMap<CharEpsilonMap<Vec<To>>> =
+            StateSymbolMap::new(states.len(), symbols.len(), CharEpsilonMap::default());
+        for (from, to) in &pda.transitions {
+            let to = to.iter().map(|to| To {
+                state: state(to.state),
+                stack: to.stack.iter().map(|&s| symbol(s)).collect(),
+                transition: to.transition,
+                function: to.function,
+            });
+            transitions[(state(from.state), symbol(from.symbol))]
+                .get_mut_or_insert_default(from.letter.map(letter))
+                .extend(to);
         }
 
-        if ctx.contains_errors() {
-            return None;
-        }
-
-        Some(Npda {
-            initial_state,
-            initial_stack,
-            state_names,
-            symbol_names,
-            alphabet,
+        Ok(Npda {
+            initial_state: state(pda.initial_state),
+            initial_stack: symbol(pda.initial_stack),
+            state_names: StateMap(state_names),
+            symbol_names: SymbolMap(symbol_names),
+            alphabet: pda.alphabet.keys().map(|&l| letter(l)).collect(),
             final_states,
             transitions,
         })
     }
 }
+
+#[test]
+fn simulate() {
+    use crate::loader::{Context, Interner, Machine, parse_universal};
+
+    let lower = |src: &str| {
+        let interner = Interner::default();
+        let mut ctx = Context::new(src, &interner);
+        let Some(Machine::Pda(pda)) = parse_universal(&mut ctx) else {
+            panic!("expected a push down automaton");
+        };
+        Npda::lower(&pda).unwrap()
+    };
+    // a^n b^n for n >= 1, the same way round for both kinds of acceptance
+    let transitions = "E = {a, b}
+T = {z0, A}
+q0 = q0
+z0 = z0
+d(q0, a, z0) = (q0, [A z0])
+d(q0, a, A) = (q0, [A A])
+d(q0, b, A) = (q1, ~)
+d(q1, b, A) = (q1, ~)";
+    let final_state = lower(&format!(
+        "type = NPDA\naccept = F\nQ = {{q0, q1, q2}}\nF = {{q2}}\n{transitions}
+d(q1, ~, z0) = (q2, z0)"
+    ));
+    let empty_stack = lower(&format!(
+        "type = NPDA\naccept = N\nQ = {{q0, q1}}\n{transitions}\nd(q1, ~, z0) = (q1, ~)"
+    ));
+
+    for npda in [&final_state, &empty_stack] {
+        let run = |input: &str| Simulator::begin(input, npda.clone()).run(100);
+        assert_eq!(run(""), SimulatorResult::Reject);
+        assert_eq!(run("aab"), SimulatorResult::Reject);
+        assert_eq!(run("abb"), SimulatorResult::Reject);
+        let SimulatorResult::Accept(accepted) = run("aabb") else {
+            panic!("expected aabb to be accepted");
+        };
+        assert_eq!(accepted.position, 4);
+        let letters: Vec<_> = accepted.path.iter().map(|step| step.letter).collect();
+        assert_eq!(letters, [Some('a'), Some('a'), Some('b'), Some('b'), None]);
+    }
+
+    // a final state with symbols left on the stack only counts when
+    // accepting by final state
+    let SimulatorResult::Accept(accepted) = Simulator::begin("ab", final_state).run(100) else {
+        panic!("expected ab to be accepted");
+    };
+    assert_eq!(accepted.stack, [Symbol(0)]);
+
+    let mut simulator = Simulator::begin("ba", empty_stack);
+    assert_eq!(simulator.run(100), SimulatorResult::Reject);
+    assert_eq!(simulator.rejected().len(), 1);
+    assert!(simulator.rejected()[0].path.is_empty());
+
+    // a configuration whose only move leads back to itself is rejected too
+    let looping = lower(
+        "type = NPDA\naccept = F\nQ = {q0, q1}\nF = {q1}\nE = {a}\nT = {z0}
+q0 = q0\nz0 = z0\nd(q0, ~, z0) = (q0, z0)",
+    );
+    let mut simulator = Simulator::begin("a", looping);
+    assert_eq!(simulator.run(100), SimulatorResult::Reject);
+    assert_eq!(simulator.rejected().len(), 1);
+}
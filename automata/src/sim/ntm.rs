@@ -1 +1,458 @@
+use std::collections::{HashSet, VecDeque};
 
+use super::*;
+use crate::automatan::tm::{self, Direction};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct To {
+    state: State,
+    symbol: Symbol,
+    direction: Direction,
+    transition: Span,
+    function: Span,
+}
+
+impl To {
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// The symbol written over the one under the head.
+    pub fn symbol(&self) -> Symbol {
+        self.symbol
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Where the transition is written, the tuple it goes to.
+    pub fn transition(&self) -> Span {
+        self.transition
+    }
+
+    /// Where the transition function it belongs to is written.
+    pub fn function(&self) -> Span {
+        self.function
+    }
+}
+
+/// A turing machine, deterministic or not, lowered from [`tm::Tm`].
+///
+/// Deterministic turing machines are simulated by [`Simulator`] too, they only
+/// ever have one configuration running.
+#[derive(Clone, Debug)]
+pub struct Ntm {
+    initial_state: State,
+    blank_symbol: Symbol,
+    state_names: StateMap<String>,
+    symbol_names: SymbolMap<String>,
+
+    final_states: StateMap<bool>,
+    transitions: StateSymbolMap<Vec<To>>,
+}
+
+impl Ntm {
+    /// Lowers a compiled machine, numbering its states and symbols in the
+    /// order they were defined in.
+    pub fn lower(tm: &tm::Tm<'_>) -> Result<Self, TooLarge> {
+        let (state_names, states) = intern(&tm.states, |state| state.0, |info| info.definition)?;
+        let (symbol_names, symbols) =
+            intern(&tm.symbols, |symbol| symbol.0, |info| info.definition)?;
+        let state = |state| State(states[&state]);
+        let symbol = |symbol| Symbol(symbols[&symbol]);
+
+        let mut final_states = StateMap(vec![false; states.len()]);
+        for &final_state in tm.final_states.keys() {
+            final_states[state(final_state)] = true;
+        }
+
+        let mut transitions = StateSymbolMap::new(states.len(), symbols.len(), Vec::new());
+        for (from, to) in &tm.transitions {
+            let to = to.iter().map(|to| To {
+                state: state(to.state),
+                symbol: symbol(to.symbol),
+                direction: to.direction,
+                transition: to.transition,
+                function: to.function,
+            });
+            transitions[(state(from.state), symbol(from.symbol))].extend(to);
+        }
+
+        Ok(Ntm {
+            initial_state: state(tm.initial_state),
+            blank_symbol: symbol(tm.blank_symbol),
+            state_names: StateMap(state_names),
+            symbol_names: SymbolMap(symbol_names),
+            final_states,
+            transitions,
+        })
+    }
+
+    pub fn get_state_name(&self, state: State) -> Option<&str> {
+        self.state_names.get(state).map(String::as_str)
+    }
+
+    pub fn get_symbol_name(&self, symbol: Symbol) -> Option<&str> {
+        self.symbol_names.get(symbol).map(String::as_str)
+    }
+
+    pub fn get_state(&self, name: &str) -> Option<State> {
+        self.states().find(|&(_, n)| n == name).map(|(s, _)| s)
+    }
+
+    pub fn get_symbol(&self, name: &str) -> Option<Symbol> {
+        self.symbols().find(|&(_, n)| n == name).map(|(s, _)| s)
+    }
+
+    /// The symbols of `input`, a symbol per character, or the first character
+    /// which isn't one.
+    pub fn parse_input(&self, input: &str) -> Result<Vec<Symbol>, char> {
+        let mut buf = [0; 4];
+        input
+            .chars()
+            .map(|char| self.get_symbol(char.encode_utf8(&mut buf)).ok_or(char))
+            .collect()
+    }
+
+    pub fn initial_state(&self) -> State {
+        self.initial_state
+    }
+
+    pub fn blank_symbol(&self) -> Symbol {
+        self.blank_symbol
+    }
+
+    pub fn is_final(&self, state: State) -> bool {
+        self.final_states.get(state).copied().unwrap_or_default()
+    }
+
+    pub fn final_states(&self) -> impl Iterator<Item = State> {
+        self.final_states
+            .entries()
+            .filter(|&(_, f)| *f)
+            .map(|(s, _)| s)
+    }
+
+    pub fn states(&self) -> impl Iterator<Item = (State, &str)> {
+        self.state_names.entries().map(|s| (s.0, s.1.as_str()))
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = (Symbol, &str)> {
+        self.symbol_names.entries().map(|s| (s.0, s.1.as_str()))
+    }
+
+    /// The transitions from `state` reading `symbol`.
+    pub fn transitions(&self, state: State, symbol: Symbol) -> &[To] {
+        self.transitions
+            .get((state, symbol))
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+/// A tape infinite both ways, blank wherever nothing was written.
+///
+/// Only the cells between the first and last non blank ones are stored, so
+/// tapes with the same contents are equal however far the head wandered.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Tape {
+    cells: VecDeque<Symbol>,
+    /// The position of the first stored cell.
+    start: i64,
+    head: i64,
+    blank: Symbol,
+}
+
+impl Tape {
+    /// A tape holding `input` from position 0 with the head on its first
+    /// symbol.
+    pub fn new(input: impl IntoIterator<Item = Symbol>, blank: Symbol) -> Self {
+        let mut tape = Self {
+            cells: input.into_iter().collect(),
+            start: 0,
+            head: 0,
+            blank,
+        };
+        tape.trim();
+        tape
+    }
+
+    /// Where the head is, position 0 is the first symbol of the input.
+    pub fn head(&self) -> i64 {
+        self.head
+    }
+
+    pub fn read(&self, position: i64) -> Symbol {
+        usize::try_from(position - self.start)
+            .ok()
+            .and_then(|i| self.cells.get(i).copied())
+            .unwrap_or(self.blank)
+    }
+
+    /// The position of the first non blank cell, and the cells from it to
+    /// the last non blank one.
+    pub fn contents(&self) -> (i64, impl Iterator<Item = Symbol>) {
+        (self.start, self.cells.iter().copied())
+    }
+
+    fn write(&mut self, symbol: Symbol) {
+        if self.cells.is_empty() {
+            self.start = self.head;
+        }
+        while self.head < self.start {
+            self.cells.push_front(self.blank);
+            self.start -= 1;
+        }
+        while self.head >= self.start + self.cells.len() as i64 {
+            self.cells.push_back(self.blank);
+        }
+        self.cells[(self.head - self.start) as usize] = symbol;
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        while self.cells.front() == Some(&self.blank) {
+            self.cells.pop_front();
+            self.start += 1;
+        }
+        while self.cells.back() == Some(&self.blank) {
+            self.cells.pop_back();
+        }
+    }
+
+    fn shift(&mut self, direction: Direction) {
+        match direction {
+            Direction::Left => self.head -= 1,
+            Direction::Right => self.head += 1,
+            Direction::None => {}
+        }
+    }
+}
+
+/// A transition taken on the way to a configuration.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Step {
+    pub from: State,
+    /// The symbol read under the head.
+    pub read: Symbol,
+    pub to: State,
+    pub written: Symbol,
+    pub direction: Direction,
+    /// Where the transition is written, to highlight it.
+    pub transition: Span,
+    pub function: Span,
+}
+
+/// A configuration of the machine, with how it got there.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NtmState {
+    pub state: State,
+    pub tape: Tape,
+    pub path: Vec<Step>,
+}
+
+pub struct Simulator {
+    machine: Ntm,
+    running: Vec<NtmState>,
+    halted: Option<NtmState>,
+    cycled: Option<NtmState>,
+    /// Every configuration reached, to tell when a branch repeats one.
+    seen: HashSet<(State, Tape)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SimulatorResult {
+    Pending,
+    /// A configuration reached a final state.
+    Accept(NtmState),
+    /// No configuration reached a final state, and this one had no transition
+    /// to take.
+    Halt(NtmState),
+    /// Every configuration went back to one reached before, like this one,
+    /// so the machine runs forever.
+    Cycle(NtmState),
+    /// [`Simulator::run`] gave up, this configuration was still running.
+    StepLimit(NtmState),
+}
+
+impl Simulator {
+    pub fn begin(input: impl IntoIterator<Item = Symbol>, machine: Ntm) -> Self {
+        let initial = NtmState {
+            state: machine.initial_state,
+            tape: Tape::new(input, machine.blank_symbol),
+            path: Vec::new(),
+        };
+        Self {
+            seen: HashSet::from([(initial.state, initial.tape.clone())]),
+            running: vec![initial],
+            halted: None,
+            cycled: None,
+            machine,
+        }
+    }
+
+    pub fn machine(&self) -> &Ntm {
+        &self.machine
+    }
+
+    /// The configurations still being explored.
+    pub fn running(&self) -> &[NtmState] {
+        &self.running
+    }
+
+    /// Takes every transition of every running configuration once, breadth
+    /// first so nondeterministic machines are explored fairly.
+    pub fn step(&mut self) -> SimulatorResult {
+        if let Some(accepted) = self
+            .running
+            .iter()
+            .find(|tm| self.machine.is_final(tm.state))
+        {
+            return SimulatorResult::Accept(accepted.clone());
+        }
+
+        let mut new = Vec::new();
+        for tm in std::mem::take(&mut self.running) {
+            let read = tm.tape.read(tm.tape.head);
+            let transitions = self.machine.transitions(tm.state, read);
+            if transitions.is_empty() {
+                self.halted.get_or_insert(tm);
+                continue;
+            }
+            for to in transitions {
+                let mut tape = tm.tape.clone();
+                tape.write(to.symbol);
+                tape.shift(to.direction);
+                let mut path = tm.path.clone();
+                path.push(Step {
+                    from: tm.state,
+                    read,
+                    to: to.state,
+                    written: to.symbol,
+                    direction: to.direction,
+                    transition: to.transition,
+                    function: to.function,
+                });
+                let next = NtmState {
+                    state: to.state,
+                    tape,
+                    path,
+                };
+                if self.seen.insert((next.state, next.tape.clone())) {
+                    new.push(next);
+                } else {
+                    self.cycled.get_or_insert(next);
+                }
+            }
+        }
+        self.running = new;
+
+        if let Some(accepted) = self
+            .running
+            .iter()
+            .find(|tm| self.machine.is_final(tm.state))
+        {
+            return SimulatorResult::Accept(accepted.clone());
+        }
+        if !self.running.is_empty() {
+            return SimulatorResult::Pending;
+        }
+        match (&self.halted, &self.cycled) {
+            (Some(halted), _) => SimulatorResult::Halt(halted.clone()),
+            (None, Some(cycled)) => SimulatorResult::Cycle(cycled.clone()),
+            (None, None) => unreachable!("configurations end by halting or cycling"),
+        }
+    }
+
+    /// Steps until the machine accepts, halts or cycles, or `max_steps` steps
+    /// were taken.
+    pub fn run(&mut self, max_steps: usize) -> SimulatorResult {
+        for _ in 0..max_steps {
+            match self.step() {
+                SimulatorResult::Pending => {}
+                result => return result,
+            }
+        }
+        match self.running.first() {
+            Some(running) => SimulatorResult::StepLimit(running.clone()),
+            None => self.step(),
+        }
+    }
+}
+
+#[test]
+fn simulate() {
+    use crate::loader::{Context, Interner, Machine, parse_universal};
+
+    let lower = |src: &str| {
+        let interner = Interner::default();
+        let mut ctx = Context::new(src, &interner);
+        let Some(Machine::Tm(tm)) = parse_universal(&mut ctx) else {
+            panic!("expected a turing machine");
+        };
+        Ntm::lower(&tm).unwrap()
+    };
+    let symbols = |ntm: &Ntm, tape: &Tape| {
+        let (start, cells) = tape.contents();
+        let cells: String = cells.map(|s| ntm.get_symbol_name(s).unwrap()).collect();
+        (start, cells)
+    };
+
+    // flips the bits then walks back left past the start of the input
+    let flip = lower(
+        "type = TM
+Q = {q0, back, acc}
+T = {B, 0, 1}
+F = {acc}
+q0 = q0
+B = B
+d(q0, 0) = (q0, 1, R)
+d(q0, 1) = (q0, 0, R)
+d(q0, B) = (back, B, L)
+d(back, 0) = (back, 0, L)
+d(back, 1) = (back, 1, L)
+d(back, B) = (acc, 0, ~)",
+    );
+    let input = flip.parse_input("0110").unwrap();
+    let SimulatorResult::Accept(accepted) = Simulator::begin(input, flip.clone()).run(100) else {
+        panic!("expected 0110 to be accepted");
+    };
+    assert_eq!(symbols(&flip, &accepted.tape), (-1, "01001".into()));
+    assert_eq!(accepted.tape.head(), -1);
+    assert_eq!(accepted.path.len(), 10);
+    assert_eq!(flip.parse_input("012"), Err('2'));
+
+    let run = |src: &str, input: &str| {
+        let ntm = lower(src);
+        let input = ntm.parse_input(input).unwrap();
+        Simulator::begin(input, ntm).run(100)
+    };
+    let halts = "type = TM\nQ = {q0, acc}\nT = {B, a}\nF = {acc}\nB = B
+d(q0, a) = (q0, a, R)";
+    let SimulatorResult::Halt(halted) = run(halts, "aa") else {
+        panic!("expected the machine to halt");
+    };
+    assert_eq!(halted.tape.head(), 2);
+
+    let cycles = "type = TM\nQ = {q0, q1, acc}\nT = {B, a}\nF = {acc}\nB = B
+d(q0, a) = (q1, a, R)
+d(q1, B) = (q0, B, L)";
+    assert!(matches!(run(cycles, "a"), SimulatorResult::Cycle(_)));
+
+    let forever = "type = TM\nQ = {q0, acc}\nT = {B, a}\nF = {acc}\nB = B
+d(q0, B) = (q0, a, R)";
+    let SimulatorResult::StepLimit(running) = run(forever, "") else {
+        panic!("expected the machine to run out of steps");
+    };
+    assert_eq!(running.tape.head(), 100);
+
+    // only one of the guesses reaches the final state
+    let guesses = "type = NTM\nQ = {q0, q1, q2, acc}\nT = {B, a}\nF = {acc}\nB = B
+d(q0, a) = {(q1, a, R), (q2, a, R)}
+d(q1, B) = (q1, B, ~)
+d(q2, B) = (acc, a, ~)";
+    let SimulatorResult::Accept(accepted) = run(guesses, "a") else {
+        panic!("expected a guess to be accepted");
+    };
+    assert_eq!(accepted.path.len(), 2);
+}
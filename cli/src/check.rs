@@ -0,0 +1,158 @@
+use std::{
+    io::{IsTerminal, Read},
+    process::ExitCode,
+};
+
+use automata::loader::{
+    Context, FileId,
+    log::{RenderOptions, Sarif, apply_suggestions},
+    parse_universal,
+    source::{FsProvider, SourceMap},
+};
+
+/// How `check` reports the diagnostics.
+enum Format {
+    /// Rendered with the source for people, to stderr.
+    Human,
+    /// JSON lines to stdout, one entry per line.
+    Json,
+    /// One SARIF document for all the files, to stdout once they are checked.
+    Sarif(Sarif),
+}
+
+/// `automata check [--fix] [--format human|json|sarif] [files...]`, reports
+/// the diagnostics of the files or stdin. With `--fix` the machine applicable
+/// suggestions are applied first, to the files and those they include in place,
+/// or stdin to stdout, which is why fixing stdin needs the human format.
+pub fn run(mut args: impl Iterator<Item = String>) -> ExitCode {
+    let mut fix = false;
+    let mut format = Format::Human;
+    let mut files = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fix" => fix = true,
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("human") => Format::Human,
+                    Some("json") => Format::Json,
+                    Some("sarif") => Format::Sarif(Sarif::new()),
+                    Some(other) => {
+                        eprintln!("unknown format {other:?}, expected human, json or sarif");
+                        return ExitCode::FAILURE;
+                    }
+                    None => {
+                        eprintln!("--format expects human, json or sarif");
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+            flag if flag.starts_with('-') && flag != "-" => {
+                eprintln!("unknown flag {flag:?} for check");
+                return ExitCode::FAILURE;
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    if files.is_empty() {
+        if fix && !matches!(format, Format::Human) {
+            eprintln!(
+                "--fix prints the fixed stdin to stdout, it cannot be combined with --format json or sarif"
+            );
+            return ExitCode::FAILURE;
+        }
+        let mut src = String::new();
+        if let Err(err) = std::io::stdin().read_to_string(&mut src) {
+            eprintln!("cannot read stdin: {err}");
+            return ExitCode::FAILURE;
+        }
+        let mut sources = SourceMap::load("<stdin>", src.clone(), &mut FsProvider);
+        if fix {
+            let (_, fixed) = check(&sources, true, &mut format);
+            let src = match fixed.into_iter().find(|(file, _)| *file == FileId(0)) {
+                Some((_, fixed)) => fixed,
+                None => src,
+            };
+            print!("{src}");
+            sources = SourceMap::load("<stdin>", src, &mut FsProvider);
+        }
+        let errors = check(&sources, false, &mut format).0;
+        finish(format);
+        return if errors {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        };
+    }
+
+    let mut code = ExitCode::SUCCESS;
+    for path in files {
+        let src = match std::fs::read_to_string(&path) {
+            Ok(src) => src,
+            Err(err) => {
+                eprintln!("cannot read {path:?}: {err}");
+                code = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        let mut sources = SourceMap::load(path.clone(), src, &mut FsProvider);
+        if fix {
+            let (_, fixed) = check(&sources, true, &mut format);
+            for (file, src) in fixed {
+                let name = sources.files().nth(file.0 as usize).unwrap().name;
+                if let Err(err) = std::fs::write(name, src) {
+                    eprintln!("cannot write {name:?}: {err}");
+                    code = ExitCode::FAILURE;
+                }
+            }
+            let src = std::fs::read_to_string(&path).unwrap_or_default();
+            sources = SourceMap::load(path, src, &mut FsProvider);
+        }
+        if check(&sources, false, &mut format).0 {
+            code = ExitCode::FAILURE;
+        }
+    }
+    finish(format);
+    code
+}
+
+/// Prints what `format` collected over all the files.
+fn finish(format: Format) {
+    if let Format::Sarif(sarif) = format {
+        println!("{sarif}");
+    }
+}
+
+/// Compiles `sources`, printing the diagnostics unless fixing. Returns whether
+/// there were errors, and the fixed source of each file a fix applies to.
+fn check(sources: &SourceMap, fix: bool, format: &mut Format) -> (bool, Vec<(FileId, String)>) {
+    let mut ctx = Context::with_sources(sources).resilient();
+    parse_universal(&mut ctx);
+
+    let mut fixed = Vec::new();
+    if fix {
+        for (i, file) in ctx.files().iter().enumerate() {
+            let id = FileId(i as u32);
+            let src = apply_suggestions(file.src, id, ctx.logs().entries());
+            if src != file.src {
+                fixed.push((id, src));
+            }
+        }
+    } else {
+        match format {
+            Format::Human => {
+                let color =
+                    std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+                let options = RenderOptions::new().color(color);
+                for entry in ctx.logs_display() {
+                    eprintln!("{}", entry.options(options));
+                }
+            }
+            Format::Json => print!("{}", ctx.logs().to_json_lines(ctx.files())),
+            Format::Sarif(sarif) => {
+                sarif.add(ctx.logs(), ctx.files());
+            }
+        }
+    }
+    (ctx.contains_errors(), fixed)
+}
@@ -0,0 +1,32 @@
+use std::process::ExitCode;
+
+use automata::{automatan::lint::Lint, loader::codes::Code};
+
+/// `automata explain [code]`, prints the explanation of a diagnostic code, or
+/// lists every code without one.
+pub fn run(mut args: impl Iterator<Item = String>) -> ExitCode {
+    let Some(code) = args.next() else {
+        for code in Code::all() {
+            println!("{code}: {}", code.summary());
+        }
+        return ExitCode::SUCCESS;
+    };
+    if let Some(arg) = args.next() {
+        eprintln!("unexpected argument {arg:?} for explain");
+        return ExitCode::FAILURE;
+    }
+
+    match code.parse::<Code>() {
+        Ok(code) => {
+            println!("{code}: {}\n\n{}", code.summary(), code.explanation());
+            if let Some(lint) = Lint::from_code(code) {
+                println!("\nReported by the `{lint}` lint, `allow = {{{lint}}}` silences it.");
+            }
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{err} {code:?}, run `automata explain` to list them");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use std::{
+    io::{Read, Write},
+    process::ExitCode,
+};
+
+use automata::loader::fmt::format;
+
+/// `automata fmt [--check] [files...]`, formats the files in place or stdin to
+/// stdout, `-` standing for stdin. With `--check` nothing is written, the files
+/// which aren't formatted are listed instead.
+pub fn run(args: impl Iterator<Item = String>) -> ExitCode {
+    let mut check = false;
+    let mut files = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check = true,
+            flag if flag.starts_with('-') && flag != "-" => {
+                eprintln!("unknown flag {flag:?} for fmt");
+                return ExitCode::FAILURE;
+            }
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        files.push("-".to_string());
+    }
+
+    let mut code = ExitCode::SUCCESS;
+    for path in files {
+        if path == "-" {
+            if !format_stdin(check) {
+                code = ExitCode::FAILURE;
+            }
+            continue;
+        }
+        let src = match std::fs::read_to_string(&path) {
+            Ok(src) => src,
+            Err(err) => {
+                eprintln!("cannot read {path:?}: {err}");
+                code = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        let formatted = format(&src);
+        if formatted == src {
+            continue;
+        }
+        if check {
+            println!("{path}");
+            code = ExitCode::FAILURE;
+        } else if let Err(err) = std::fs::write(&path, formatted) {
+            eprintln!("cannot write {path:?}: {err}");
+            code = ExitCode::FAILURE;
+        }
+    }
+    code
+}
+
+/// Formats stdin to stdout, or with `check` lists it if it isn't formatted.
+/// Returns whether it succeeded.
+fn format_stdin(check: bool) -> bool {
+    let mut src = String::new();
+    if let Err(err) = std::io::stdin().read_to_string(&mut src) {
+        eprintln!("cannot read stdin: {err}");
+        return false;
+    }
+    let formatted = format(&src);
+    if check {
+        if formatted != src {
+            println!("<stdin>");
+            return false;
+        }
+        return true;
+    }
+    _ = std::io::stdout().write_all(formatted.as_bytes());
+    true
+}
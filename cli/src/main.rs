@@ -1 +1,35 @@
-pub fn main() {}
+use std::process::ExitCode;
+
+mod check;
+mod explain;
+mod fmt;
+
+const USAGE: &str = "usage: automata <command> [args...]
+
+commands:
+    check [--fix] [--format human|json|sarif] [files...]
+                                check machine files, --fix applies the safe fixes
+    explain [code]              explain a diagnostic code like A0012, or list them
+    fmt [--check] [files...]    format machine files in place, or stdin to stdout
+";
+
+pub fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("check") => check::run(args),
+        Some("explain") => explain::run(args),
+        Some("fmt") => fmt::run(args),
+        Some("help" | "--help" | "-h") => {
+            print!("{USAGE}");
+            ExitCode::SUCCESS
+        }
+        Some(command) => {
+            eprint!("unknown command {command:?}\n\n{USAGE}");
+            ExitCode::FAILURE
+        }
+        None => {
+            eprint!("{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,674 @@
+//! Everything the server knows about a document, recomputed from its text.
+
+use automata::{
+    automatan::{Letter, State, Symbol, cfg::Variable, tm::Direction},
+    delta_lower, epsilon, gamma_upper,
+    loader::{
+        BLANK_SYMBOL, Context, FileId, INITIAL_STACK, INITIAL_STATE, Machine, Span, Spanned,
+        ast::{self, Item, ProductionUnit, Regex, TopLevel},
+        lexer::{Lexer, Token},
+        log::{LogEntry, LogLevel},
+        parse_universal,
+        parser::Parser,
+        source::{FsProvider, MemoryProvider, SourceMap},
+    },
+    otherwise, sigma_upper,
+};
+use serde_json::{Value, json};
+
+/// Converts between byte offsets and the UTF-16 line/character positions of
+/// the protocol.
+pub struct LineIndex<'a> {
+    text: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, starts }
+    }
+
+    pub fn position(&self, offset: usize) -> Value {
+        let line = self.starts.partition_point(|start| *start <= offset) - 1;
+        let character: usize = self.text[self.starts[line]..offset]
+            .chars()
+            .map(char::len_utf16)
+            .sum();
+        json!({ "line": line, "character": character })
+    }
+
+    pub fn range(&self, span: Span) -> Value {
+        json!({ "start": self.position(span.0), "end": self.position(span.1) })
+    }
+
+    pub fn offset(&self, position: &Value) -> Option<usize> {
+        let line = position.get("line")?.as_u64()? as usize;
+        let character = position.get("character")?.as_u64()? as usize;
+        let start = *self.starts.get(line)?;
+        let end = self
+            .starts
+            .get(line + 1)
+            .copied()
+            .unwrap_or(self.text.len());
+
+        let mut utf16 = 0;
+        for (i, char) in self.text[start..end].char_indices() {
+            if utf16 >= character {
+                return Some(start + i);
+            }
+            utf16 += char.len_utf16();
+        }
+        Some(end)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NameKind {
+    State,
+    Letter,
+    Symbol,
+    Variable,
+}
+
+/// A state, letter, symbol or variable of the machine.
+pub struct Name {
+    pub text: String,
+    pub kind: NameKind,
+    /// Where it's defined, which may be an included file.
+    pub definition: Option<Span>,
+    /// Every use as a name of its kind, in the document and the files it
+    /// includes, including the definition.
+    pub references: Vec<Span>,
+    pub hover: String,
+}
+
+pub const TOKEN_TYPES: &[&str] = &[
+    "keyword",
+    "variable",
+    "enumMember",
+    "typeParameter",
+    "string",
+    "comment",
+    "operator",
+];
+
+const KEYWORD: u32 = 0;
+const STRING: u32 = 4;
+const COMMENT: u32 = 5;
+const OPERATOR: u32 = 6;
+
+impl NameKind {
+    fn token_type(self) -> u32 {
+        match self {
+            NameKind::State => 1,
+            NameKind::Letter => 2,
+            NameKind::Symbol | NameKind::Variable => 3,
+        }
+    }
+}
+
+pub struct Analysis {
+    pub diagnostics: Vec<Value>,
+    pub names: Vec<Name>,
+    /// Classified tokens as `(span, token type)`.
+    pub tokens: Vec<(Span, u32)>,
+    /// The uri and text of the document and the files it includes, by id.
+    files: Vec<(String, String)>,
+    grammar: bool,
+}
+
+impl Analysis {
+    /// Compiles `text`, the contents of the document at `uri`. Includes are
+    /// read from disk for `file://` documents.
+    pub fn new(uri: &str, text: &str) -> Self {
+        let path = uri.strip_prefix("file://").map(percent_decode);
+        let sources = match &path {
+            Some(path) => SourceMap::load(path.clone(), text, &mut FsProvider),
+            None => SourceMap::load(uri, text, &mut MemoryProvider::default()),
+        };
+        let mut ctx = Context::with_sources(&sources).resilient();
+        let machine = parse_universal(&mut ctx);
+
+        let index = LineIndex::new(text);
+        let diagnostics = ctx
+            .logs()
+            .entries()
+            .iter()
+            .map(|entry| diagnostic(entry, uri, &ctx, &index))
+            .collect();
+
+        let files: Vec<_> = ctx
+            .files()
+            .iter()
+            .enumerate()
+            .map(|(i, file)| {
+                let uri = match i {
+                    0 => uri.to_string(),
+                    _ => format!("file://{}", file.name),
+                };
+                (uri, file.src.to_string())
+            })
+            .collect();
+
+        let mut names = machine.as_ref().map(collect_names).unwrap_or_default();
+        if let Some(machine) = &machine {
+            // parsed again for the spans of the names, the machine only keeps
+            // those of definitions and transitions
+            let mut ctx = Context::with_sources(&sources);
+            let items: Vec<_> = Parser::new(&mut ctx).collect();
+            let mut resolver = Resolver {
+                machine,
+                files: &files,
+                names: &mut names,
+            };
+            for item in &items {
+                resolver.top_level(item);
+            }
+        }
+
+        let lexed: Vec<_> = Lexer::new(text)
+            .filter_map(|Spanned(token, span)| Some((token.ok()?, span)))
+            .collect();
+        let significant = |i: usize| {
+            lexed[i..]
+                .iter()
+                .map(|(token, _)| token)
+                .find(|token| !matches!(token, Token::Comment(_)))
+        };
+
+        let mut tokens = Vec::new();
+        for (i, (token, span)) in lexed.iter().enumerate() {
+            let first = i == 0 || lexed[i - 1].0 == Token::LineEnd;
+            let kind = match token {
+                Token::Comment(_) => COMMENT,
+                Token::String(..) => STRING,
+                Token::Tilde | Token::Star => KEYWORD,
+                Token::Eq | Token::LSmallArrow | Token::LBigArrow | Token::Or => OPERATOR,
+                Token::Ident(epsilon!(pat) | otherwise!(pat)) => KEYWORD,
+                // the names of definitions and functions, like Q or d
+                Token::Ident(_)
+                    if first && matches!(significant(i + 1), Some(Token::Eq | Token::LPar)) =>
+                {
+                    KEYWORD
+                }
+                Token::Ident(_) => match names.iter().find(|name| name.references.contains(span)) {
+                    Some(name) => name.kind.token_type(),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            tokens.push((*span, kind));
+        }
+
+        Analysis {
+            diagnostics,
+            names,
+            tokens,
+            files,
+            grammar: matches!(machine, Some(Machine::Cfg(_))),
+        }
+    }
+
+    /// The name with a reference at `offset` of the document.
+    pub fn name_at(&self, offset: usize) -> Option<&Name> {
+        self.names.iter().find(|name| {
+            name.references
+                .iter()
+                .any(|span| span.2 == FileId(0) && span.0 <= offset && offset <= span.1)
+        })
+    }
+
+    /// The `{ uri, range }` location of `span`, in whichever file it's in.
+    pub fn location(&self, span: Span) -> Value {
+        let (uri, text) = &self.files[span.2.0 as usize];
+        json!({ "uri": uri, "range": LineIndex::new(text).range(span) })
+    }
+
+    /// Whether `name` means something else than a name where names are used,
+    /// like `epsilon`, or is the name of a definition or function, like `Q` or
+    /// `d`, which only grammars allow as names.
+    pub fn is_reserved(&self, name: &str) -> bool {
+        matches!(name, epsilon!(pat) | otherwise!(pat))
+            || !self.grammar
+                && matches!(
+                    name,
+                    delta_lower!(pat)
+                        | sigma_upper!(pat)
+                        | gamma_upper!(pat)
+                        | "Q"
+                        | "F"
+                        | "accept"
+                        | "type"
+                )
+    }
+
+    /// The semantic tokens in the relative encoding of the protocol, tokens
+    /// spanning several lines are split up.
+    pub fn semantic_tokens(&self, text: &str) -> Vec<u32> {
+        let index = LineIndex::new(text);
+        let mut data = Vec::new();
+        let (mut last_line, mut last_start) = (0, 0);
+        for (span, kind) in &self.tokens {
+            let mut start = span.0;
+            for part in text[span.0..span.1].split_inclusive('\n') {
+                let content = part.trim_end_matches(['\n', '\r']);
+                let length: usize = content.chars().map(char::len_utf16).sum();
+                let position = index.position(start);
+                start += part.len();
+                if length == 0 {
+                    continue;
+                }
+
+                let line = position["line"].as_u64().unwrap() as u32;
+                let character = position["character"].as_u64().unwrap() as u32;
+                let delta_start = if line == last_line {
+                    character - last_start
+                } else {
+                    character
+                };
+                data.extend([line - last_line, delta_start, length as u32, *kind, 0]);
+                (last_line, last_start) = (line, character);
+            }
+        }
+        data
+    }
+}
+
+fn diagnostic(entry: &LogEntry, uri: &str, ctx: &Context, index: &LineIndex) -> Value {
+    let range = |span: Option<Span>| match span {
+        Some(span) => index.range(ctx.root_span(span)),
+        None => index.range(Span(0, 0, FileId(0))),
+    };
+
+    let mut message = entry.message.clone();
+    if let Some(label) = &entry.label {
+        message.push_str(&format!(" ({label})"));
+    }
+    for note in &entry.notes {
+        message.push_str(&format!("\n{}: {}", note.level.name(), note.message));
+    }
+    let related: Vec<_> = entry
+        .labels
+        .iter()
+        .map(|label| {
+            json!({
+                "location": { "uri": uri, "range": range(Some(label.span)) },
+                "message": label.message,
+            })
+        })
+        .collect();
+
+    let mut diagnostic = json!({
+        "range": range(entry.span),
+        "severity": match entry.level {
+            LogLevel::Error => 1,
+            LogLevel::Warning => 2,
+            LogLevel::Info => 3,
+            LogLevel::Help => 4,
+        },
+        "source": "automata",
+        "message": message,
+        "relatedInformation": related,
+    });
+    if let Some(code) = entry.code {
+        diagnostic["code"] = json!(code.to_string());
+    }
+    diagnostic
+}
+
+fn name(text: &str, kind: NameKind, hover: String, definition: Span) -> Name {
+    Name {
+        text: text.to_string(),
+        kind,
+        definition: Some(definition),
+        references: Vec::new(),
+        hover,
+    }
+}
+
+/// The names of `machine`, without their references.
+fn collect_names(machine: &Machine) -> Vec<Name> {
+    let mut names = Vec::new();
+    fn letter<'a>(letter: Option<Letter<'a>>) -> &'a str {
+        letter.map_or("ε", |letter| letter.0)
+    }
+
+    match machine {
+        Machine::Fa(fa) => {
+            for (State(state), info) in &fa.states {
+                let mut lines = Vec::new();
+                for (from, to) in fa
+                    .transitions
+                    .iter()
+                    .filter(|(from, _)| from.state.0 == *state)
+                {
+                    for to in to {
+                        lines.push(format!(
+                            "δ({state}, {}) = {}",
+                            letter(from.letter),
+                            to.state.0
+                        ));
+                    }
+                }
+                let hover = state_hover(
+                    state,
+                    fa.initial_state.0 == *state,
+                    fa.final_states.contains_key(&State(state)),
+                    lines,
+                );
+                names.push(name(state, NameKind::State, hover, info.definition));
+            }
+            for (Letter(letter), info) in &fa.alphabet {
+                names.push(name(
+                    letter,
+                    NameKind::Letter,
+                    format!("letter `{letter}`"),
+                    info.definition,
+                ));
+            }
+        }
+        Machine::Pda(pda) => {
+            for (State(state), info) in &pda.states {
+                let mut lines = Vec::new();
+                for (from, to) in pda
+                    .transitions
+                    .iter()
+                    .filter(|(from, _)| from.state.0 == *state)
+                {
+                    for to in to {
+                        let stack: Vec<_> = to.stack.iter().map(|symbol| symbol.0).collect();
+                        lines.push(format!(
+                            "δ({state}, {}, {}) = ({}, [{}])",
+                            letter(from.letter),
+                            from.symbol.0,
+                            to.state.0,
+                            stack.join(" ")
+                        ));
+                    }
+                }
+                let is_final = pda
+                    .final_states
+                    .as_ref()
+                    .is_some_and(|states| states.contains_key(&State(state)));
+                let hover = state_hover(state, pda.initial_state.0 == *state, is_final, lines);
+                names.push(name(state, NameKind::State, hover, info.definition));
+            }
+            for (Letter(letter), info) in &pda.alphabet {
+                names.push(name(
+                    letter,
+                    NameKind::Letter,
+                    format!("letter `{letter}`"),
+                    info.definition,
+                ));
+            }
+            for (Symbol(symbol), info) in &pda.symbols {
+                names.push(name(
+                    symbol,
+                    NameKind::Symbol,
+                    format!("stack symbol `{symbol}`"),
+                    info.definition,
+                ));
+            }
+        }
+        Machine::Tm(tm) => {
+            for (State(state), info) in &tm.states {
+                let mut lines = Vec::new();
+                for (from, to) in tm
+                    .transitions
+                    .iter()
+                    .filter(|(from, _)| from.state.0 == *state)
+                {
+                    for to in to {
+                        let direction = match to.direction {
+                            Direction::Left => "L",
+                            Direction::Right => "R",
+                            Direction::None => "~",
+                        };
+                        lines.push(format!(
+                            "δ({state}, {}) = ({}, {}, {direction})",
+                            from.symbol.0, to.state.0, to.symbol.0
+                        ));
+                    }
+                }
+                let hover = state_hover(
+                    state,
+                    tm.initial_state.0 == *state,
+                    tm.final_states.contains_key(&State(state)),
+                    lines,
+                );
+                names.push(name(state, NameKind::State, hover, info.definition));
+            }
+            for (Symbol(symbol), info) in &tm.symbols {
+                names.push(name(
+                    symbol,
+                    NameKind::Symbol,
+                    format!("tape symbol `{symbol}`"),
+                    info.definition,
+                ));
+            }
+        }
+        Machine::Cfg(cfg) => {
+            for (Variable(variable), info) in &cfg.variables {
+                names.push(name(
+                    variable,
+                    NameKind::Variable,
+                    format!("variable `{variable}`"),
+                    info.definition,
+                ));
+            }
+            for (Letter(letter), info) in &cfg.alphabet {
+                names.push(name(
+                    letter,
+                    NameKind::Letter,
+                    format!("terminal `{letter}`"),
+                    info.definition,
+                ));
+            }
+        }
+    }
+
+    names
+}
+
+/// Records where the names of a machine are used by what the place they're
+/// used at expects, so a state and a letter spelled the same stay apart and
+/// keywords like the `q0` of `q0 = q1` aren't taken for names.
+struct Resolver<'m, 'a> {
+    machine: &'m Machine<'a>,
+    files: &'m [(String, String)],
+    names: &'m mut [Name],
+}
+
+impl Resolver<'_, '_> {
+    fn top_level(&mut self, Spanned(top_level, _): &Spanned<TopLevel>) {
+        use NameKind as K;
+        match top_level {
+            TopLevel::Item(Spanned(name, _), item) => {
+                let kind = match (self.machine, *name) {
+                    (Machine::Cfg(_), "V" | "start") => K::Variable,
+                    (Machine::Cfg(_), sigma_upper!(pat)) => K::Letter,
+                    (Machine::Cfg(_), _) => return,
+                    (_, "Q" | "F" | INITIAL_STATE) => K::State,
+                    (_, sigma_upper!(pat) | "regex") => K::Letter,
+                    (_, gamma_upper!(pat) | INITIAL_STACK | BLANK_SYMBOL) => K::Symbol,
+                    _ => return,
+                };
+                self.item(item, kind);
+            }
+            TopLevel::TransitionFunc(Spanned((Spanned(name, _), args), _), list) => {
+                let Some((from, to)) = self.transition_kinds() else {
+                    return;
+                };
+                if !matches!(*name, delta_lower!(pat)) {
+                    return;
+                }
+                for (arg, kind) in args.0.0.iter().zip(from) {
+                    self.item(arg, *kind);
+                }
+                self.destination(list, to);
+            }
+            TopLevel::Table(table) => {
+                let Some((from, to)) = self.transition_kinds() else {
+                    return;
+                };
+                if !matches!(table.name.0, delta_lower!(pat)) {
+                    return;
+                }
+                for column in &table.columns {
+                    match &from[1..] {
+                        [kind] => self.item(column, *kind),
+                        kinds => self.tuples(column, kinds),
+                    }
+                }
+                for row in &table.rows {
+                    self.item(&row.label, K::State);
+                    for Spanned(cell, span) in &row.cells {
+                        if let Some(cell) = cell {
+                            self.destination(&Spanned(cell.clone(), *span), to);
+                        }
+                    }
+                }
+            }
+            TopLevel::ProductionRule(head, groups) => {
+                for group in std::iter::once(head).chain(&groups.0) {
+                    for Spanned(unit, span) in &group.0.0 {
+                        match unit {
+                            ProductionUnit::Ident(text) => {
+                                let variable = self
+                                    .names
+                                    .iter()
+                                    .any(|name| name.kind == K::Variable && name.text == *text);
+                                let kind = if variable { K::Variable } else { K::Letter };
+                                self.reference(text, kind, *span);
+                            }
+                            ProductionUnit::String(text) => self.reference(text, K::Letter, *span),
+                            ProductionUnit::Epsilon(_) => {}
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// The kinds of the arguments of the transition function, and of the
+    /// elements of the tuples it goes to, `None` for a plain set of states.
+    fn transition_kinds(&self) -> Option<(&'static [NameKind], Option<&'static [NameKind]>)> {
+        use NameKind as K;
+        match self.machine {
+            Machine::Fa(_) => Some((&[K::State, K::Letter], None)),
+            Machine::Pda(_) => Some((
+                &[K::State, K::Letter, K::Symbol],
+                Some(&[K::State, K::Symbol]),
+            )),
+            Machine::Tm(_) => Some((&[K::State, K::Symbol], Some(&[K::State, K::Symbol]))),
+            Machine::Cfg(_) => None,
+        }
+    }
+
+    fn destination(&mut self, item: &Spanned<Item>, tuple: Option<&[NameKind]>) {
+        match tuple {
+            Some(kinds) => self.tuples(item, kinds),
+            None => self.item(item, NameKind::State),
+        }
+    }
+
+    /// Records the names `item` uses as `kind`, looking into sets and lists.
+    fn item(&mut self, Spanned(item, span): &Spanned<Item>, kind: NameKind) {
+        match item {
+            Item::Symbol(ast::Symbol::Ident(text)) => self.reference(text, kind, *span),
+            Item::String(text) => self.reference(text, kind, *span),
+            Item::List(ast::List(items, _)) => {
+                for item in items {
+                    self.item(item, kind);
+                }
+            }
+            Item::Regex(regex) => self.regex(Spanned(regex, *span)),
+            _ => {}
+        }
+    }
+
+    /// Records the names the tuples of `item` use, the elements as `kinds`.
+    fn tuples(&mut self, Spanned(item, _): &Spanned<Item>, kinds: &[NameKind]) {
+        match item {
+            Item::Tuple(ast::Tuple(items)) => {
+                for (item, kind) in items.iter().zip(kinds) {
+                    self.item(item, *kind);
+                }
+            }
+            Item::List(ast::List(items, _)) => {
+                for item in items {
+                    self.tuples(item, kinds);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn regex(&mut self, Spanned(regex, span): Spanned<&Regex>) {
+        match regex {
+            Regex::Terminal(text) => self.reference(text, NameKind::Letter, span),
+            Regex::Match { .. } => {}
+            Regex::Concat(regexes) | Regex::Union(regexes) | Regex::Intersection(regexes) => {
+                for regex in regexes {
+                    self.regex(regex.as_ref());
+                }
+            }
+            Regex::Star(regex) | Regex::Plus(regex) | Regex::Complement(regex) => {
+                self.regex(regex.as_ref().as_ref())
+            }
+        }
+    }
+
+    /// Records `span` as a use of the name `text` of `kind`, if it's written
+    /// out there rather than generated from a range or template.
+    fn reference(&mut self, text: &str, kind: NameKind, span: Span) {
+        let written = &self.files[span.2.0 as usize].1[span.0..span.1];
+        if written != text && !written.starts_with(['"', '\'']) {
+            return;
+        }
+        if let Some(name) = self
+            .names
+            .iter_mut()
+            .find(|name| name.kind == kind && name.text == text)
+            && !name.references.contains(&span)
+        {
+            name.references.push(span);
+        }
+    }
+}
+
+fn state_hover(state: &str, initial: bool, is_final: bool, mut lines: Vec<String>) -> String {
+    let mut header = format!("state `{state}`");
+    match (initial, is_final) {
+        (true, true) => header.push_str(" (initial, final)"),
+        (true, false) => header.push_str(" (initial)"),
+        (false, true) => header.push_str(" (final)"),
+        (false, false) => {}
+    }
+    if lines.is_empty() {
+        return format!("{header}\n\nno outgoing transitions");
+    }
+    lines.sort();
+    format!("{header}\n\n```\n{}\n```", lines.join("\n"))
+}
+
+fn percent_decode(str: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut rest = str.as_bytes();
+    while let [first, tail @ ..] = rest {
+        if *first == b'%'
+            && let [high, low, tail @ ..] = tail
+            && let Ok(byte) = u8::from_str_radix(&format!("{}{}", *high as char, *low as char), 16)
+        {
+            bytes.push(byte);
+            rest = tail;
+            continue;
+        }
+        bytes.push(*first);
+        rest = tail;
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
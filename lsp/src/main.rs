@@ -0,0 +1,253 @@
+//! A language server for machine files, spoken over stdio.
+
+use std::{collections::HashMap, io, process::ExitCode};
+
+use automata::loader::{
+    FileId,
+    lexer::{Lexer, Token},
+};
+use serde_json::{Value, json};
+
+mod analysis;
+mod rpc;
+
+use analysis::{Analysis, LineIndex, TOKEN_TYPES};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, String>,
+    shutdown: bool,
+}
+
+type Response = Result<Value, (i64, String)>;
+
+impl Server {
+    /// Handles `message`, returning the messages to send back.
+    fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or("");
+        let params = &message["params"];
+
+        let Some(id) = message.get("id") else {
+            return self.notification(method, params);
+        };
+        let response = match method {
+            "initialize" => Ok(capabilities()),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/definition" => self.definition(params),
+            "textDocument/references" => self.references(params),
+            "textDocument/rename" => self.rename(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/semanticTokens/full" => self.semantic_tokens(params),
+            _ => Err((METHOD_NOT_FOUND, format!("unsupported method {method:?}"))),
+        };
+        vec![match response {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        }]
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or("");
+                self.documents.insert(uri.clone(), text.to_string());
+            }
+            "textDocument/didChange" => {
+                // only full syncs are advertised, so the last change is the text
+                let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                else {
+                    return Vec::new();
+                };
+                self.documents.insert(uri.clone(), text.to_string());
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![publish_diagnostics(&uri, Vec::new())];
+            }
+            _ => return Vec::new(),
+        }
+
+        let analysis = Analysis::new(&uri, &self.documents[&uri]);
+        vec![publish_diagnostics(&uri, analysis.diagnostics)]
+    }
+
+    /// The document, its analysis and the offset of the position of `params`.
+    fn locate<'a>(
+        &'a self,
+        params: &'a Value,
+    ) -> Result<(&'a str, &'a str, Analysis, usize), (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+        let Some(text) = self.documents.get(uri) else {
+            return Err((INVALID_PARAMS, format!("unknown document {uri:?}")));
+        };
+        let Some(offset) = LineIndex::new(text).offset(&params["position"]) else {
+            return Err((INVALID_PARAMS, "position outside of the document".into()));
+        };
+        Ok((uri, text, Analysis::new(uri, text), offset))
+    }
+
+    fn definition(&self, params: &Value) -> Response {
+        let (_, _, analysis, offset) = self.locate(params)?;
+        Ok(
+            match analysis.name_at(offset).and_then(|name| name.definition) {
+                Some(span) => analysis.location(span),
+                None => Value::Null,
+            },
+        )
+    }
+
+    fn references(&self, params: &Value) -> Response {
+        let (_, _, analysis, offset) = self.locate(params)?;
+        let Some(name) = analysis.name_at(offset) else {
+            return Ok(Value::Null);
+        };
+        let declaration = params["context"]["includeDeclaration"]
+            .as_bool()
+            .unwrap_or(true);
+        Ok(name
+            .references
+            .iter()
+            .filter(|span| declaration || Some(**span) != name.definition)
+            .map(|span| analysis.location(*span))
+            .collect())
+    }
+
+    /// Renames the name at the position everywhere it's used as one, in the
+    /// document and the files it includes.
+    fn rename(&self, params: &Value) -> Response {
+        let (_, _, analysis, offset) = self.locate(params)?;
+        let Some(name) = analysis.name_at(offset) else {
+            return Err((INVALID_PARAMS, "nothing to rename here".into()));
+        };
+        if analysis.is_reserved(&name.text) {
+            return Err((INVALID_PARAMS, format!("{:?} is a keyword", name.text)));
+        }
+
+        let new_name = params["newName"].as_str().unwrap_or("");
+        let mut tokens = Lexer::new(new_name);
+        if !matches!(
+            (tokens.next(), tokens.next()),
+            (Some(automata::loader::Spanned(Ok(Token::Ident(ident)), _)), None) if ident == new_name
+        ) {
+            return Err((INVALID_PARAMS, format!("{new_name:?} is not a valid name")));
+        }
+        if analysis.is_reserved(new_name) {
+            return Err((INVALID_PARAMS, format!("{new_name:?} is a keyword")));
+        }
+        if analysis.names.iter().any(|other| other.text == new_name) {
+            return Err((INVALID_PARAMS, format!("{new_name:?} is already defined")));
+        }
+
+        let mut changes = serde_json::Map::new();
+        for span in &name.references {
+            let location = analysis.location(*span);
+            let edits = changes
+                .entry(location["uri"].as_str().unwrap())
+                .or_insert_with(|| json!([]));
+            edits
+                .as_array_mut()
+                .unwrap()
+                .push(json!({ "range": location["range"], "newText": new_name }));
+        }
+        Ok(json!({ "changes": changes }))
+    }
+
+    fn hover(&self, params: &Value) -> Response {
+        let (_, text, analysis, offset) = self.locate(params)?;
+        let Some(name) = analysis.name_at(offset) else {
+            return Ok(Value::Null);
+        };
+        let Some(span) = name
+            .references
+            .iter()
+            .find(|span| span.2 == FileId(0) && span.0 <= offset && offset <= span.1)
+        else {
+            return Ok(Value::Null);
+        };
+        Ok(json!({
+            "contents": { "kind": "markdown", "value": name.hover },
+            "range": LineIndex::new(text).range(*span),
+        }))
+    }
+
+    fn semantic_tokens(&self, params: &Value) -> Response {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+        let Some(text) = self.documents.get(uri) else {
+            return Err((INVALID_PARAMS, format!("unknown document {uri:?}")));
+        };
+        let analysis = Analysis::new(uri, text);
+        Ok(json!({ "data": analysis.semantic_tokens(text) }))
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "capabilities": {
+            "textDocumentSync": 1,
+            "definitionProvider": true,
+            "referencesProvider": true,
+            "renameProvider": true,
+            "hoverProvider": true,
+            "semanticTokensProvider": {
+                "legend": { "tokenTypes": TOKEN_TYPES, "tokenModifiers": [] },
+                "full": true,
+            },
+        },
+        "serverInfo": { "name": "automata-lsp", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+fn main() -> ExitCode {
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut server = Server::default();
+
+    loop {
+        let message = match rpc::read_message(&mut input) {
+            Ok(Some(message)) => message,
+            Ok(None) => return ExitCode::FAILURE,
+            Err(err) => {
+                eprintln!("automata-lsp: {err}");
+                return ExitCode::FAILURE;
+            }
+        };
+        if message["method"] == "exit" {
+            return if server.shutdown {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            };
+        }
+        for reply in server.handle(&message) {
+            if let Err(err) = rpc::write_message(&mut output, &reply) {
+                eprintln!("automata-lsp: {err}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+}
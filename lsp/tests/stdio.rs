@@ -0,0 +1,290 @@
+//! Drives the server over stdio the way an editor would.
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use serde_json::{Value, json};
+
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+}
+
+impl Client {
+    fn spawn() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_automata-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Client {
+            child,
+            stdin,
+            stdout,
+            next_id: 0,
+        }
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            self.stdout.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        self.stdout.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        loop {
+            let message = self.receive();
+            if message["id"] == id {
+                return message;
+            }
+        }
+    }
+}
+
+const URI: &str = "untitled:machine";
+
+fn at(line: u32, character: u32) -> Value {
+    json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } })
+}
+
+#[test]
+fn session() {
+    let mut client = Client::spawn();
+    let init = client.request("initialize", json!({ "capabilities": {} }));
+    assert_eq!(init["result"]["capabilities"]["hoverProvider"], true);
+    client.notify("initialized", json!({}));
+
+    let text = "type = DFA
+Q = {q0, q1}
+E = {a, b}
+F = {q1}
+q0 = q0
+d(q0, a) = q1
+d(q1, c) = q0
+d(q1, b) = q0
+d(q0, b) = q0";
+    client.notify(
+        "textDocument/didOpen",
+        json!({ "textDocument": { "uri": URI, "languageId": "automata", "version": 1, "text": text } }),
+    );
+    let published = client.receive();
+    assert_eq!(published["method"], "textDocument/publishDiagnostics");
+    let diagnostics = published["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({ "line": 6, "character": 6 })
+    );
+    assert_eq!(diagnostics[0]["code"], "A0012");
+
+    // q1 in `d(q0, a) = q1`
+    let definition = client.request("textDocument/definition", at(5, 12));
+    assert_eq!(
+        definition["result"]["range"]["start"],
+        json!({ "line": 1, "character": 9 })
+    );
+
+    let mut references = at(5, 12);
+    references["context"] = json!({ "includeDeclaration": false });
+    let references = client.request("textDocument/references", references);
+    assert_eq!(references["result"].as_array().unwrap().len(), 4);
+
+    let mut rename = at(5, 12);
+    rename["newName"] = json!("done");
+    let rename = client.request("textDocument/rename", rename);
+    assert_eq!(
+        rename["result"]["changes"][URI].as_array().unwrap().len(),
+        5
+    );
+
+    let mut invalid = at(5, 12);
+    invalid["newName"] = json!("q0");
+    assert!(
+        client
+            .request("textDocument/rename", invalid)
+            .get("error")
+            .is_some()
+    );
+
+    let hover = client.request("textDocument/hover", at(1, 5));
+    let hover = hover["result"]["contents"]["value"].as_str().unwrap();
+    assert!(hover.contains("(initial)"));
+    assert!(hover.contains("δ(q0, a) = q1"));
+
+    let tokens = client.request(
+        "textDocument/semanticTokens/full",
+        json!({ "textDocument": { "uri": URI } }),
+    );
+    let data = tokens["result"]["data"].as_array().unwrap();
+    assert_eq!(data.len() % 5, 0);
+    // `type` is the first token, a keyword
+    assert_eq!(
+        data[..5],
+        [json!(0), json!(0), json!(4), json!(0), json!(0)]
+    );
+
+    client.notify(
+        "textDocument/didChange",
+        json!({ "textDocument": { "uri": URI, "version": 2 }, "contentChanges": [{ "text": text.replace("c)", "a)") }] }),
+    );
+    let published = client.receive();
+    assert!(
+        published["params"]["diagnostics"]
+            .as_array()
+            .unwrap()
+            .is_empty()
+    );
+
+    assert_eq!(
+        client.request("shutdown", Value::Null)["result"],
+        Value::Null
+    );
+    client.notify("exit", Value::Null);
+    assert!(client.child.wait().unwrap().success());
+}
+
+#[test]
+fn rename_by_kind() {
+    let dir = std::env::temp_dir().join(format!("automata-lsp-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("states"), "Q = {a, q1}").unwrap();
+    let uri = format!("file://{}", dir.join("main").display());
+    let states_uri = format!("file://{}", dir.join("states").display());
+    let at = |line: u32, character: u32, new_name: &str| {
+        json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character },
+            "newName": new_name,
+        })
+    };
+
+    let mut client = Client::spawn();
+    client.request("initialize", json!({ "capabilities": {} }));
+    let text = "type = NFA
+include \"states\"
+E = {a, b}
+F = {a}
+q0 = a
+d(a, a) = {a, q1}
+d(q1, b) = a";
+    client.notify(
+        "textDocument/didOpen",
+        json!({ "textDocument": { "uri": uri, "languageId": "automata", "version": 1, "text": text } }),
+    );
+    let published = client.receive();
+    assert!(
+        published["params"]["diagnostics"]
+            .as_array()
+            .unwrap()
+            .is_empty()
+    );
+
+    // the state a, not the letter, including its definition in the other file
+    let rename = client.request("textDocument/rename", at(5, 2, "s"));
+    let changes = &rename["result"]["changes"];
+    assert_eq!(changes[&uri].as_array().unwrap().len(), 5);
+    assert_eq!(changes[&states_uri].as_array().unwrap().len(), 1);
+    assert_eq!(
+        changes[&states_uri][0]["range"]["start"],
+        json!({ "line": 0, "character": 5 })
+    );
+
+    let rename = client.request("textDocument/rename", at(5, 5, "c"));
+    let edits = rename["result"]["changes"][&uri].as_array().unwrap();
+    let lines: Vec<_> = edits
+        .iter()
+        .map(|edit| edit["range"]["start"]["line"].as_u64().unwrap())
+        .collect();
+    assert_eq!(lines, [2, 5]);
+
+    // keywords are neither names nor valid new names
+    for (line, character, new_name) in [(4, 0, "s"), (5, 5, "d"), (5, 2, "epsilon"), (5, 2, "Q")] {
+        let rename = client.request("textDocument/rename", at(line, character, new_name));
+        assert!(
+            rename.get("error").is_some(),
+            "{new_name} at {line}:{character}"
+        );
+    }
+
+    client.request("shutdown", Value::Null);
+    client.notify("exit", Value::Null);
+    assert!(client.child.wait().unwrap().success());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn hover_with_includes() {
+    let dir = std::env::temp_dir().join(format!("automata-lsp-hover-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let text = "type = NFA
+include \"states\"
+E = {a, b}
+F = {a}
+d(a, a) = a
+q0 = a";
+    // the state a of the included file ends where the one of F starts
+    let offset = text.find("F = {a}").unwrap() + 5;
+    let states = format!("{}\nQ = {{a}}", "/".repeat(offset - 7));
+    assert_eq!(states.find('a'), Some(offset - 1));
+    std::fs::write(dir.join("states"), states).unwrap();
+    let uri = format!("file://{}", dir.join("main").display());
+
+    let mut client = Client::spawn();
+    client.request("initialize", json!({ "capabilities": {} }));
+    client.notify(
+        "textDocument/didOpen",
+        json!({ "textDocument": { "uri": uri, "languageId": "automata", "version": 1, "text": text } }),
+    );
+    client.receive();
+
+    let hover = client.request(
+        "textDocument/hover",
+        json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": 3, "character": 5 },
+        }),
+    );
+    assert_eq!(
+        hover["result"]["range"],
+        json!({
+            "start": { "line": 3, "character": 5 },
+            "end": { "line": 3, "character": 6 },
+        })
+    );
+
+    client.request("shutdown", Value::Null);
+    client.notify("exit", Value::Null);
+    assert!(client.child.wait().unwrap().success());
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -100,7 +100,8 @@ export type State = string;
 export type Symbol = string;
 export type Letter = string;
 
-export type Span = readonly [number, number];
+// start, end and the id of the file, spans in included files point at the include
+export type Span = readonly [number, number, number?];
 
 export type StateInfo = { readonly definition: Span };
 export type LetterInfo = { readonly definition: Span };
@@ -1,9 +1,11 @@
 use std::collections::HashMap;
 
 use automata::{
-    automatan::{fa::Fa, pda::Pda, tm::Tm}, delta_lower, epsilon, gamma_upper, loader::{self, Context, Machine, Span, Spanned, lexer::Lexer}, sigma_upper
+    automatan::{cfg::Cfg, fa::Fa, pda::Pda, tm::Tm}, delta_lower, epsilon, gamma_upper, loader::{self, Context, Interner, Machine, Span, Spanned, lexer::Lexer, source::{MemoryProvider, SourceMap}}, sigma_upper
 };
 
+use automata::loader::codes::Code;
+use automata::loader::log::{LogSink, Position, RenderOptions, Sarif};
 use wasm_bindgen::prelude::wasm_bindgen;
 
 #[wasm_bindgen]
@@ -45,7 +47,7 @@ pub fn lex(input: &str) -> Vec<Tok> {
     let mut index_utf16 = 0;
     let mut index_utf8 = 0;
     Lexer::new(input)
-        .map(|Spanned(tok, Span(start_utf8, end_utf8))| {
+        .map(|Spanned(tok, Span(start_utf8, end_utf8, _))| {
             let since_last = &input[index_utf8..start_utf8];
             let since_start = &input[start_utf8..end_utf8];
 
@@ -78,6 +80,9 @@ pub fn lex(input: &str) -> Vec<Tok> {
                 Token::Dash => Kind::Punc,
                 Token::Star => Kind::Punc,
                 Token::And => Kind::Punc,
+                Token::Slash => Kind::Punc,
+                Token::Percent => Kind::Punc,
+                Token::DotDot => Kind::Punc,
                 Token::LSmallArrow => Kind::Punc,
                 Token::LBigArrow => Kind::Punc,
                 Token::Comment(_) => Kind::Comment,
@@ -137,14 +142,68 @@ pub enum LogLevel {
 pub struct CompileLog {
     pub level: LogLevel,
     pub message: String,
+    /// UTF-16 offsets into `file`.
     pub start: Option<usize>,
     pub end: Option<usize>,
+    /// The included file the entry is in, `None` for the input itself.
+    pub file: Option<String>,
+    /// What to show next to the primary span.
+    pub label: Option<String>,
+    /// Other spans related to the entry.
+    pub labels: Vec<CompileLabel>,
+    /// Notes and helps without a span.
+    pub notes: Vec<CompileNote>,
+    /// The diagnostic code, like `A0012`.
+    pub code: Option<String>,
+    /// Edits of the input suggested by the entry, for quick-fixes.
+    pub fixes: Vec<CompileFix>,
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone)]
+pub struct CompileLabel {
+    /// UTF-16 offsets into `file`.
+    pub start: usize,
+    pub end: usize,
+    /// The included file the label is in, `None` for the input itself.
+    pub file: Option<String>,
+    pub message: String,
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone)]
+pub struct CompileNote {
+    pub level: LogLevel,
+    pub message: String,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub enum Applicability {
+    MachineApplicable = "machine_applicable",
+    MaybeIncorrect = "maybe_incorrect",
+    HasPlaceholders = "has_placeholders",
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone)]
+pub struct CompileFix {
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String,
+    pub applicability: Applicability,
 }
 
 #[wasm_bindgen(getter_with_clone)]
 pub struct CompileResult {
     pub log: Vec<CompileLog>,
     pub ansi_log: String,
+    /// The log as HTML, styled by `html_style`.
+    pub html_log: String,
+    /// The log as JSON lines, see `Logs::to_json_lines`.
+    pub json_log: String,
+    /// The log as a SARIF 2.1.0 document.
+    pub sarif_log: String,
     pub machine: Option<String>,
 }
 
@@ -158,6 +217,7 @@ impl<'a> FixupSpan for Machine<'a>{
             Machine::Fa(fa) => fa.fixup(func),
             Machine::Pda(pda) => pda.fixup(func),
             Machine::Tm(tm) => tm.fixup(func),
+            Machine::Cfg(cfg) => cfg.fixup(func),
         }
     }
 }
@@ -200,13 +260,73 @@ impl<'a> FixupSpan for Tm<'a>{
 
 
 
+impl<'a> FixupSpan for Cfg<'a>{
+    fn fixup(&mut self, mut func: impl FnMut(Span) -> Span) {
+        self.alphabet.values_mut().for_each(|v| v.definition = func(v.definition));
+        self.variables.values_mut().for_each(|v| v.definition = func(v.definition));
+        self.productions.values_mut().flat_map(|v|v.iter_mut()).for_each(|p|{
+            p.production = func(p.production);
+            p.rule = func(p.rule);
+        });
+    }
+}
+
+/// The stylesheet for the classes of `CompileResult::html_log`.
+#[wasm_bindgen]
+pub fn html_style() -> String {
+    loader::log::HTML_STYLE.to_string()
+}
+
 #[wasm_bindgen]
 pub fn compile(input: &str) -> CompileResult {
-    let mut ctx = Context::new(input);
+    let interner = Interner::default();
+    compile_with(Context::new(input, &interner).resilient(), input)
+}
+
+/// Applies the fixes of `input` which are certain to be right, like adding
+/// missing commas.
+#[wasm_bindgen]
+pub fn fix(input: &str) -> String {
+    let interner = Interner::default();
+    let mut ctx = Context::new(input, &interner).resilient();
+    automata::loader::parse_universal(&mut ctx);
+    loader::log::apply_suggestions(input, Default::default(), ctx.logs().entries())
+}
+
+/// Compiles `input` which may include the files of `files`, a JSON object of
+/// paths to their contents. If `files` isn't such an object it's reported in
+/// the logs and `input` is compiled without any files to include.
+#[wasm_bindgen]
+pub fn compile_with_files(input: &str, files: &str) -> CompileResult {
+    let (files, error) = match serde_json::from_str(files) {
+        Ok(files) => (files, None),
+        Err(err) => (HashMap::new(), Some(err)),
+    };
+    let sources = SourceMap::load("main", input, &mut MemoryProvider(files));
+    let mut ctx = Context::with_sources(&sources).resilient();
+    if let Some(err) = error {
+        ctx.emit_error_locless(format!("invalid included files: {err}"))
+            .code(Code::INVALID_FILES);
+    }
+    compile_with(ctx, input)
+}
+
+fn compile_with<'a>(mut ctx: Context<'a>, input: &str) -> CompileResult {
     let result = automata::loader::parse_universal(&mut ctx);
 
+    // the editor only shows the input, so spans of the machine in included
+    // files point at the include they came from
+    let utf16 = |span: Span| {
+        let span = ctx.root_span(span);
+        Span(
+            Position::new(input, span.0).utf16_offset,
+            Position::new(input, span.1).utf16_offset,
+            span.2,
+        )
+    };
+
     let machine = result.map(|mut result| {
-        result.fixup(|span|Span(input[..span.0].chars().map(char::len_utf16).sum(), input[..span.1].chars().map(char::len_utf16).sum()));
+        result.fixup(utf16);
         serde_json::to_string(&result).unwrap()
     });
 
@@ -215,30 +335,84 @@ pub fn compile(input: &str) -> CompileResult {
         writeln!(&mut s, "{e}").unwrap();
         s
     });
+    let html_options = RenderOptions::new().html();
+    let html_log = ctx.logs_display().map(|e| e.options(html_options).to_string()).collect();
+    let json_log = ctx.logs().to_json_lines(ctx.files());
+    let sarif_log = Sarif::new().add(ctx.logs(), ctx.files()).to_string();
+
+    let level = |level| match level {
+        loader::log::LogLevel::Help => LogLevel::Help,
+        loader::log::LogLevel::Info => LogLevel::Info,
+        loader::log::LogLevel::Warning => LogLevel::Warning,
+        loader::log::LogLevel::Error => LogLevel::Error,
+    };
+    // entries name the file they are in, with offsets into it
+    let file = |span: Span| {
+        (span.2 != Default::default()).then(|| ctx.file(span.2).name.to_string())
+    };
+    let local = |span: Span| {
+        let src = ctx.file(span.2).src;
+        (Position::new(src, span.0).utf16_offset, Position::new(src, span.1).utf16_offset)
+    };
 
     let log = ctx
-        .into_logs()
-        .into_entries()
+        .logs()
+        .entries()
+        .iter()
         .map(|e| CompileLog {
-            level: match e.level {
-                loader::log::LogLevel::Help => LogLevel::Help,
-                loader::log::LogLevel::Info => LogLevel::Info,
-                loader::log::LogLevel::Warning => LogLevel::Warning,
-                loader::log::LogLevel::Error => LogLevel::Error,
-            },
-            message: e.message,
-            start: e
-                .span
-                .map(|span| input[..span.0].chars().map(char::len_utf16).sum()),
-            end: e
-                .span
-                .map(|span| input[..span.1].chars().map(char::len_utf16).sum()),
+            level: level(e.level),
+            message: e.message.clone(),
+            start: e.span.map(|span| local(span).0),
+            end: e.span.map(|span| local(span).1),
+            file: e.span.and_then(file),
+            label: e.label.clone(),
+            labels: e
+                .labels
+                .iter()
+                .map(|label| CompileLabel {
+                    start: local(label.span).0,
+                    end: local(label.span).1,
+                    file: file(label.span),
+                    message: label.message.clone(),
+                })
+                .collect(),
+            notes: e
+                .notes
+                .iter()
+                .map(|note| CompileNote {
+                    level: level(note.level),
+                    message: note.message.clone(),
+                })
+                .collect(),
+            code: e.code.map(|code| code.to_string()),
+            fixes: e
+                .suggestions
+                .iter()
+                .filter(|suggestion| suggestion.span.2 == Default::default())
+                .map(|suggestion| CompileFix {
+                    start: utf16(suggestion.span).0,
+                    end: utf16(suggestion.span).1,
+                    replacement: suggestion.replacement.clone(),
+                    applicability: match suggestion.applicability {
+                        loader::log::Applicability::MachineApplicable => {
+                            Applicability::MachineApplicable
+                        }
+                        loader::log::Applicability::MaybeIncorrect => Applicability::MaybeIncorrect,
+                        loader::log::Applicability::HasPlaceholders => {
+                            Applicability::HasPlaceholders
+                        }
+                    },
+                })
+                .collect(),
         })
         .collect();
 
     CompileResult {
         log,
         ansi_log,
+        html_log,
+        json_log,
+        sarif_log,
         machine,
     }
 }